@@ -18,12 +18,10 @@ bin_save_path=$proj_path
 bin="$bin_save_path/$bin_name"
 updater="$bin_save_path/$updater_name"
 
-# 图片保存路径
-img_save_path="$proj_path/"
-
+# 只安装可执行文件，
+# 配置、壁纸与日志放在XDG目录中，由wow自己创建
 mkdir -p "$proj_path"
 mkdir -p "$bin_save_path"
-mkdir -p "$img_save_path"
 
 if command -v curl >/dev/null 2>&1; then
   curl -L "$bin_url" -o "$bin"
@@ -48,10 +46,15 @@ echo "export PATH=\"$proj_path:\$PATH\"" >>"$CONFIG_FILE"
 
 echo ""
 echo "安装路径: $proj_path"
+echo "配置文件: ${XDG_CONFIG_HOME:-$HOME/.config}/wow/wow.conf"
+echo "壁纸与日志: ${XDG_DATA_HOME:-$HOME/.local/share}/wow"
 echo "使用：\`source $CONFIG_FILE\` 来激活环境变量"
 
-touch "$proj_path/wow-run"
-echo "nohup $bin run >/dev/null 2>&1 &" >"$proj_path/wow-run"
+# 后台运行，日志由wow写入数据目录
+printf '#!/bin/sh\nnohup "%s" run >/dev/null 2>&1 &\n' "$bin" >"$proj_path/wow-run"
 chmod +x "$proj_path/wow-run"
 
-echo "使用\`wow-run\`开启自动更新"
+# 安装清单，`wow bye`只删除其中的文件
+printf "%s\n" "$bin_name" "$updater_name" "wow-run" >"$proj_path/.installed"
+
+echo "使用\`wow-run\`开启自动更新，或\`wow service install\`随登录启动"
@@ -0,0 +1,193 @@
+use super::config::Config;
+use super::ipc;
+use super::paths::Dirs;
+use super::status::Status;
+use clap::ValueEnum;
+use serde_json::{Value, json};
+use std::io::Write;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::select;
+use tokio::time::{interval, sleep};
+
+#[derive(ValueEnum, Clone, Copy, PartialEq)]
+pub enum BarFormat {
+    /// Waybar自定义模块，`return-type: json`
+    Waybar,
+    /// i3bar协议
+    I3bar,
+    /// 纯文本，适用于polybar等
+    Text,
+}
+
+/// 持续输出状态栏内容，
+/// 状态由守护进程推送，
+/// 守护进程未运行时显示配置中的状态并等待其启动
+pub async fn run(format: BarFormat, dirs: &Dirs, config_path: &str) {
+    if format == BarFormat::I3bar {
+        println!("{{\"version\":1}}");
+        println!("[");
+    }
+    let mut last = String::new();
+    let sock = ipc::socket_path(&dirs.runtime);
+    loop {
+        match ipc::subscribe(&sock).await {
+            Ok(mut lines) => {
+                let mut status = Value::Null;
+                // 倒计时每分钟刷新一次
+                let mut tick = interval(Duration::from_secs(60));
+                loop {
+                    select! {
+                        line = lines.next_line() => match line {
+                            Ok(Some(l)) => status = serde_json::from_str(&l).unwrap_or(Value::Null),
+                            _ => break,
+                        },
+                        _ = tick.tick() => {}
+                    }
+                    if !status.is_null() {
+                        emit(render(format, &status), &mut last);
+                    }
+                }
+            }
+            Err(_) => {
+                let mut config = Config::default();
+                config.load(config_path);
+                let status = Status::collect(&config, &dirs.data).to_json();
+                emit(render(format, &status), &mut last);
+                sleep(Duration::from_secs(10)).await;
+            }
+        }
+    }
+}
+
+fn emit(line: String, last: &mut String) {
+    if line == *last {
+        return;
+    }
+    println!("{}", line);
+    let _ = std::io::stdout().flush();
+    *last = line;
+}
+
+/// 将`wow status --json`格式的状态转换为一行状态栏内容
+pub fn render(format: BarFormat, status: &Value) -> String {
+    let running = status["running"].as_bool().unwrap_or(false);
+    // 之后成功更新过时不再算作错误
+    let has_error = !status["last_error"].is_null()
+        && status["last_error_at"].as_u64() >= status["last_success"].as_u64();
+    let title = match status["title"].as_str() {
+        Some(t) if !t.is_empty() => t.to_string(),
+        _ => std::path::Path::new(status["image"].as_str().unwrap_or(""))
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or("wow".to_string()),
+    };
+    let countdown = match status["next_update"].as_u64() {
+        Some(t) if running => {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            short_duration(t.saturating_sub(now))
+        }
+        _ => "off".to_string(),
+    };
+    let text = format!("{} {}", title, countdown);
+
+    let mut tooltip = vec![title.clone()];
+    if let Some(c) = status["copyright"].as_str().filter(|c| !c.is_empty()) {
+        tooltip.push(c.to_string());
+    }
+    tooltip.push(format!(
+        "来源: {}",
+        status["source_name"].as_str().unwrap_or("")
+    ));
+    tooltip.push(if running {
+        format!("下次更新: {}后", countdown)
+    } else {
+        "守护进程未运行".to_string()
+    });
+    if let Some(e) = status["last_error"].as_str() {
+        tooltip.push(format!("上次错误: {}", e.replace('\n', " ")));
+    }
+    let class = if !running {
+        "stopped"
+    } else if has_error {
+        "error"
+    } else {
+        "running"
+    };
+
+    match format {
+        BarFormat::Waybar => json!({
+            "text": text,
+            "alt": class,
+            "class": class,
+            "tooltip": tooltip.join("\n"),
+        })
+        .to_string(),
+        BarFormat::I3bar => {
+            let mut block = json!({
+                "name": "wow",
+                "full_text": text,
+                "short_text": countdown,
+            });
+            if has_error {
+                block["urgent"] = json!(true);
+            }
+            format!("[{}],", block)
+        }
+        BarFormat::Text => text,
+    }
+}
+
+/// `2h15m`
+fn short_duration(secs: u64) -> String {
+    let (h, m) = (secs / 3600, secs % 3600 / 60);
+    match (h, m) {
+        (0, 0) => "<1m".to_string(),
+        (0, m) => format!("{}m", m),
+        (h, m) => format!("{}h{}m", h, m),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(last_success: u64, last_error_at: u64) -> Value {
+        json!({
+            "running": true,
+            "title": "海",
+            "source_name": "必应每日图片",
+            "next_update": 0,
+            "last_success": last_success,
+            "last_error": "网络错误",
+            "last_error_at": last_error_at,
+        })
+    }
+
+    #[test]
+    fn error_then_success() {
+        let s = status(200, 100);
+        let waybar: Value = serde_json::from_str(&render(BarFormat::Waybar, &s)).unwrap();
+        assert_eq!(waybar["class"], "running");
+        let i3bar = render(BarFormat::I3bar, &s);
+        assert!(!i3bar.contains("urgent"), "{}", i3bar);
+    }
+
+    #[test]
+    fn success_then_error() {
+        let s = status(100, 200);
+        let waybar: Value = serde_json::from_str(&render(BarFormat::Waybar, &s)).unwrap();
+        assert_eq!(waybar["class"], "error");
+        assert!(
+            waybar["tooltip"]
+                .as_str()
+                .unwrap()
+                .contains("上次错误: 网络错误")
+        );
+        let i3bar = render(BarFormat::I3bar, &s);
+        let block: Value = serde_json::from_str(i3bar.trim_end_matches(',')).unwrap();
+        assert_eq!(block[0]["urgent"], true);
+    }
+}
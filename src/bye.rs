@@ -0,0 +1,318 @@
+use super::error::{Error, Result};
+use super::paths::{self, Dirs};
+use super::service;
+use super::status;
+use std::fs;
+use std::path::Path;
+
+// setup.sh的安装目录，相对于主目录
+const SETUP_DIR: &str = "Pictures/wow";
+// setup.sh写入的安装清单，每行一个安装的文件名
+const MANIFEST: &str = ".installed";
+// 没有安装清单的旧版setup.sh安装的文件
+const LEGACY_FILES: [&str; 3] = ["wow", "updater", "wow-run"];
+
+/// `wow bye`要做的清理
+pub struct Plan {
+    // 运行中的守护进程
+    pid: Option<u32>,
+    // 服务文件
+    services: Vec<String>,
+    // 包含setup.sh添加的PATH的shell配置文件
+    rc_files: Vec<String>,
+    path_line: String,
+    // 要删除的文件与目录
+    remove: Vec<String>,
+    // setup.sh创建的安装目录，删除后为空时一并删除
+    setup_dir: Option<String>,
+    // 不是setup.sh安装的可执行文件，由包管理器等卸载
+    kept_exe: Option<String>,
+}
+
+impl Plan {
+    /// `config_path`为使用的配置文件，
+    /// `pid`为运行中的守护进程，
+    /// `keep_images`时保留壁纸与收藏
+    pub fn new(
+        dirs: &Dirs,
+        config_path: &str,
+        pid: Option<u32>,
+        keep_images: bool,
+    ) -> Result<Self> {
+        let mut plan = Plan::files(
+            dirs,
+            &paths::home()?,
+            paths::shared_home(),
+            config_path,
+            keep_images,
+        )?;
+        plan.pid = pid;
+        plan.services = service::installed()?;
+        Ok(plan)
+    }
+
+    /// 要修改与删除的文件，
+    /// `shared`为目录中可能有其它程序的文件
+    fn files(
+        dirs: &Dirs,
+        home: &str,
+        shared: bool,
+        config_path: &str,
+        keep_images: bool,
+    ) -> Result<Self> {
+        // 与setup.sh中的`echo "export PATH=\"$proj_path:\$PATH\""`一致
+        let path_line = format!("export PATH=\"{}:$PATH\"", dirs.exe);
+        let rc_files = [".bashrc", ".zshrc"]
+            .iter()
+            .map(|f| format!("{}/{}", home, f))
+            .filter(|f| fs::read_to_string(f).is_ok_and(|c| c.lines().any(|l| l == path_line)))
+            .collect();
+        let installed = setup_files(dirs, home);
+
+        let mut remove: Vec<String> = vec![];
+        let mut dirs_to_clean = vec![&dirs.config, &dirs.data, &dirs.cache, &dirs.runtime];
+        dirs_to_clean.dedup();
+        for dir in dirs_to_clean {
+            if !Path::new(dir).exists() {
+                continue;
+            }
+            // XDG下的`wow`目录由wow创建，可以整个删除，
+            // `WOW_HOME`与安装目录中可能有其它文件，只删除wow的
+            let owned = !shared && dir != &dirs.exe;
+            if owned && !(keep_images && dir == &dirs.data) {
+                remove.push(dir.clone());
+                continue;
+            }
+            for e in fs::read_dir(dir).map_err(|e| Error::io(&format!("无法读取{}", dir), e))? {
+                let Ok(e) = e else {
+                    continue;
+                };
+                let name = e.file_name().to_string_lossy().to_string();
+                let image = name == "liked" || paths::is_wallpaper(&name);
+                let ours = owned || image || paths::is_wow_file(&name);
+                if !ours || (keep_images && image) || installed.iter().flatten().any(|f| *f == name)
+                {
+                    continue;
+                }
+                remove.push(e.path().to_string_lossy().to_string());
+            }
+        }
+
+        // `--config`指定的配置文件与其备份
+        for path in [config_path.to_string(), format!("{}.bak", config_path)] {
+            let covered = remove.iter().any(|r| Path::new(&path).starts_with(r));
+            if !config_path.is_empty() && Path::new(&path).is_file() && !covered {
+                remove.push(path);
+            }
+        }
+
+        let mut kept_exe = None;
+        match &installed {
+            Some(files) => {
+                for name in files {
+                    let path = format!("{}/{}", dirs.exe, name);
+                    if Path::new(&path).exists() {
+                        remove.push(path);
+                    }
+                }
+            }
+            None => {
+                kept_exe = std::env::current_exe()
+                    .ok()
+                    .map(|e| e.to_string_lossy().to_string());
+            }
+        }
+
+        Ok(Plan {
+            pid: None,
+            services: vec![],
+            rc_files,
+            path_line,
+            remove,
+            setup_dir: installed.map(|_| dirs.exe.clone()),
+            kept_exe,
+        })
+    }
+
+    pub fn print(&self) {
+        if let Some(pid) = self.pid {
+            println!("  停止守护进程 PID {}", pid);
+        }
+        for s in &self.services {
+            println!("  停用并删除服务 {}", s);
+        }
+        for f in &self.rc_files {
+            println!("  从{}中删除 {}", f, self.path_line);
+        }
+        for p in &self.remove {
+            println!("  删除 {}", p);
+        }
+        if let Some(exe) = &self.kept_exe {
+            println!("  保留 {}，它不是setup.sh安装的，请用安装它的方式卸载", exe);
+        }
+    }
+
+    pub fn execute(&self) -> Result<()> {
+        // 服务管理的守护进程随服务停止
+        service::uninstall()?;
+        if let Some(pid) = self.pid {
+            status::stop(pid)?;
+        }
+        self.remove_files()
+    }
+
+    /// 从shell配置文件中删除PATH，
+    /// 再删除文件与目录
+    fn remove_files(&self) -> Result<()> {
+        for f in &self.rc_files {
+            let content =
+                fs::read_to_string(f).map_err(|e| Error::io(&format!("无法读取{}", f), e))?;
+            let kept: Vec<&str> = content.lines().filter(|l| *l != self.path_line).collect();
+            let mut kept = kept.join("\n");
+            if content.ends_with('\n') {
+                kept.push('\n');
+            }
+            fs::write(f, kept).map_err(|e| Error::io(&format!("无法写入{}", f), e))?;
+        }
+        for p in &self.remove {
+            let res = if Path::new(p).is_dir() {
+                fs::remove_dir_all(p)
+            } else {
+                fs::remove_file(p)
+            };
+            match res {
+                Ok(_) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(Error::io(&format!("无法删除{}", p), e)),
+            }
+        }
+        // 只删除空目录
+        if let Some(dir) = &self.setup_dir {
+            let _ = fs::remove_dir(dir);
+        }
+        Ok(())
+    }
+}
+
+/// setup.sh安装到`dirs.exe`中的文件名，
+/// 不是setup.sh安装时为None
+fn setup_files(dirs: &Dirs, home: &str) -> Option<Vec<String>> {
+    let manifest = format!("{}/{}", dirs.exe, MANIFEST);
+    if let Ok(content) = fs::read_to_string(&manifest) {
+        let mut files: Vec<String> = content
+            .lines()
+            .map(str::trim)
+            // 只接受安装目录中的文件名
+            .filter(|l| !l.is_empty() && !l.contains('/') && *l != "." && *l != "..")
+            .map(str::to_string)
+            .collect();
+        files.push(MANIFEST.to_string());
+        return Some(files);
+    }
+    (Path::new(&dirs.exe) == Path::new(home).join(SETUP_DIR))
+        .then(|| LEGACY_FILES.iter().map(|f| f.to_string()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("wow-bye-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.to_string_lossy().to_string()
+    }
+
+    /// 主目录`home`下XDG布局的目录，
+    /// 可执行文件由setup.sh安装
+    fn setup(home: &str) -> Dirs {
+        let dirs = Dirs {
+            config: format!("{}/.config/wow", home),
+            data: format!("{}/.local/share/wow", home),
+            cache: format!("{}/.cache/wow", home),
+            runtime: format!("{}/.cache/wow", home),
+            exe: format!("{}/{}", home, SETUP_DIR),
+        };
+        for d in [&dirs.config, &dirs.data, &dirs.cache, &dirs.exe] {
+            fs::create_dir_all(d).unwrap();
+        }
+        fs::write(dirs.config_file(), "").unwrap();
+        fs::write(format!("{}/1700000000.jpg", dirs.data), "").unwrap();
+        for f in ["wow", "wow-run"] {
+            fs::write(format!("{}/{}", dirs.exe, f), "").unwrap();
+        }
+        fs::write(
+            format!("{}/{}", dirs.exe, MANIFEST),
+            "wow\nwow-run\n../.bashrc\n",
+        )
+        .unwrap();
+        dirs
+    }
+
+    #[test]
+    fn path_line() {
+        let home = temp_dir("rc");
+        let dirs = setup(&home);
+        let line = format!("export PATH=\"{}/Pictures/wow:$PATH\"", home);
+        let bashrc = format!("{}/.bashrc", home);
+        let zshrc = format!("{}/.zshrc", home);
+        fs::write(
+            &bashrc,
+            format!("alias ll='ls -l'\n{}\nexport EDITOR=vi\n", line),
+        )
+        .unwrap();
+        // 只有完全相同的行才是setup.sh添加的
+        fs::write(&zshrc, format!("# {}\n{} # mine\n", line, line)).unwrap();
+
+        let plan = Plan::files(&dirs, &home, false, &dirs.config_file(), false).unwrap();
+        assert_eq!(plan.path_line, line);
+        assert_eq!(plan.rc_files, [bashrc.as_str()]);
+        assert_eq!(plan.setup_dir.as_deref(), Some(dirs.exe.as_str()));
+        assert!(plan.kept_exe.is_none());
+        let mut remove = plan.remove.clone();
+        remove.sort();
+        let mut expected = vec![
+            dirs.config.clone(),
+            dirs.data.clone(),
+            dirs.cache.clone(),
+            format!("{}/wow", dirs.exe),
+            format!("{}/wow-run", dirs.exe),
+            format!("{}/{}", dirs.exe, MANIFEST),
+        ];
+        expected.sort();
+        assert_eq!(remove, expected);
+
+        plan.remove_files().unwrap();
+        assert_eq!(
+            fs::read_to_string(&bashrc).unwrap(),
+            "alias ll='ls -l'\nexport EDITOR=vi\n"
+        );
+        assert_eq!(
+            fs::read_to_string(&zshrc).unwrap(),
+            format!("# {}\n{} # mine\n", line, line)
+        );
+        for p in expected {
+            assert!(!Path::new(&p).exists(), "{}", p);
+        }
+        assert!(!Path::new(&dirs.exe).exists());
+    }
+
+    #[test]
+    fn custom_config() {
+        let home = temp_dir("config");
+        let dirs = setup(&home);
+        let custom = format!("{}/my.conf", home);
+        fs::write(&custom, "").unwrap();
+        fs::write(format!("{}.bak", custom), "").unwrap();
+        let plan = Plan::files(&dirs, &home, false, &custom, true).unwrap();
+        assert!(plan.remove.contains(&custom));
+        assert!(plan.remove.contains(&format!("{}.bak", custom)));
+        // 保留壁纸时只删除数据目录中wow的文件
+        assert!(!plan.remove.contains(&dirs.data));
+
+        // 在删除的目录中时不重复列出
+        let plan = Plan::files(&dirs, &home, false, &dirs.config_file(), false).unwrap();
+        assert!(!plan.remove.contains(&dirs.config_file()));
+    }
+}
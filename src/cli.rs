@@ -0,0 +1,545 @@
+#[cfg(target_family = "unix")]
+use super::bar::BarFormat;
+use super::duration;
+use super::filter;
+use super::hook::HookKind;
+use super::logger::{Level, SystemLog};
+use super::monitor::MonitorMode;
+use super::quiet::{self, Window};
+use super::rule::{self, Orientation};
+use super::sun::SunMode;
+use clap::{Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use std::time::Duration;
+
+/// 自动壁纸更新 ωoω
+///
+/// 不带子命令时，到更新时间则更新壁纸
+#[derive(Parser)]
+#[command(name = "wow", version, disable_help_subcommand = true)]
+pub struct Cli {
+    /// 使用指定的配置文件
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// 显示帮助信息与当前配置
+    Help,
+    /// 开启自动更新
+    Run {
+        /// 忽略运行中标记启动，用于上次异常退出后
+        #[arg(long)]
+        force: bool,
+    },
+    /// 关闭自动更新
+    Stop,
+    /// 查看运行状态
+    Status {
+        /// 以JSON格式输出
+        #[arg(long)]
+        json: bool,
+    },
+    /// 更新壁纸
+    #[command(visible_alias = "next")]
+    Update {
+        /// 本次使用的图片来源，不修改配置
+        #[arg(long, value_parser = clap::value_parser!(u8).range(1..=3))]
+        source: Option<u8>,
+    },
+    /// 收藏当前壁纸
+    Like,
+    /// 持续输出状态栏内容
+    #[cfg(target_family = "unix")]
+    Bar {
+        #[arg(long, value_enum, default_value_t = BarFormat::Waybar)]
+        format: BarFormat,
+    },
+    /// 设置壁纸更新间隔
+    Freq {
+        /// 如90s、30m、2h30m、1d、PT30M，不带单位时为小时
+        #[arg(value_parser = duration::parse)]
+        interval: Option<Duration>,
+        /// 允许设置的最短间隔
+        #[arg(long, value_parser = duration::parse)]
+        min: Option<Duration>,
+        /// 允许设置的最长间隔
+        #[arg(long, value_parser = duration::parse)]
+        max: Option<Duration>,
+    },
+    /// 设置暂缓更新的条件，如安静时段、全屏
+    Quiet {
+        #[command(subcommand)]
+        item: Option<QuietCmd>,
+    },
+    /// 按日出日落切换白天与夜间的壁纸
+    Sun {
+        #[command(subcommand)]
+        item: Option<SunCmd>,
+    },
+    /// 按一天中的时间切换的动态壁纸
+    Dynamic {
+        #[command(subcommand)]
+        item: Option<DynamicCmd>,
+    },
+    /// 设置代理、超时等网络请求
+    Net {
+        #[command(subcommand)]
+        item: Option<NetCmd>,
+    },
+    /// 管理必应来源的镜像
+    Mirror {
+        #[command(subcommand)]
+        item: Option<MirrorCmd>,
+    },
+    /// 检查所有镜像是否可用
+    Doctor,
+    /// 选择壁纸图片来源
+    From {
+        /// 1: 必应随机历史图片，2: 必应每日图片，3: 自定义命令
+        #[arg(value_parser = clap::value_parser!(u8).range(1..=3))]
+        source: Option<u8>,
+        /// 来源3执行的命令，输出图片路径、地址或JSON
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        cmd: Vec<String>,
+    },
+    /// 设置壁纸滤镜
+    Filter {
+        #[command(subcommand)]
+        item: Option<FilterCmd>,
+    },
+    /// 设置锁屏壁纸
+    Lock {
+        #[command(subcommand)]
+        item: Option<LockCmd>,
+    },
+    /// 设置图片筛选规则
+    Rule {
+        #[command(subcommand)]
+        item: Option<RuleCmd>,
+    },
+    /// 设置多显示器壁纸
+    Monitor {
+        /// 以JSON格式输出显示器信息
+        #[arg(long)]
+        json: bool,
+
+        #[command(subcommand)]
+        item: Option<MonitorCmd>,
+    },
+    /// 设置桌面通知
+    Notify {
+        #[command(subcommand)]
+        item: Option<NotifyCmd>,
+    },
+    /// 设置更新壁纸时执行的钩子
+    Hook {
+        #[command(subcommand)]
+        item: Option<HookCmd>,
+    },
+    /// 从壁纸生成终端配色
+    Palette {
+        #[command(subcommand)]
+        item: Option<PaletteCmd>,
+    },
+    /// 查看日志
+    Log {
+        /// 持续输出新写入的日志
+        #[arg(short, long)]
+        follow: bool,
+        /// 只显示不低于该级别的日志
+        #[arg(long, value_enum, default_value_t = Level::Debug)]
+        level: Level,
+        /// 显示最后n行
+        #[arg(short = 'n', long, default_value_t = 50)]
+        lines: usize,
+
+        #[command(subcommand)]
+        item: Option<LogCmd>,
+    },
+    /// 管理开机自启的系统服务
+    Service {
+        #[command(subcommand)]
+        item: ServiceCmd,
+    },
+    /// 升级到最新版本
+    Upgrade {
+        /// 只检查是否有新版本
+        #[arg(long)]
+        check: bool,
+        /// 发布清单的地址，默认读取`WOW_MANIFEST_URL`
+        #[arg(long, value_name = "URL")]
+        manifest: Option<String>,
+    },
+    /// 卸载程序，删除服务、配置、壁纸与日志
+    Bye {
+        /// 保留壁纸与收藏
+        #[arg(long)]
+        keep_images: bool,
+        /// 只列出要删除的内容
+        #[arg(long)]
+        dry_run: bool,
+        /// 不再确认
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// 赞赏
+    Tip,
+    /// 生成shell补全脚本
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, PartialEq)]
+pub enum Switch {
+    On,
+    Off,
+}
+
+impl Switch {
+    pub fn is_on(self) -> bool {
+        self == Switch::On
+    }
+}
+
+/// `#rrggbb`或`off`
+#[derive(Clone)]
+pub struct Color(pub Option<[u8; 3]>);
+
+#[derive(Subcommand)]
+pub enum FilterCmd {
+    /// 亮度倍率，1为原图
+    Brightness {
+        #[arg(value_parser = in_range(0.0, 2.0))]
+        value: f32,
+    },
+    /// 模糊半径(像素)
+    Blur {
+        #[arg(value_parser = in_range(0.0, 100.0))]
+        value: f32,
+    },
+    /// 灰度
+    Gray { state: Switch },
+    /// 着色
+    Tint {
+        /// #rrggbb，off为关闭
+        #[arg(value_parser = color)]
+        color: Color,
+        /// 强度
+        #[arg(value_parser = in_range(0.0, 1.0), default_value_t = 0.3)]
+        strength: f32,
+    },
+    /// 暗角强度
+    Vignette {
+        #[arg(value_parser = in_range(0.0, 1.0))]
+        value: f32,
+    },
+    /// 生成暗色壁纸
+    Dark {
+        state: Switch,
+        /// 暗色壁纸的亮度倍率
+        #[arg(value_parser = in_range(0.0, 1.0))]
+        brightness: Option<f32>,
+    },
+    /// 恢复默认
+    Reset,
+}
+
+#[derive(Subcommand)]
+pub enum LockCmd {
+    /// 锁屏壁纸跟随桌面壁纸更新
+    On,
+    /// 不设置锁屏壁纸
+    Off,
+    /// 模糊半径(像素)
+    Blur {
+        #[arg(value_parser = in_range(0.0, 100.0))]
+        value: f32,
+    },
+    /// 亮度倍率，1为原图
+    Brightness {
+        #[arg(value_parser = in_range(0.0, 2.0))]
+        value: f32,
+    },
+    /// 额外复制到该路径，供登录界面读取，off为不复制
+    Copy { path: String },
+}
+
+#[derive(Subcommand)]
+pub enum RuleCmd {
+    /// 最小宽高(像素)
+    Size { width: u32, height: u32 },
+    /// 宽高比范围，如16:9，0为不限
+    Ratio {
+        #[arg(value_parser = ratio)]
+        min: f32,
+        #[arg(value_parser = ratio)]
+        max: f32,
+    },
+    /// 图片方向
+    Orientation {
+        #[arg(value_enum)]
+        value: Orientation,
+    },
+    /// 最大文件大小(MB)，0为不限
+    Maxsize {
+        #[arg(value_parser = in_range(0.0, f32::MAX))]
+        mb: f32,
+    },
+    /// 不符合规则时重新获取的次数，必应每日图片不重新获取
+    Retry { times: u8 },
+    /// 恢复默认
+    Reset,
+}
+
+#[derive(Subcommand)]
+pub enum MonitorCmd {
+    /// 设置多显示器模式
+    Mode {
+        #[arg(value_enum)]
+        mode: MonitorMode,
+    },
+    /// 显示器使用指定的图片来源，仅each模式
+    Map {
+        output: String,
+        #[arg(value_parser = clap::value_parser!(u8).range(1..=3))]
+        source: u8,
+    },
+    /// 显示器使用全局图片来源
+    Unmap { output: String },
+}
+
+#[derive(Subcommand)]
+pub enum NotifyCmd {
+    /// 壁纸更新后通知
+    On,
+    /// 不通知
+    Off,
+    /// 更新失败时通知
+    Error { state: Switch },
+    /// 以当前壁纸发送一条测试通知
+    Test,
+}
+
+#[derive(Subcommand)]
+pub enum HookCmd {
+    /// 设置钩子命令，off为不执行
+    Set {
+        #[arg(value_enum)]
+        kind: HookKind,
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        cmd: Vec<String>,
+    },
+    /// 钩子的超时时间(秒)
+    Timeout {
+        #[arg(value_parser = clap::value_parser!(u64).range(1..))]
+        secs: u64,
+    },
+    /// 以当前壁纸执行钩子
+    Run {
+        #[arg(value_enum)]
+        kind: HookKind,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum QuietCmd {
+    /// 添加安静时段，如22:00-07:00
+    Add {
+        #[arg(value_parser = quiet::parse_window)]
+        window: Window,
+    },
+    /// 删除安静时段
+    Remove {
+        #[arg(value_parser = quiet::parse_window)]
+        window: Window,
+    },
+    /// 删除所有安静时段
+    Clear,
+    /// 当前窗口全屏时暂缓
+    Fullscreen { state: Switch },
+    /// 有程序阻止空闲时暂缓，如演示、视频会议、屏幕共享
+    Inhibit { state: Switch },
+    /// 检查命令，退出码不为0时暂缓，off为不执行
+    Check {
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        cmd: Vec<String>,
+    },
+    /// 检查现在是否会暂缓更新
+    Test,
+}
+
+#[derive(Subcommand)]
+pub enum SunCmd {
+    /// 开启昼夜切换，需要先设置位置
+    On,
+    /// 关闭昼夜切换
+    Off,
+    /// 所在位置，北纬与东经为正
+    Location {
+        #[arg(allow_negative_numbers = true, value_parser = in_range_f64(-90.0, 90.0))]
+        lat: f64,
+        #[arg(allow_negative_numbers = true, value_parser = in_range_f64(-180.0, 180.0))]
+        lon: f64,
+    },
+    /// 白天在日出后、夜间在日落后多少分钟开始，负数为提前
+    Offset {
+        #[arg(allow_negative_numbers = true, value_parser = clap::value_parser!(i32).range(-180..=180))]
+        dawn: i32,
+        #[arg(allow_negative_numbers = true, value_parser = clap::value_parser!(i32).range(-180..=180))]
+        dusk: i32,
+    },
+    /// 切换图片来源，或在原图与暗色壁纸间切换
+    Mode {
+        #[arg(value_enum)]
+        mode: SunMode,
+    },
+    /// 白天与夜间的图片来源，仅source模式
+    Source {
+        #[arg(value_parser = clap::value_parser!(u8).range(1..=3))]
+        day: u8,
+        #[arg(value_parser = clap::value_parser!(u8).range(1..=3))]
+        night: u8,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum DynamicCmd {
+    /// 导入并开启，文件夹、manifest.json、GNOME XML或macOS动态壁纸.heic
+    Import { path: String },
+    /// 导出为GNOME XML幻灯片
+    Export { path: String },
+    /// 开启动态壁纸，自动更新暂停
+    On,
+    /// 关闭动态壁纸，恢复自动更新
+    Off,
+}
+
+#[derive(Subcommand)]
+pub enum NetCmd {
+    /// http://、https://或socks5://开头的代理，env为读取*_PROXY环境变量，off为不使用
+    Proxy { url: String },
+    /// 连接与读取的超时时间，如10s、1m
+    Timeout {
+        #[arg(value_parser = duration::parse)]
+        connect: Duration,
+        #[arg(value_parser = duration::parse)]
+        read: Duration,
+    },
+    /// 请求使用的User-Agent，default为默认
+    Ua {
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        ua: Vec<String>,
+    },
+    /// 额外信任PEM格式的根证书，off为删除所有
+    Ca { path: String },
+    /// 最多跟随重定向的次数，0为不跟随
+    Redirects { max: u8 },
+    /// 恢复默认
+    Reset,
+}
+
+#[derive(Subcommand)]
+pub enum MirrorCmd {
+    /// 为来源添加一个等价的地址
+    Add {
+        /// 1: 必应随机历史图片，2: 必应每日图片
+        #[arg(value_parser = clap::value_parser!(u8).range(1..=2))]
+        source: u8,
+        url: String,
+    },
+    /// 删除来源的一个地址，每个来源至少保留一个
+    Remove {
+        #[arg(value_parser = clap::value_parser!(u8).range(1..=2))]
+        source: u8,
+        url: String,
+    },
+    /// 恢复默认地址并清空统计
+    Reset,
+}
+
+#[derive(Subcommand)]
+pub enum LogCmd {
+    /// 写入日志文件的最低级别
+    SetLevel {
+        #[arg(value_enum)]
+        level: Level,
+    },
+    /// 日志文件大小上限(MB)与保留的旧日志数
+    Rotate {
+        #[arg(value_parser = in_range(0.01, f32::MAX))]
+        mb: f32,
+        files: u8,
+    },
+    /// 同时写入系统日志
+    System {
+        #[arg(value_enum)]
+        target: SystemLog,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ServiceCmd {
+    /// 安装并启用服务，systemd用户服务或launchd
+    Install {
+        /// 定时执行一次更新，不常驻后台
+        #[arg(long)]
+        timer: bool,
+    },
+    /// 停用并删除服务
+    Uninstall,
+    /// 查看服务状态
+    Status,
+}
+
+#[derive(Subcommand)]
+pub enum PaletteCmd {
+    /// 每次更新壁纸后导出配色
+    On,
+    /// 不导出配色
+    Off,
+    /// 立即从当前壁纸导出配色
+    Now,
+    /// 导出后执行的命令，off为不执行
+    Reload {
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        cmd: Vec<String>,
+    },
+}
+
+fn in_range(min: f32, max: f32) -> impl Fn(&str) -> Result<f32, String> + Clone {
+    move |s: &str| match s.parse::<f32>() {
+        Ok(v) if v >= min && v <= max => Ok(v),
+        Ok(_) if max == f32::MAX => Err(format!("需要不小于{}", min)),
+        Ok(_) => Err(format!("需要在{}到{}之间", min, max)),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn in_range_f64(min: f64, max: f64) -> impl Fn(&str) -> Result<f64, String> + Clone {
+    move |s: &str| match s.parse::<f64>() {
+        Ok(v) if v >= min && v <= max => Ok(v),
+        Ok(_) => Err(format!("需要在{}到{}之间", min, max)),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn color(s: &str) -> Result<Color, String> {
+    if s == "off" {
+        return Ok(Color(None));
+    }
+    match filter::parse_color(s) {
+        Some(c) => Ok(Color(Some(c))),
+        None => Err("需要#rrggbb格式的颜色或off".to_string()),
+    }
+}
+
+fn ratio(s: &str) -> Result<f32, String> {
+    rule::parse_ratio(s).ok_or("需要16:9或1.78格式的宽高比".to_string())
+}
@@ -1,76 +1,247 @@
-use super::wow::ErrInfo;
+use super::dynamic::DynamicConf;
+use super::error::{Error, Result};
+use super::filter::Filter;
+use super::hook::HookConf;
+use super::logger::LogConf;
+use super::meta::ImageMeta;
+use super::mirror::MirrorConf;
+use super::monitor::MonitorConf;
+use super::net::NetConf;
+use super::notify::NotifyConf;
+use super::palette::PaletteConf;
+use super::quiet::QuietConf;
+use super::rule::ImageRule;
+use super::setter::LockConf;
+use super::source::Source;
+use super::sun::SunConf;
 use bincode::{Decode, Encode};
 use std::fs::File;
-use std::time::SystemTime;
+use std::io::Write;
+use std::time::{Duration, SystemTime};
 
 const BING_PAPER_EVERYDAY_URL: &str = "https://bing.img.run/uhd.php";
 const BING_PAPER_RANDOM_URL: &str = "https://bing.img.run/rand_uhd.php";
+/// 执行自定义命令获取图片的来源
+pub const CMD_SOURCE: u8 = 3;
+/// 本地图片，动态壁纸的帧，不能设置为来源
+pub const FILE_SOURCE: u8 = 4;
+/// 更新间隔范围的上下限，
+/// 守护进程每30秒检查一次
+pub const FREQ_LIMIT: (Duration, Duration) =
+    (Duration::from_secs(30), Duration::from_secs(365 * 86400));
 
-#[derive(Encode, Decode)]
-pub struct Config {
+// 配置文件开头的标记与格式版本，
+// 之后为bincode编码的`Config`，
+// 修改`Config`的字段时增加版本，并保留旧版本的解析
+const MAGIC: &[u8; 4] = b"WOW\0";
+const VERSION: u16 = 1;
+
+// 迁移到XDG目录之前的配置文件格式，没有标记与版本
+#[derive(Decode)]
+struct LegacyConfig {
     source: u8,
     // 秒
     freq: usize,
     update_at: SystemTime,
     cur_img: String,
+    _ask_stop: bool,
+    _working: bool,
+}
+
+#[derive(Encode, Decode)]
+pub struct Config {
+    source: u8,
+    // 来源为`CMD_SOURCE`时执行的命令
+    source_cmd: String,
+    freq: Duration,
+    // 允许设置的更新间隔范围
+    freq_min: Duration,
+    freq_max: Duration,
+    update_at: SystemTime,
+    cur_img: String,
+    cur_dark_img: String,
+    // 多显示器、锁屏等额外生成的图片
+    cur_output_imgs: Vec<String>,
+    cur_meta: ImageMeta,
+    last_error: String,
+    last_error_at: SystemTime,
+    // 守护进程的PID
+    pid: u32,
+    filter: Filter,
+    palette: PaletteConf,
+    monitor: MonitorConf,
+    rule: ImageRule,
+    lock: LockConf,
+    notify: NotifyConf,
+    hook: HookConf,
+    log: LogConf,
+    quiet: QuietConf,
+    sun: SunConf,
+    dynamic: DynamicConf,
+    net: NetConf,
+    mirror: MirrorConf,
     pub ask_stop: bool,
     pub working: bool,
 }
 
-impl Config {
-    pub fn default() -> Self {
+impl Default for Config {
+    fn default() -> Self {
         Config {
             source: 1,
-            freq: 4 * 60 * 60,
+            source_cmd: String::new(),
+            freq: Duration::from_secs(4 * 60 * 60),
+            freq_min: Duration::from_secs(5 * 60),
+            freq_max: Duration::from_secs(30 * 86400),
             update_at: SystemTime::now(),
             cur_img: String::new(),
+            cur_dark_img: String::new(),
+            cur_output_imgs: vec![],
+            cur_meta: ImageMeta::default(),
+            last_error: String::new(),
+            last_error_at: SystemTime::UNIX_EPOCH,
+            pid: 0,
+            filter: Filter::default(),
+            palette: PaletteConf::default(),
+            monitor: MonitorConf::default(),
+            rule: ImageRule::default(),
+            lock: LockConf::default(),
+            notify: NotifyConf::default(),
+            hook: HookConf::default(),
+            log: LogConf::default(),
+            quiet: QuietConf::default(),
+            sun: SunConf::default(),
+            dynamic: DynamicConf::default(),
+            net: NetConf::default(),
+            mirror: MirrorConf::default(),
             ask_stop: false,
             working: false,
         }
     }
+}
+
+impl Config {
     /// 从`path`加载配置文件，
     /// 如果发生错误，
     /// 外层将重置配置
     pub fn load(&mut self, path: &str) -> bool {
-        // let mut _config = Config::default();
         let data = match std::fs::read(path) {
             Ok(d) => d,
             Err(_) => {
                 return false;
             }
         };
-        match bincode::decode_from_slice::<Config, bincode::config::Configuration>(
-            &data,
-            bincode::config::standard(),
-        ) {
-            Ok((c, _)) => {
+        match Config::decode(&data) {
+            Some(c) => {
                 *self = c;
                 true
             }
-            Err(_) => false,
+            None => false,
+        }
+    }
+
+    /// 按版本解析配置文件的内容，
+    /// 没有标记时为迁移到XDG目录之前的格式
+    fn decode(data: &[u8]) -> Option<Self> {
+        let Some(rest) = data.strip_prefix(MAGIC) else {
+            return Config::decode_legacy(data);
+        };
+        let (version, body) = rest.split_at_checked(2)?;
+        match u16::from_le_bytes([version[0], version[1]]) {
+            VERSION => bincode::decode_from_slice(body, bincode::config::standard())
+                .ok()
+                .map(|(c, _)| c),
+            _ => None,
+        }
+    }
+
+    /// 之后新增的设置使用默认值
+    fn decode_legacy(data: &[u8]) -> Option<Self> {
+        let (old, len): (LegacyConfig, usize) =
+            bincode::decode_from_slice(data, bincode::config::standard()).ok()?;
+        if len != data.len() {
+            return None;
+        }
+        let mut config = Config::default();
+        config.set_url(old.source);
+        config
+            .set_freq(Duration::from_secs(old.freq as u64).clamp(config.freq_min, config.freq_max));
+        config.set_update_at(old.update_at);
+        config.set_cur_img(&old.cur_img);
+        Some(config)
+    }
+
+    /// 读取`path`处的配置文件，
+    /// 不存在或无法解析时返回错误
+    pub fn open(path: &str) -> Result<Self> {
+        let mut config = Config::default();
+        if config.load(path) {
+            Ok(config)
+        } else {
+            Err(Error::config(&format!("无法读取配置文件{}", path)))
         }
     }
 
     pub fn get_url(&self) -> &'static str {
-        match self.source {
+        Self::url_of(self.source)
+    }
+
+    pub fn url_of(source: u8) -> &'static str {
+        match source {
             1 => BING_PAPER_RANDOM_URL,
             2 => BING_PAPER_EVERYDAY_URL,
+            CMD_SOURCE | FILE_SOURCE => "",
             _ => BING_PAPER_RANDOM_URL,
         }
     }
 
+    /// 编号为`id`的图片来源
+    pub fn image_source(&self, id: u8) -> Source {
+        Source::from_id(id, &self.source_cmd)
+    }
+
+    pub fn name_of(source: u8) -> &'static str {
+        match source {
+            1 => "必应随机历史图片",
+            2 => "必应每日图片",
+            CMD_SOURCE => "自定义命令",
+            FILE_SOURCE => "本地图片",
+            _ => "未知来源｜unknow",
+        }
+    }
+
+    pub fn get_source(&self) -> u8 {
+        self.source
+    }
+
     pub fn set_url(&mut self, i: u8) {
         self.source = i;
     }
 
-    pub fn get_freq(&self) -> usize {
+    pub fn get_source_cmd(&self) -> &str {
+        &self.source_cmd
+    }
+
+    pub fn set_source_cmd(&mut self, cmd: &str) {
+        self.source_cmd = cmd.to_string();
+    }
+
+    pub fn get_freq(&self) -> Duration {
         self.freq
     }
 
-    pub fn set_freq(&mut self, freq: usize) {
+    pub fn set_freq(&mut self, freq: Duration) {
         self.freq = freq;
     }
 
+    pub fn get_freq_bounds(&self) -> (Duration, Duration) {
+        (self.freq_min, self.freq_max)
+    }
+
+    pub fn set_freq_bounds(&mut self, min: Duration, max: Duration) {
+        self.freq_min = min;
+        self.freq_max = max;
+    }
+
     pub fn get_update_at(&self) -> SystemTime {
         self.update_at
     }
@@ -86,15 +257,234 @@ impl Config {
         self.cur_img = cur_img.to_string();
     }
 
-    pub fn flush(&self, path: &str) -> ErrInfo {
-        match File::create(path) {
-            Ok(mut fs) => {
-                match bincode::encode_into_std_write(self, &mut fs, bincode::config::standard()) {
-                    Ok(_) => ErrInfo::empty(),
-                    Err(e) => ErrInfo::new(&format!("{}", e)),
-                }
-            }
-            Err(e) => ErrInfo::new(&format!("{}", e)),
+    pub fn get_cur_dark_img(&self) -> &str {
+        &self.cur_dark_img
+    }
+
+    pub fn set_cur_dark_img(&mut self, cur_dark_img: &str) {
+        self.cur_dark_img = cur_dark_img.to_string();
+    }
+
+    pub fn get_cur_output_imgs(&self) -> &[String] {
+        &self.cur_output_imgs
+    }
+
+    pub fn set_cur_output_imgs(&mut self, imgs: Vec<String>) {
+        self.cur_output_imgs = imgs;
+    }
+
+    pub fn get_cur_meta(&self) -> &ImageMeta {
+        &self.cur_meta
+    }
+
+    pub fn set_cur_meta(&mut self, meta: ImageMeta) {
+        self.cur_meta = meta;
+    }
+
+    pub fn get_last_error(&self) -> &str {
+        &self.last_error
+    }
+
+    pub fn get_last_error_at(&self) -> SystemTime {
+        self.last_error_at
+    }
+
+    pub fn set_last_error(&mut self, err: &str, t: SystemTime) {
+        self.last_error = err.to_string();
+        self.last_error_at = t;
+    }
+
+    pub fn get_pid(&self) -> u32 {
+        self.pid
+    }
+
+    pub fn set_pid(&mut self, pid: u32) {
+        self.pid = pid;
+    }
+
+    pub fn get_filter(&self) -> &Filter {
+        &self.filter
+    }
+
+    pub fn set_filter(&mut self, filter: Filter) {
+        self.filter = filter;
+    }
+
+    pub fn get_palette(&self) -> &PaletteConf {
+        &self.palette
+    }
+
+    pub fn set_palette(&mut self, palette: PaletteConf) {
+        self.palette = palette;
+    }
+
+    pub fn get_monitor(&self) -> &MonitorConf {
+        &self.monitor
+    }
+
+    pub fn set_monitor(&mut self, monitor: MonitorConf) {
+        self.monitor = monitor;
+    }
+
+    pub fn get_rule(&self) -> &ImageRule {
+        &self.rule
+    }
+
+    pub fn set_rule(&mut self, rule: ImageRule) {
+        self.rule = rule;
+    }
+
+    pub fn get_lock(&self) -> &LockConf {
+        &self.lock
+    }
+
+    pub fn set_lock(&mut self, lock: LockConf) {
+        self.lock = lock;
+    }
+
+    pub fn get_notify(&self) -> &NotifyConf {
+        &self.notify
+    }
+
+    pub fn set_notify(&mut self, notify: NotifyConf) {
+        self.notify = notify;
+    }
+
+    pub fn get_hook(&self) -> &HookConf {
+        &self.hook
+    }
+
+    pub fn set_hook(&mut self, hook: HookConf) {
+        self.hook = hook;
+    }
+
+    pub fn get_log(&self) -> &LogConf {
+        &self.log
+    }
+
+    pub fn set_log(&mut self, log: LogConf) {
+        self.log = log;
+    }
+
+    pub fn get_quiet(&self) -> &QuietConf {
+        &self.quiet
+    }
+
+    pub fn set_quiet(&mut self, quiet: QuietConf) {
+        self.quiet = quiet;
+    }
+
+    pub fn get_sun(&self) -> &SunConf {
+        &self.sun
+    }
+
+    pub fn set_sun(&mut self, sun: SunConf) {
+        self.sun = sun;
+    }
+
+    pub fn get_dynamic(&self) -> &DynamicConf {
+        &self.dynamic
+    }
+
+    pub fn set_dynamic(&mut self, dynamic: DynamicConf) {
+        self.dynamic = dynamic;
+    }
+
+    pub fn get_net(&self) -> &NetConf {
+        &self.net
+    }
+
+    pub fn set_net(&mut self, net: NetConf) {
+        self.net = net;
+    }
+
+    pub fn get_mirror(&self) -> &MirrorConf {
+        &self.mirror
+    }
+
+    pub fn set_mirror(&mut self, mirror: MirrorConf) {
+        self.mirror = mirror;
+    }
+
+    pub fn flush(&self, path: &str) -> Result<()> {
+        let mut fs = File::create(path).map_err(|e| Error::io("无法写入配置文件", e))?;
+        let header = fs
+            .write_all(MAGIC)
+            .and_then(|_| fs.write_all(&VERSION.to_le_bytes()));
+        header.map_err(|e| Error::io("无法写入配置文件", e))?;
+        match bincode::encode_into_std_write(self, &mut fs, bincode::config::standard()) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(Error::io("无法写入配置文件", std::io::Error::other(e))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("wow-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join(name).to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn round_trip() {
+        let path = temp_file("round-trip.conf");
+        let mut config = Config::default();
+        config.set_url(2);
+        config.set_freq(Duration::from_secs(3600));
+        config.set_cur_img("/tmp/1700000000.jpg");
+        config.flush(&path).unwrap();
+
+        let data = std::fs::read(&path).unwrap();
+        assert!(data.starts_with(b"WOW\0\x01\0"));
+        let loaded = Config::open(&path).unwrap();
+        assert_eq!(loaded.get_source(), 2);
+        assert_eq!(loaded.get_freq(), Duration::from_secs(3600));
+        assert_eq!(loaded.get_cur_img(), "/tmp/1700000000.jpg");
+    }
+
+    #[test]
+    fn legacy_layout() {
+        #[derive(Encode)]
+        struct Old {
+            source: u8,
+            freq: usize,
+            update_at: SystemTime,
+            cur_img: String,
+            ask_stop: bool,
+            working: bool,
         }
+        let update_at = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let old = Old {
+            source: 2,
+            freq: 7200,
+            update_at,
+            cur_img: "/opt/wow/1700000000.jpg".to_string(),
+            ask_stop: false,
+            working: true,
+        };
+        let data = bincode::encode_to_vec(&old, bincode::config::standard()).unwrap();
+        let config = Config::decode(&data).unwrap();
+        assert_eq!(config.get_source(), 2);
+        assert_eq!(config.get_freq(), Duration::from_secs(7200));
+        assert_eq!(config.get_update_at(), update_at);
+        assert_eq!(config.get_cur_img(), "/opt/wow/1700000000.jpg");
+        assert!(!config.working);
+    }
+
+    #[test]
+    fn unknown_layout() {
+        let mut newer = MAGIC.to_vec();
+        newer.extend((VERSION + 1).to_le_bytes());
+        newer.extend(
+            bincode::encode_to_vec(Config::default(), bincode::config::standard()).unwrap(),
+        );
+        assert!(Config::decode(&newer).is_none());
+        assert!(Config::decode(b"WOW\0").is_none());
+        assert!(Config::decode(b"").is_none());
+        assert!(Config::decode(b"not a config file at all").is_none());
     }
 }
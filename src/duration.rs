@@ -0,0 +1,157 @@
+use std::time::Duration;
+
+/// 解析时长，支持
+/// - `90s` `30m` `2h30m` `1d` `1w`，数字可以带小数
+/// - ISO-8601，如`PT30M` `P1DT2H`
+/// - 不带单位的数字，单位为小时
+pub fn parse(s: &str) -> Result<Duration, String> {
+    let s: String = s.chars().filter(|c| !c.is_whitespace()).collect();
+    if s.is_empty() {
+        return Err("时长不能为空".to_string());
+    }
+    // 不接受`inf`、`NaN`、`1e3`等浮点数写法
+    if s.bytes().all(|b| b.is_ascii_digit() || b == b'.')
+        && let Ok(hours) = s.parse::<f64>()
+    {
+        return from_secs(hours * 3600.0);
+    }
+    if let Some(iso) = s.strip_prefix(['P', 'p']) {
+        return parse_iso(iso);
+    }
+    let secs = sum(&s, |unit| match unit {
+        "s" | "sec" => Some(1.0),
+        "m" | "min" => Some(60.0),
+        "h" => Some(3600.0),
+        "d" => Some(86400.0),
+        "w" => Some(604800.0),
+        _ => None,
+    })
+    .map_err(|e| format!("{}，需要如90s、30m、2h30m、1d或PT30M", e))?;
+    from_secs(secs)
+}
+
+/// `P`之后的部分，
+/// 年和月的长度不固定，不支持
+fn parse_iso(s: &str) -> Result<Duration, String> {
+    let s = s.to_uppercase();
+    let (date, time) = match s.split_once('T') {
+        Some((d, t)) if !t.is_empty() => (d, t),
+        Some(_) => return Err("ISO-8601时长的T之后需要时间".to_string()),
+        None => (s.as_str(), ""),
+    };
+    if date.is_empty() && time.is_empty() {
+        return Err("ISO-8601时长不能为空".to_string());
+    }
+    let date = sum(date, |unit| match unit {
+        "W" => Some(604800.0),
+        "D" => Some(86400.0),
+        _ => None,
+    })
+    .map_err(|e| format!("{}，ISO-8601时长只支持W、D与T之后的H、M、S", e))?;
+    let time = sum(time, |unit| match unit {
+        "H" => Some(3600.0),
+        "M" => Some(60.0),
+        "S" => Some(1.0),
+        _ => None,
+    })
+    .map_err(|e| format!("{}，ISO-8601时长只支持W、D与T之后的H、M、S", e))?;
+    from_secs(date + time)
+}
+
+/// 将`数字单位数字单位...`换算为秒
+fn sum(s: &str, unit_secs: impl Fn(&str) -> Option<f64>) -> Result<f64, String> {
+    let mut total = 0.0;
+    let mut rest = s;
+    while !rest.is_empty() {
+        let num_end = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(rest.len());
+        let unit_end = rest[num_end..]
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .map_or(rest.len(), |i| num_end + i);
+        let (num, unit) = (&rest[..num_end], &rest[num_end..unit_end]);
+        let num: f64 = num
+            .parse()
+            .map_err(|_| format!("无法解析`{}`", &rest[..unit_end]))?;
+        match unit_secs(unit) {
+            Some(secs) => total += num * secs,
+            None if unit.is_empty() => return Err(format!("`{}`缺少单位", num)),
+            None => return Err(format!("不支持的单位`{}`", unit)),
+        }
+        rest = &rest[unit_end..];
+    }
+    Ok(total)
+}
+
+fn from_secs(secs: f64) -> Result<Duration, String> {
+    if secs <= 0.0 {
+        return Err("需要大于0".to_string());
+    }
+    Duration::try_from_secs_f64(secs).map_err(|_| "时长过长".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(s: &str) -> f64 {
+        parse(s).unwrap().as_secs_f64()
+    }
+
+    #[test]
+    fn units() {
+        assert_eq!(secs("90s"), 90.0);
+        assert_eq!(secs("10sec"), 10.0);
+        assert_eq!(secs("30m"), 1800.0);
+        assert_eq!(secs("30min"), 1800.0);
+        assert_eq!(secs("2h30m"), 9000.0);
+        assert_eq!(secs(" 1h 30m "), 5400.0);
+        assert_eq!(secs("1.5h"), 5400.0);
+        assert_eq!(secs("1d"), 86400.0);
+        assert_eq!(secs("1w"), 604800.0);
+        assert_eq!(secs("1w1d1h1m1s"), 694861.0);
+        // 不带单位时为小时
+        assert_eq!(secs("2"), 7200.0);
+        assert_eq!(secs("0.5"), 1800.0);
+    }
+
+    #[test]
+    fn iso() {
+        assert_eq!(secs("PT30M"), 1800.0);
+        assert_eq!(secs("pt1h30m"), 5400.0);
+        assert_eq!(secs("P1DT2H"), 93600.0);
+        assert_eq!(secs("P2W"), 1209600.0);
+        assert_eq!(secs("PT0.5S"), 0.5);
+        for bad in ["P", "PT", "P1DT", "P1M", "P1Y", "PT1D", "P1H"] {
+            assert!(parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        for bad in [
+            "", " ", "h", "1x", "1h5", "1.2.3h", "-1h", "h1", "1hh", "1 H",
+        ] {
+            assert!(parse(bad).is_err(), "{}", bad);
+        }
+        assert_eq!(
+            parse("1h5").unwrap_err(),
+            "`5`缺少单位，需要如90s、30m、2h30m、1d或PT30M"
+        );
+    }
+
+    #[test]
+    fn bounds() {
+        for zero in ["0", "0.0", "0s", "0h0m", "PT0S"] {
+            assert_eq!(parse(zero).unwrap_err(), "需要大于0", "{}", zero);
+        }
+        let huge = "9".repeat(400);
+        for huge in [huge.as_str(), "99999999999999999999w"] {
+            assert_eq!(parse(huge).unwrap_err(), "时长过长", "{}", huge);
+        }
+        for bad in ["-1", "inf", "NaN", "1e3"] {
+            assert!(parse(bad).is_err(), "{}", bad);
+        }
+        assert_eq!(parse("0.001s").unwrap(), Duration::from_millis(1));
+    }
+}
@@ -0,0 +1,842 @@
+use super::duration;
+use super::error::{Error, Result};
+use super::sun;
+use base64::Engine;
+use bincode::{Decode, Encode};
+use chrono::{DateTime, Datelike, Local, NaiveDate, TimeZone, Timelike};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, SystemTime};
+
+const DAY_SECS: u32 = 86400;
+// 日出日落偏移的上限
+const MAX_SUN_OFFSET: u32 = 12 * 3600;
+// 按太阳位置查找时间的步长
+const SOLAR_STEP: u64 = 120;
+// macOS动态壁纸XMP的命名空间
+const APPLE_NS: &str = "http://ns.apple.com/namespace/1.0/";
+
+/// 帧可以跟随的太阳事件
+#[derive(Encode, Decode, Clone, Copy, PartialEq)]
+pub enum SunEvent {
+    Sunrise,
+    Noon,
+    Sunset,
+}
+
+impl SunEvent {
+    fn name(self) -> &'static str {
+        match self {
+            SunEvent::Sunrise => "sunrise",
+            SunEvent::Noon => "noon",
+            SunEvent::Sunset => "sunset",
+        }
+    }
+}
+
+/// 帧完全显示的时刻
+#[derive(Encode, Decode, Clone, PartialEq)]
+pub enum At {
+    // 本地时间，从0点起的秒
+    Clock(u32),
+    // 太阳事件后的秒，可为负，需要`wow sun location`设置的位置
+    Sun(SunEvent, i32),
+    // 太阳的高度角与方位角(度)，方位角从正北顺时针计算，
+    // macOS动态壁纸使用，同样需要位置
+    Solar(f64, f64),
+}
+
+impl At {
+    /// 解析`06:30`、`06:30:15`、`sunrise`、`sunset+30m`、`noon-1h`、`solar:-0.8,270`，
+    /// 文件名中的`06-30`、`06_30`、`0630`也可以
+    pub fn parse(s: &str) -> Option<At> {
+        let s = s.trim().to_lowercase();
+        if let Some(rest) = s.strip_prefix("solar:") {
+            let (altitude, azimuth) = rest.split_once(',')?;
+            let altitude: f64 = altitude.trim().parse().ok()?;
+            let azimuth: f64 = azimuth.trim().parse().ok()?;
+            return ((-90.0..=90.0).contains(&altitude) && (0.0..360.0).contains(&azimuth))
+                .then_some(At::Solar(altitude, azimuth));
+        }
+        for event in [SunEvent::Sunrise, SunEvent::Noon, SunEvent::Sunset] {
+            let Some(rest) = s.strip_prefix(event.name()) else {
+                continue;
+            };
+            let offset = match rest.chars().next() {
+                None => 0,
+                Some(sign @ ('+' | '-')) => {
+                    let secs = duration::parse(&rest[1..]).ok()?.as_secs() as u32;
+                    if secs > MAX_SUN_OFFSET {
+                        return None;
+                    }
+                    if sign == '-' {
+                        -(secs as i32)
+                    } else {
+                        secs as i32
+                    }
+                }
+                _ => return None,
+            };
+            return Some(At::Sun(event, offset));
+        }
+
+        let parts: Vec<&str> = if s.len() == 4 && s.chars().all(|c| c.is_ascii_digit()) {
+            vec![&s[..2], &s[2..]]
+        } else {
+            s.split([':', '-', '_']).collect()
+        };
+        if !(2..=3).contains(&parts.len()) {
+            return None;
+        }
+        let nums: Vec<u32> = parts
+            .iter()
+            .map(|p| p.parse().ok())
+            .collect::<Option<_>>()?;
+        let (h, m, sec) = (nums[0], nums[1], nums.get(2).copied().unwrap_or(0));
+        (h < 24 && m < 60 && sec < 60).then_some(At::Clock(h * 3600 + m * 60 + sec))
+    }
+
+    /// 在本地日期`date`的时间，
+    /// 跟随太阳但没有位置，或当天极昼极夜没有该太阳事件时为None，
+    /// 太阳位置取当天最接近的时刻
+    pub fn resolve(&self, date: NaiveDate, location: Option<(f64, f64)>) -> Option<SystemTime> {
+        let midnight = Local
+            .from_local_datetime(&date.and_hms_opt(0, 0, 0)?)
+            .earliest()
+            .map(SystemTime::from)?;
+        match self {
+            At::Clock(secs) => Some(midnight + Duration::from_secs(*secs as u64)),
+            At::Solar(altitude, azimuth) => {
+                let (lat, lon) = location?;
+                let (altitude, azimuth) = (altitude.to_radians(), azimuth.to_radians());
+                // 与目标位置的夹角越小越接近
+                let closeness = |t: SystemTime| {
+                    let (a, z) = sun::position(t, lat, lon);
+                    let (a, z) = (a.to_radians(), z.to_radians());
+                    a.sin() * altitude.sin() + a.cos() * altitude.cos() * (z - azimuth).cos()
+                };
+                (0..DAY_SECS as u64 / SOLAR_STEP)
+                    .map(|i| midnight + Duration::from_secs(i * SOLAR_STEP))
+                    .max_by(|a, b| closeness(*a).total_cmp(&closeness(*b)))
+            }
+            At::Sun(event, offset) => {
+                let (lat, lon) = location?;
+                let n = (date - NaiveDate::from_ymd_opt(2000, 1, 1)?).num_days();
+                let (rise, set) = sun::sunrise_sunset(n, lat, lon)?;
+                let t = match event {
+                    SunEvent::Sunrise => rise,
+                    SunEvent::Sunset => set,
+                    SunEvent::Noon => rise + set.duration_since(rise).ok()? / 2,
+                };
+                let d = Duration::from_secs(offset.unsigned_abs() as u64);
+                Some(if *offset >= 0 { t + d } else { t - d })
+            }
+        }
+    }
+}
+
+impl fmt::Display for At {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            At::Clock(secs) if secs % 60 == 0 => {
+                write!(f, "{:02}:{:02}", secs / 3600, secs % 3600 / 60)
+            }
+            At::Clock(secs) => write!(
+                f,
+                "{:02}:{:02}:{:02}",
+                secs / 3600,
+                secs % 3600 / 60,
+                secs % 60
+            ),
+            At::Solar(altitude, azimuth) => write!(f, "solar:{},{}", altitude, azimuth),
+            At::Sun(event, 0) => write!(f, "{}", event.name()),
+            At::Sun(event, offset) => {
+                let sign = if *offset < 0 { '-' } else { '+' };
+                let secs = offset.unsigned_abs();
+                if secs % 60 == 0 {
+                    write!(f, "{}{}{}m", event.name(), sign, secs / 60)
+                } else {
+                    write!(f, "{}{}{}s", event.name(), sign, secs)
+                }
+            }
+        }
+    }
+}
+
+#[derive(Encode, Decode, Clone)]
+pub struct Frame {
+    pub at: At,
+    // 图片的绝对路径
+    pub image: String,
+    // 秒，从上一帧渐变到这一帧的时长，
+    // 壁纸在渐变的中点切换
+    pub transition: u32,
+}
+
+/// 按一天中的时间切换的一组壁纸
+#[derive(Encode, Decode, Clone, Default)]
+pub struct DynamicConf {
+    // 开启时暂停自动更新
+    pub enabled: bool,
+    // 导入的文件夹或文件
+    pub name: String,
+    pub frames: Vec<Frame>,
+    // 最近设置的帧的图片
+    pub applied: String,
+}
+
+impl DynamicConf {
+    /// 从文件夹、`manifest.json`、GNOME XML或macOS动态壁纸`.heic`导入，
+    /// `.heic`中的图片提取到`data_dir`中，
+    /// `location`为`wow sun location`设置的位置
+    pub fn import(path: &str, data_dir: &str, location: Option<(f64, f64)>) -> Result<Self> {
+        let p = Path::new(path);
+        let ext = p
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let frames = if p.is_dir() {
+            from_dir(p)?
+        } else {
+            match ext.as_str() {
+                "json" => from_manifest(p)?,
+                "xml" => from_gnome_xml(p)?,
+                "heic" | "heif" => from_heic(p, data_dir)?,
+                _ => {
+                    return Err(Error::config(&format!(
+                        "不支持的动态壁纸{}\n需要文件夹、manifest.json、GNOME XML或.heic",
+                        path
+                    )));
+                }
+            }
+        };
+        if frames.is_empty() {
+            return Err(Error::config(&format!("{}中没有图片", path)));
+        }
+        for f in &frames {
+            if !Path::new(&f.image).is_file() {
+                return Err(Error::config(&format!("找不到图片{}", f.image)));
+            }
+            if matches!(f.at, At::Sun(..) | At::Solar(..)) && location.is_none() {
+                return Err(Error::config(&format!(
+                    "{}跟随太阳\n请先使用`wow sun location <纬度> <经度>`设置位置",
+                    f.at
+                )));
+            }
+        }
+        let mut frames = frames;
+        let today = Local::now().date_naive();
+        frames.sort_by_key(|f| f.at.resolve(today, location));
+        let name = fs::canonicalize(p)
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or(path.to_string());
+        Ok(DynamicConf {
+            enabled: true,
+            name,
+            frames,
+            applied: String::new(),
+        })
+    }
+
+    /// `t`时应显示的帧
+    pub fn frame_at(&self, location: Option<(f64, f64)>, t: SystemTime) -> Option<&Frame> {
+        self.switches(location, t)
+            .into_iter()
+            .rev()
+            .find(|(at, _)| *at <= t)
+            .map(|(_, i)| &self.frames[i])
+    }
+
+    /// `t`之后的下一次切换
+    pub fn next_switch(&self, location: Option<(f64, f64)>, t: SystemTime) -> Option<SystemTime> {
+        self.switches(location, t)
+            .into_iter()
+            .find(|(at, _)| *at > t)
+            .map(|(at, _)| at)
+    }
+
+    /// `t`前后几天按时间排序的切换，
+    /// 为`(时间, 帧序号)`
+    fn switches(&self, location: Option<(f64, f64)>, t: SystemTime) -> Vec<(SystemTime, usize)> {
+        let today = DateTime::<Local>::from(t).date_naive();
+        let mut out = vec![];
+        for date in [today.pred_opt(), Some(today), today.succ_opt()]
+            .into_iter()
+            .flatten()
+        {
+            for (i, f) in self.frames.iter().enumerate() {
+                if let Some(at) = f.at.resolve(date, location) {
+                    out.push((at - Duration::from_secs(f.transition as u64 / 2), i));
+                }
+            }
+        }
+        out.sort_by_key(|(at, _)| *at);
+        out
+    }
+
+    /// 导出为GNOME的XML幻灯片，
+    /// 跟随日出日落的帧按`date`当天计算
+    pub fn to_gnome_xml(&self, location: Option<(f64, f64)>, date: NaiveDate) -> Result<String> {
+        // 按整秒计算，各段时长之和正好为一天
+        let mut frames: Vec<(SystemTime, &Frame)> = self
+            .frames
+            .iter()
+            .filter_map(|f| {
+                let t = f.at.resolve(date, location)?;
+                let secs = t.duration_since(SystemTime::UNIX_EPOCH).ok()?.as_secs();
+                Some((SystemTime::UNIX_EPOCH + Duration::from_secs(secs), f))
+            })
+            .collect();
+        frames.sort_by_key(|(t, _)| *t);
+        let Some((first, _)) = frames.first().copied() else {
+            return Err(Error::config("没有可以导出的帧"));
+        };
+
+        let start = DateTime::<Local>::from(first);
+        let mut out = String::from("<background>\n  <starttime>\n");
+        out += &format!(
+            "    <year>{}</year>\n    <month>{:02}</month>\n    <day>{:02}</day>\n    <hour>{:02}</hour>\n    <minute>{:02}</minute>\n    <second>{:02}</second>\n",
+            start.year(),
+            start.month(),
+            start.day(),
+            start.hour(),
+            start.minute(),
+            start.second()
+        );
+        out += "  </starttime>\n";
+        for (i, (t, f)) in frames.iter().enumerate() {
+            let (next_t, next) = match frames.get(i + 1) {
+                Some((nt, n)) => (*nt, *n),
+                None => (first + Duration::from_secs(DAY_SECS as u64), frames[0].1),
+            };
+            let gap = next_t.duration_since(*t).unwrap_or_default().as_secs();
+            let transition = (next.transition as u64).min(gap);
+            out += &format!(
+                "  <static>\n    <duration>{}.0</duration>\n    <file>{}</file>\n  </static>\n",
+                gap - transition,
+                xml_escape(&f.image)
+            );
+            if transition > 0 {
+                out += &format!(
+                    "  <transition type=\"overlay\">\n    <duration>{}.0</duration>\n    <from>{}</from>\n    <to>{}</to>\n  </transition>\n",
+                    transition,
+                    xml_escape(&f.image),
+                    xml_escape(&next.image)
+                );
+            }
+        }
+        out += "</background>\n";
+        Ok(out)
+    }
+}
+
+/// 文件夹中有`manifest.json`时读取，
+/// 否则以文件名作为时间，如`06-30.jpg`、`sunset.jpg`，
+/// 文件名不是时间时按名称顺序均匀分布在一天中
+fn from_dir(dir: &Path) -> Result<Vec<Frame>> {
+    let manifest = dir.join("manifest.json");
+    if manifest.is_file() {
+        return from_manifest(&manifest);
+    }
+    let entries =
+        fs::read_dir(dir).map_err(|e| Error::io(&format!("无法读取{}", dir.display()), e))?;
+    let mut images: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| {
+            p.extension().is_some_and(|e| {
+                matches!(
+                    e.to_string_lossy().to_lowercase().as_str(),
+                    "jpg" | "jpeg" | "png"
+                )
+            })
+        })
+        .collect();
+    images.sort_by_key(|p| natural_key(&stem(p)));
+
+    let timed: Option<Vec<Frame>> = images
+        .iter()
+        .map(|p| {
+            At::parse(&stem(p)).map(|at| Frame {
+                at,
+                image: absolute(p),
+                transition: 0,
+            })
+        })
+        .collect();
+    if let Some(frames) = timed {
+        return Ok(frames);
+    }
+    let step = DAY_SECS / images.len().max(1) as u32;
+    Ok(images
+        .iter()
+        .enumerate()
+        .map(|(i, p)| Frame {
+            at: At::Clock(i as u32 * step),
+            image: absolute(p),
+            transition: 0,
+        })
+        .collect())
+}
+
+/// `{"frames": [{"at": "sunrise", "image": "dawn.jpg", "transition": "30m"}]}`，
+/// 图片路径相对于清单所在的目录
+fn from_manifest(path: &Path) -> Result<Vec<Frame>> {
+    let data = fs::read(path).map_err(|e| Error::io(&format!("无法读取{}", path.display()), e))?;
+    let v: serde_json::Value = serde_json::from_slice(&data)
+        .map_err(|e| Error::decode(&format!("{}无效", path.display()), e))?;
+    let invalid = |msg: String| Error::Decode {
+        msg: format!("{}无效: {}", path.display(), msg),
+        source: None,
+    };
+    let base = path.parent().unwrap_or(Path::new("."));
+    let mut frames = vec![];
+    for f in v["frames"]
+        .as_array()
+        .ok_or(invalid("缺少frames".to_string()))?
+    {
+        let at = f["at"].as_str().unwrap_or("");
+        let at = At::parse(at).ok_or(invalid(format!("无法解析时间`{}`", at)))?;
+        let image = f["image"]
+            .as_str()
+            .ok_or(invalid("缺少image".to_string()))?;
+        let transition = match &f["transition"] {
+            serde_json::Value::Null => 0,
+            serde_json::Value::String(s) => duration::parse(s).map_err(invalid)?.as_secs() as u32,
+            v => v
+                .as_u64()
+                .ok_or(invalid(format!("无法解析渐变时长`{}`", v)))? as u32,
+        };
+        frames.push(Frame {
+            at,
+            image: absolute(&base.join(image)),
+            transition: transition.min(DAY_SECS),
+        });
+    }
+    Ok(frames)
+}
+
+/// GNOME的`<background>`幻灯片，
+/// 不满一天时循环播放
+fn from_gnome_xml(path: &Path) -> Result<Vec<Frame>> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| Error::io(&format!("无法读取{}", path.display()), e))?;
+    let doc = roxmltree::Document::parse(&text)
+        .map_err(|e| Error::decode(&format!("{}无效", path.display()), e))?;
+    let base = path.parent().unwrap_or(Path::new("."));
+    let child_text = |n: roxmltree::Node, tag: &str| {
+        n.children()
+            .find(|c| c.has_tag_name(tag))
+            .and_then(|c| c.text())
+            .map(|t| t.trim().to_string())
+            .unwrap_or_default()
+    };
+    let secs = |n: roxmltree::Node, tag: &str| child_text(n, tag).parse::<f64>().unwrap_or(0.0);
+
+    // 从开始时间起的秒，图片，渐变时长
+    let mut start = 0.0;
+    let mut cursor = 0.0;
+    let mut pending = 0.0;
+    let mut slides: Vec<(f64, String, f64)> = vec![];
+    for node in doc.root_element().children().filter(|n| n.is_element()) {
+        match node.tag_name().name() {
+            "starttime" => {
+                start =
+                    secs(node, "hour") * 3600.0 + secs(node, "minute") * 60.0 + secs(node, "second")
+            }
+            "static" => {
+                // 多种尺寸时为`<file><size>路径</size></file>`
+                let file = node.children().find(|c| c.has_tag_name("file"));
+                let file = file
+                    .and_then(|f| f.children().find(|c| c.has_tag_name("size")).or(Some(f)))
+                    .and_then(|f| f.text())
+                    .map(|t| t.trim().to_string())
+                    .unwrap_or_default();
+                slides.push((cursor, absolute(&base.join(file)), pending));
+                pending = 0.0;
+                cursor += secs(node, "duration");
+            }
+            "transition" => {
+                pending = secs(node, "duration");
+                cursor += pending;
+            }
+            _ => {}
+        }
+    }
+    // 第一张图片从最后一张渐变而来
+    if let Some(first) = slides.first_mut() {
+        first.2 = pending;
+    }
+
+    let mut frames: Vec<Frame> = vec![];
+    let period = if cursor >= 60.0 {
+        cursor
+    } else {
+        DAY_SECS as f64
+    };
+    let mut offset = 0.0;
+    while offset < DAY_SECS as f64 {
+        for (at, image, transition) in &slides {
+            let t = (start + offset + at) as u32 % DAY_SECS;
+            frames.retain(|f| f.at != At::Clock(t));
+            frames.push(Frame {
+                at: At::Clock(t),
+                image: image.clone(),
+                transition: *transition as u32,
+            });
+        }
+        offset += period;
+    }
+    Ok(frames)
+}
+
+/// macOS动态壁纸，
+/// 时间取自XMP中的`apple_desktop:h24`或`apple_desktop:solar`，
+/// 图片由libheif的`heif-convert`提取
+fn from_heic(path: &Path, data_dir: &str) -> Result<Vec<Frame>> {
+    let data = fs::read(path).map_err(|e| Error::io(&format!("无法读取{}", path.display()), e))?;
+    let timing = heic_timing(&data)?;
+
+    let dir = format!("{}/dynamic/{}", data_dir, stem(path));
+    // 清除上次导入提取的图片
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).map_err(|e| Error::io(&format!("无法创建{}", dir), e))?;
+    match Command::new("heif-convert")
+        .arg(path)
+        .arg(format!("{}/frame.jpg", dir))
+        .output()
+    {
+        Ok(o) if o.status.success() => {}
+        Ok(o) => {
+            return Err(Error::command(
+                "heif-convert执行失败",
+                String::from_utf8_lossy(&o.stderr).trim().to_string(),
+            ));
+        }
+        Err(e) => return Err(Error::command("需要libheif的heif-convert来提取图片", e)),
+    }
+
+    // 一张图片时保存为frame.jpg，多张时为frame-1.jpg、frame-2.jpg...
+    let mut images: Vec<PathBuf> = fs::read_dir(&dir)
+        .map_err(|e| Error::io(&format!("无法读取{}", dir), e))?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| stem(p).starts_with("frame") && p.extension().is_some_and(|e| e == "jpg"))
+        .collect();
+    images.sort_by_key(|p| natural_key(&stem(p)));
+    timing
+        .into_iter()
+        .map(|(at, i)| {
+            let image = images.get(i).ok_or(Error::Decode {
+                msg: format!("{}中没有第{}张图片", path.display(), i + 1),
+                source: None,
+            })?;
+            Ok(Frame {
+                at,
+                image: absolute(image),
+                transition: 0,
+            })
+        })
+        .collect()
+}
+
+/// 帧的时间与图片序号，
+/// `h24`为一天中的比例，`solar`为太阳的高度角与方位角
+fn heic_timing(data: &[u8]) -> Result<Vec<(At, usize)>> {
+    let failed = |msg: &str| Error::Decode {
+        msg: msg.to_string(),
+        source: None,
+    };
+    for (key, list, solar) in [("h24", "ti", false), ("solar", "si", true)] {
+        let Some(encoded) = xmp_packets(data).find_map(|xmp| apple_desktop(xmp, key)) else {
+            continue;
+        };
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(encoded.trim())
+            .map_err(|e| Error::decode("动态壁纸的时间信息无效", e))?;
+        let plist = plist::Value::from_reader(std::io::Cursor::new(bytes))
+            .map_err(|e| Error::decode("动态壁纸的时间信息无效", e))?;
+        let items = plist
+            .as_dictionary()
+            .and_then(|d| d.get(list))
+            .and_then(|v| v.as_array())
+            .ok_or(failed("动态壁纸的时间信息中没有帧"))?;
+        let num = |v: &plist::Value, k: &str| {
+            let v = v.as_dictionary()?.get(k)?;
+            v.as_real().or(v.as_signed_integer().map(|i| i as f64))
+        };
+        let mut out = vec![];
+        for item in items {
+            let i = num(item, "i").ok_or(failed("动态壁纸的帧缺少图片序号"))? as usize;
+            let at = if solar {
+                let (Some(a), Some(z)) = (num(item, "a"), num(item, "z")) else {
+                    return Err(failed("动态壁纸的帧缺少太阳位置"));
+                };
+                At::Solar(a.clamp(-90.0, 90.0), z.rem_euclid(360.0))
+            } else {
+                let t = num(item, "t").unwrap_or(0.0);
+                At::Clock((t * DAY_SECS as f64) as u32 % DAY_SECS)
+            };
+            out.push((at, i));
+        }
+        return Ok(out);
+    }
+    Err(failed("没有找到动态壁纸的时间信息"))
+}
+
+/// 文件中的XMP包，`<x:xmpmeta>...</x:xmpmeta>`
+fn xmp_packets(data: &[u8]) -> impl Iterator<Item = &str> {
+    const OPEN: &[u8] = b"<x:xmpmeta";
+    const CLOSE: &[u8] = b"</x:xmpmeta>";
+    let mut rest = data;
+    std::iter::from_fn(move || {
+        loop {
+            let end = rest.windows(CLOSE.len()).position(|w| w == CLOSE)? + CLOSE.len();
+            // 取结束标签前最近的开始标签，跳过不完整的包
+            let start = rest[..end].windows(OPEN.len()).rposition(|w| w == OPEN);
+            let packet = &rest[start.unwrap_or(end)..end];
+            rest = &rest[end..];
+            if let Ok(xmp) = std::str::from_utf8(packet) {
+                return Some(xmp);
+            }
+        }
+    })
+}
+
+/// XMP中`apple_desktop:key`属性或元素的值
+fn apple_desktop(xmp: &str, key: &str) -> Option<String> {
+    let doc = roxmltree::Document::parse(xmp).ok()?;
+    doc.descendants().filter(|n| n.is_element()).find_map(|n| {
+        if let Some(a) = n
+            .attributes()
+            .find(|a| a.name() == key && a.namespace() == Some(APPLE_NS))
+        {
+            return Some(a.value().to_string());
+        }
+        let tag = n.tag_name();
+        (tag.name() == key && tag.namespace() == Some(APPLE_NS))
+            .then(|| n.text().unwrap_or_default().to_string())
+    })
+}
+
+fn stem(p: &Path) -> String {
+    p.file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+fn absolute(p: &Path) -> String {
+    fs::canonicalize(p)
+        .unwrap_or(p.to_path_buf())
+        .to_string_lossy()
+        .to_string()
+}
+
+/// 按名称中的数字排序，`2`在`10`之前
+fn natural_key(name: &str) -> (String, u64, String) {
+    let digits: String = name
+        .chars()
+        .skip_while(|c| !c.is_ascii_digit())
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    let letters: String = name.chars().filter(|c| !c.is_ascii_digit()).collect();
+    (letters, digits.parse().unwrap_or(0), name.to_string())
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_clock() {
+        assert!(At::parse("06:30") == Some(At::Clock(6 * 3600 + 30 * 60)));
+        assert!(At::parse(" 06:30:15 ") == Some(At::Clock(6 * 3600 + 30 * 60 + 15)));
+        assert!(At::parse("06-30") == Some(At::Clock(6 * 3600 + 30 * 60)));
+        assert!(At::parse("6_30") == Some(At::Clock(6 * 3600 + 30 * 60)));
+        assert!(At::parse("0630") == Some(At::Clock(6 * 3600 + 30 * 60)));
+        assert!(At::parse("00:00") == Some(At::Clock(0)));
+        for bad in ["24:00", "06:60", "06:30:60", "6", "1:2:3:4", "ab:cd", ""] {
+            assert!(At::parse(bad).is_none(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn parse_sun() {
+        assert!(At::parse("sunrise") == Some(At::Sun(SunEvent::Sunrise, 0)));
+        assert!(At::parse("SUNSET+30m") == Some(At::Sun(SunEvent::Sunset, 1800)));
+        assert!(At::parse("noon-1h") == Some(At::Sun(SunEvent::Noon, -3600)));
+        assert!(At::parse("sunset+12h") == Some(At::Sun(SunEvent::Sunset, 12 * 3600)));
+        for bad in ["sunset+13h", "sunrise*1h", "sunrise+", "dusk"] {
+            assert!(At::parse(bad).is_none(), "{}", bad);
+        }
+        assert!(At::parse("solar:-0.5,270") == Some(At::Solar(-0.5, 270.0)));
+        for bad in ["solar:95,10", "solar:10,360", "solar:10", "solar:a,b"] {
+            assert!(At::parse(bad).is_none(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn display_parses_back() {
+        for at in [
+            At::Clock(6 * 3600 + 30 * 60),
+            At::Clock(6 * 3600 + 30 * 60 + 15),
+            At::Sun(SunEvent::Sunrise, 0),
+            At::Sun(SunEvent::Sunset, 1800),
+            At::Sun(SunEvent::Noon, -3600),
+            At::Sun(SunEvent::Noon, 45),
+            At::Solar(-0.5, 270.25),
+        ] {
+            assert!(At::parse(&at.to_string()) == Some(at.clone()), "{}", at);
+        }
+    }
+
+    #[test]
+    fn solar_follows_sun() {
+        let location = Some((51.5, -0.1));
+        let date = NaiveDate::from_ymd_opt(2024, 6, 15).unwrap();
+        let sunset = At::Sun(SunEvent::Sunset, 0)
+            .resolve(date, location)
+            .unwrap();
+        let (altitude, azimuth) = sun::position(sunset, 51.5, -0.1);
+        assert!((altitude + 0.833).abs() < 0.5, "{}", altitude);
+        // 夏季在西北方日落
+        assert!((290.0..320.0).contains(&azimuth), "{}", azimuth);
+
+        let local = DateTime::<Local>::from(sunset).date_naive();
+        let solar = At::Solar(altitude, azimuth)
+            .resolve(local, location)
+            .unwrap();
+        let diff = match solar.duration_since(sunset) {
+            Ok(d) => d,
+            Err(e) => e.duration(),
+        };
+        assert!(diff <= Duration::from_secs(SOLAR_STEP), "{:?}", diff);
+        assert!(At::Solar(altitude, azimuth).resolve(local, None).is_none());
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("wow-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn summary(frames: &[Frame]) -> Vec<(String, String, u32)> {
+        frames
+            .iter()
+            .map(|f| (f.at.to_string(), f.image.clone(), f.transition))
+            .collect()
+    }
+
+    #[test]
+    fn gnome_xml_round_trip() {
+        let dir = temp_dir("gnome");
+        let image = |name: &str| {
+            let p = dir.join(name);
+            fs::write(&p, b"").unwrap();
+            absolute(&p)
+        };
+        let conf = DynamicConf {
+            enabled: true,
+            name: String::new(),
+            frames: vec![
+                Frame {
+                    at: At::Clock(6 * 3600),
+                    image: image("dawn & day.jpg"),
+                    transition: 0,
+                },
+                Frame {
+                    at: At::Clock(12 * 3600),
+                    image: image("noon.jpg"),
+                    transition: 1800,
+                },
+                Frame {
+                    at: At::Clock(20 * 3600 + 15 * 60),
+                    image: image("night.jpg"),
+                    transition: 600,
+                },
+            ],
+            applied: String::new(),
+        };
+        let date = NaiveDate::from_ymd_opt(2024, 6, 15).unwrap();
+        let xml = conf.to_gnome_xml(None, date).unwrap();
+        let path = dir.join("wow.xml");
+        fs::write(&path, xml).unwrap();
+
+        let mut frames = from_gnome_xml(&path).unwrap();
+        frames.sort_by_key(|f| f.at.resolve(date, None));
+        assert_eq!(summary(&frames), summary(&conf.frames));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    fn xmp(key: &str, items: Vec<plist::Dictionary>, list: &str) -> Vec<u8> {
+        let mut root = plist::Dictionary::new();
+        root.insert(
+            list.to_string(),
+            plist::Value::Array(items.into_iter().map(plist::Value::Dictionary).collect()),
+        );
+        let mut bin = vec![];
+        plist::Value::Dictionary(root)
+            .to_writer_binary(&mut bin)
+            .unwrap();
+        let encoded = base64::engine::general_purpose::STANDARD.encode(bin);
+        // 前后是无法按UTF-8解码的图片数据
+        let mut data = vec![0xff, 0xd8, 0x00, 0xc3];
+        data.extend(b"<x:xmpmeta>broken");
+        data.extend([0xfe, 0xff]);
+        data.extend(format!(
+            r#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#"><rdf:Description xmlns:apple_desktop="{}" apple_desktop:{}="{}"/></rdf:RDF></x:xmpmeta>"#,
+            APPLE_NS, key, encoded
+        ).as_bytes());
+        data.extend([0x80, 0x81]);
+        data
+    }
+
+    fn item(pairs: &[(&str, f64)]) -> plist::Dictionary {
+        let mut d = plist::Dictionary::new();
+        for (k, v) in pairs {
+            let v = if *k == "i" {
+                plist::Value::Integer((*v as i64).into())
+            } else {
+                plist::Value::Real(*v)
+            };
+            d.insert(k.to_string(), v);
+        }
+        d
+    }
+
+    #[test]
+    fn heic_h24_timing() {
+        let data = xmp(
+            "h24",
+            vec![
+                item(&[("i", 1.0), ("t", 0.75)]),
+                item(&[("i", 0.0), ("t", 0.25)]),
+            ],
+            "ti",
+        );
+        let timing = heic_timing(&data).unwrap();
+        assert!(timing == vec![(At::Clock(18 * 3600), 1), (At::Clock(6 * 3600), 0)]);
+    }
+
+    #[test]
+    fn heic_solar_timing() {
+        let data = xmp(
+            "solar",
+            vec![
+                item(&[("i", 0.0), ("a", -0.34), ("z", 270.9)]),
+                item(&[("i", 1.0), ("a", 60.0), ("z", -180.0)]),
+            ],
+            "si",
+        );
+        let timing = heic_timing(&data).unwrap();
+        assert!(timing == vec![(At::Solar(-0.34, 270.9), 0), (At::Solar(60.0, 180.0), 1)]);
+        assert!(heic_timing(b"\xff\xd8 no metadata").is_err());
+    }
+}
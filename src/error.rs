@@ -0,0 +1,195 @@
+use super::logger;
+use std::fmt;
+
+type Source = Box<dyn std::error::Error + Send + Sync>;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// wow的错误，
+/// `msg`说明出错的操作，`source`为底层原因
+#[derive(Debug)]
+pub enum Error {
+    /// 无法连接、超时等请求失败
+    Network { msg: String, source: Option<Source> },
+    /// 服务器返回了错误状态码
+    Http { status: u16, url: String },
+    /// 读写文件失败
+    Io { msg: String, source: std::io::Error },
+    /// 图片、JSON等内容无法解析
+    Decode { msg: String, source: Option<Source> },
+    /// 图片不符合筛选规则或被钩子拒绝
+    Rejected { msg: String, source: Option<Source> },
+    /// 设置壁纸失败
+    Setter { msg: String, source: Option<Source> },
+    /// 钩子、自定义来源等用户命令执行失败
+    Command { msg: String, source: Option<Source> },
+    /// 无法通过D-Bus发送桌面通知
+    Notify { msg: String, source: Option<Source> },
+    /// 配置无效或缺失
+    Config(String),
+    /// 未到更新时间，不是真正的错误
+    NotDue,
+}
+
+impl Error {
+    pub fn network(msg: &str, e: impl Into<Source>) -> Self {
+        Error::Network {
+            msg: msg.to_string(),
+            source: Some(e.into()),
+        }
+    }
+
+    pub fn io(msg: &str, e: std::io::Error) -> Self {
+        Error::Io {
+            msg: msg.to_string(),
+            source: e,
+        }
+    }
+
+    pub fn decode(msg: &str, e: impl Into<Source>) -> Self {
+        Error::Decode {
+            msg: msg.to_string(),
+            source: Some(e.into()),
+        }
+    }
+
+    pub fn setter(msg: &str, e: impl Into<Source>) -> Self {
+        Error::Setter {
+            msg: msg.to_string(),
+            source: Some(e.into()),
+        }
+    }
+
+    pub fn command(msg: &str, e: impl Into<Source>) -> Self {
+        Error::Command {
+            msg: msg.to_string(),
+            source: Some(e.into()),
+        }
+    }
+
+    pub fn notify(msg: &str, e: impl Into<Source>) -> Self {
+        Error::Notify {
+            msg: msg.to_string(),
+            source: Some(e.into()),
+        }
+    }
+
+    pub fn config(msg: &str) -> Self {
+        Error::Config(msg.to_string())
+    }
+
+    pub fn rejected(msg: &str) -> Self {
+        Error::Rejected {
+            msg: msg.to_string(),
+            source: None,
+        }
+    }
+
+    /// 进程的退出码，
+    /// 0为成功，2留给clap的命令行参数错误
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Config(_) => 10,
+            Error::NotDue => 3,
+            Error::Network { .. } | Error::Http { .. } => 4,
+            Error::Io { .. } => 5,
+            Error::Decode { .. } | Error::Rejected { .. } => 6,
+            Error::Setter { .. } => 7,
+            Error::Command { .. } => 8,
+            Error::Notify { .. } => 9,
+        }
+    }
+
+    /// 包含所有底层原因的说明
+    pub fn report(&self) -> String {
+        let mut out = self.to_string();
+        let mut source = std::error::Error::source(self);
+        while let Some(e) = source {
+            out += &format!("\n  原因: {}", e);
+            source = e.source();
+        }
+        out
+    }
+
+    /// 写入日志，
+    /// 未到更新时间只作为调试信息
+    pub fn log(&self) {
+        match self {
+            Error::NotDue => logger::debug(&format!(
+                "{}\n使用`wow update`手动更新\n使用`wow help`获取帮助",
+                self
+            )),
+            _ => logger::error(&self.report()),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Network { msg, .. }
+            | Error::Io { msg, .. }
+            | Error::Decode { msg, .. }
+            | Error::Rejected { msg, .. }
+            | Error::Setter { msg, .. }
+            | Error::Command { msg, .. }
+            | Error::Notify { msg, .. }
+            | Error::Config(msg) => write!(f, "{}", msg),
+            Error::Http { status, url } => write!(f, "服务器返回{}: {}", status, url),
+            Error::NotDue => write!(f, "未到更新时间"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Network { source, .. }
+            | Error::Decode { source, .. }
+            | Error::Rejected { source, .. }
+            | Error::Setter { source, .. }
+            | Error::Command { source, .. }
+            | Error::Notify { source, .. } => source
+                .as_deref()
+                .map(|e| e as &(dyn std::error::Error + 'static)),
+            Error::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// 打印并记录不影响后续流程的错误
+pub trait PrintErr {
+    fn print_err(self);
+}
+
+impl<T> PrintErr for Result<T> {
+    fn print_err(self) {
+        if let Err(e) = self {
+            e.log();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exit_codes() {
+        let io = || std::io::Error::other("io");
+        let codes = [
+            Error::config("配置").exit_code(),
+            Error::NotDue.exit_code(),
+            Error::network("网络", io()).exit_code(),
+            Error::io("文件", io()).exit_code(),
+            Error::rejected("图片").exit_code(),
+            Error::setter("壁纸", io()).exit_code(),
+            Error::command("命令", io()).exit_code(),
+            Error::notify("通知", io()).exit_code(),
+        ];
+        assert_eq!(codes, [10, 3, 4, 5, 6, 7, 8, 9]);
+        // 不与成功和clap的命令行参数错误混淆
+        assert!(codes.iter().all(|c| *c > 2));
+    }
+}
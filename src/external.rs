@@ -0,0 +1,204 @@
+use super::error::{Error, Result};
+use super::filter;
+use image::{ImageFormat, ImageReader};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::Command;
+use tokio::time::timeout;
+
+// 等待命令输出的最长时间
+const CMD_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// 自定义命令给出的图片
+pub struct Picked {
+    // 网络地址或本地路径
+    pub location: Location,
+    pub title: String,
+    pub copyright: String,
+}
+
+pub enum Location {
+    Url(String),
+    Path(String),
+}
+
+/// 执行`cmd`获取图片，
+/// 命令在标准输出打印图片路径、地址，
+/// 或`{"url"|"path", "title", "copyright"}`格式的JSON
+pub async fn pick(cmd: &str) -> Result<Picked> {
+    if cmd.is_empty() {
+        return Err(Error::config(
+            "没有设置图片来源命令\n使用`wow from 3 <命令>`设置",
+        ));
+    }
+    let child = match Command::new("sh")
+        .arg("-c")
+        .arg(cmd)
+        .stdout(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+    {
+        Ok(c) => c,
+        Err(e) => return Err(Error::command("图片来源命令执行失败", e)),
+    };
+    let out = match timeout(CMD_TIMEOUT, child.wait_with_output()).await {
+        Ok(Ok(o)) if o.status.success() => o.stdout,
+        Ok(Ok(o)) => {
+            return Err(Error::command("图片来源命令执行失败", o.status.to_string()));
+        }
+        Ok(Err(e)) => return Err(Error::command("图片来源命令执行失败", e)),
+        Err(_) => {
+            return Err(Error::command(
+                "图片来源命令执行失败",
+                format!("{}秒内未结束", CMD_TIMEOUT.as_secs()),
+            ));
+        }
+    };
+    parse(&String::from_utf8_lossy(&out))
+}
+
+fn parse(out: &str) -> Result<Picked> {
+    let out = out.trim();
+    if out.starts_with('{') {
+        let v: serde_json::Value = match serde_json::from_str(out) {
+            Ok(v) => v,
+            Err(e) => return Err(Error::decode("图片来源命令输出的JSON无效", e)),
+        };
+        let text = |k: &str| v[k].as_str().unwrap_or("").to_string();
+        let location = match (v["url"].as_str(), v["path"].as_str()) {
+            (Some(u), _) if !u.is_empty() => location(u),
+            (_, Some(p)) if !p.is_empty() => location(p),
+            _ => {
+                return Err(Error::Decode {
+                    msg: "图片来源命令输出的JSON中没有url或path".to_string(),
+                    source: None,
+                });
+            }
+        };
+        return Ok(Picked {
+            location,
+            title: text("title"),
+            copyright: text("copyright"),
+        });
+    }
+    match out.lines().map(str::trim).find(|l| !l.is_empty()) {
+        Some(l) => Ok(Picked {
+            location: location(l),
+            title: String::new(),
+            copyright: String::new(),
+        }),
+        None => Err(Error::Command {
+            msg: "图片来源命令没有输出图片".to_string(),
+            source: None,
+        }),
+    }
+}
+
+fn location(s: &str) -> Location {
+    if s.starts_with("http://") || s.starts_with("https://") {
+        Location::Url(s.to_string())
+    } else {
+        Location::Path(s.strip_prefix("file://").unwrap_or(s).to_string())
+    }
+}
+
+/// 自定义来源可能给出PNG等格式的图片，
+/// 统一转换为JPEG
+pub fn to_jpeg(path: &str) -> Result<()> {
+    let reader = ImageReader::open(path)
+        .and_then(|r| r.with_guessed_format())
+        .map_err(|e| Error::io("无法读取图片", e))?;
+    if reader.format() == Some(ImageFormat::Jpeg) {
+        return Ok(());
+    }
+    match reader.decode() {
+        Ok(img) => filter::save_jpeg(&img.to_rgb8(), path),
+        Err(e) => Err(Error::decode("不是有效的图片", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path_of(p: &Picked) -> Option<&str> {
+        match &p.location {
+            Location::Path(p) => Some(p),
+            Location::Url(_) => None,
+        }
+    }
+
+    fn url_of(p: &Picked) -> Option<&str> {
+        match &p.location {
+            Location::Url(u) => Some(u),
+            Location::Path(_) => None,
+        }
+    }
+
+    #[test]
+    fn plain_output() {
+        let p = parse("\n  /home/me/Pictures/sea.jpg  \nignored\n").unwrap();
+        assert_eq!(path_of(&p), Some("/home/me/Pictures/sea.jpg"));
+        assert!(p.title.is_empty() && p.copyright.is_empty());
+
+        let p = parse("file:///tmp/a b.png\n").unwrap();
+        assert_eq!(path_of(&p), Some("/tmp/a b.png"));
+        let p = parse("https://example.com/a.jpg?w=3840\n").unwrap();
+        assert_eq!(url_of(&p), Some("https://example.com/a.jpg?w=3840"));
+        let p = parse("http://example.com/a.jpg").unwrap();
+        assert_eq!(url_of(&p), Some("http://example.com/a.jpg"));
+    }
+
+    #[test]
+    fn json_output() {
+        let p =
+            parse(r#"{"url": "https://example.com/a.jpg", "title": "海", "copyright": "© 某人"}"#)
+                .unwrap();
+        assert_eq!(url_of(&p), Some("https://example.com/a.jpg"));
+        assert_eq!(p.title, "海");
+        assert_eq!(p.copyright, "© 某人");
+
+        let p = parse(r#"{"path": "/tmp/a.jpg", "title": 1}"#).unwrap();
+        assert_eq!(path_of(&p), Some("/tmp/a.jpg"));
+        assert!(p.title.is_empty());
+        // url为空时使用path
+        let p = parse(r#"{"url": "", "path": "file:///tmp/b.jpg"}"#).unwrap();
+        assert_eq!(path_of(&p), Some("/tmp/b.jpg"));
+    }
+
+    #[test]
+    fn malformed_output() {
+        for out in ["", " \n\n"] {
+            let e = parse(out).err().unwrap();
+            assert!(matches!(e, Error::Command { .. }), "{:?}: {}", out, e);
+        }
+        for out in [
+            "{",
+            r#"{"url": "https://example.com/a.jpg""#,
+            r#"{"title": "海"}"#,
+            r#"{"url": 1, "path": null}"#,
+        ] {
+            let e = parse(out).err().unwrap();
+            assert!(matches!(e, Error::Decode { .. }), "{:?}: {}", out, e);
+        }
+    }
+
+    #[tokio::test]
+    async fn run_command() {
+        let p = pick("printf '%s\\n' /tmp/sea.jpg").await.unwrap();
+        assert_eq!(path_of(&p), Some("/tmp/sea.jpg"));
+        let p = pick(r#"echo '{"url": "https://example.com/a.jpg", "title": "海"}'"#)
+            .await
+            .unwrap();
+        assert_eq!(url_of(&p), Some("https://example.com/a.jpg"));
+        assert_eq!(p.title, "海");
+
+        let e = pick("").await.err().unwrap();
+        assert!(matches!(e, Error::Config(_)), "{}", e);
+        // 退出码不为0或没有输出
+        for cmd in ["echo /tmp/sea.jpg; exit 3", "true"] {
+            let e = pick(cmd).await.err().unwrap();
+            assert!(matches!(e, Error::Command { .. }), "{:?}: {}", cmd, e);
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use super::error::{Error, Result};
+use bincode::{Decode, Encode};
+use image::{ImageReader, RgbImage, codecs::jpeg::JpegEncoder, imageops};
+use std::fs::File;
+
+const JPEG_QUALITY: u8 = 92;
+
+/// 壁纸后处理滤镜
+///
+/// 各项取默认值时不做任何处理
+#[derive(Encode, Decode, Clone)]
+pub struct Filter {
+    // 亮度倍率，1.0为原图
+    pub brightness: f32,
+    // 模糊半径(像素)，0为不模糊
+    pub blur: f32,
+    pub grayscale: bool,
+    // 着色颜色与强度(0~1)
+    pub tint: Option<([u8; 3], f32)>,
+    // 暗角强度(0~1)
+    pub vignette: f32,
+    pub dark: DarkVariant,
+}
+
+/// 自动生成的暗色壁纸
+#[derive(Encode, Decode, Clone)]
+pub struct DarkVariant {
+    pub enabled: bool,
+    // 在滤镜结果上再乘的亮度倍率
+    pub brightness: f32,
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Filter {
+            brightness: 1.0,
+            blur: 0.0,
+            grayscale: false,
+            tint: None,
+            vignette: 0.0,
+            dark: DarkVariant {
+                enabled: false,
+                brightness: 0.5,
+            },
+        }
+    }
+}
+
+impl Filter {
+    /// 是否需要对原图做处理
+    pub fn is_identity(&self) -> bool {
+        self.brightness == 1.0
+            && self.blur <= 0.0
+            && !self.grayscale
+            && self.tint.is_none()
+            && self.vignette <= 0.0
+    }
+
+    /// 对`path`处的图片应用滤镜并覆盖原文件，
+    /// 开启暗色壁纸时另存到`dark_path`
+    pub fn process(&self, path: &str, dark_path: &str) -> Result<()> {
+        if self.is_identity() && !self.dark.enabled {
+            return Ok(());
+        }
+        let mut img = load_rgb(path)?;
+        if !self.is_identity() {
+            img = self.apply(img);
+            save_jpeg(&img, path)?;
+        }
+        if self.dark.enabled {
+            scale_brightness(&mut img, self.dark.brightness);
+            return save_jpeg(&img, dark_path);
+        }
+        Ok(())
+    }
+
+    /// 依次应用模糊、亮度、灰度、着色、暗角
+    pub fn apply(&self, img: RgbImage) -> RgbImage {
+        let mut img = if self.blur > 0.0 {
+            imageops::fast_blur(&img, self.blur)
+        } else {
+            img
+        };
+
+        let (w, h) = img.dimensions();
+        let (cx, cy) = (w as f32 / 2.0, h as f32 / 2.0);
+        let max_d2 = cx * cx + cy * cy;
+        for (x, y, p) in img.enumerate_pixels_mut() {
+            let mut c = [p[0] as f32, p[1] as f32, p[2] as f32];
+            for v in c.iter_mut() {
+                *v *= self.brightness;
+            }
+            if self.grayscale {
+                let l = 0.299 * c[0] + 0.587 * c[1] + 0.114 * c[2];
+                c = [l, l, l];
+            }
+            if let Some((color, strength)) = self.tint {
+                for (v, t) in c.iter_mut().zip(color) {
+                    *v = *v * (1.0 - strength) + t as f32 * strength;
+                }
+            }
+            if self.vignette > 0.0 {
+                let (dx, dy) = (x as f32 - cx, y as f32 - cy);
+                let k = 1.0 - self.vignette * (dx * dx + dy * dy) / max_d2;
+                for v in c.iter_mut() {
+                    *v *= k;
+                }
+            }
+            for (i, v) in c.iter().enumerate() {
+                p[i] = v.round().clamp(0.0, 255.0) as u8;
+            }
+        }
+        img
+    }
+}
+
+pub fn load_rgb(path: &str) -> Result<RgbImage> {
+    let reader = ImageReader::open(path)
+        .and_then(|r| r.with_guessed_format())
+        .map_err(|e| Error::io(&format!("can't open image {}", path), e))?;
+    match reader.decode() {
+        Ok(img) => Ok(img.into_rgb8()),
+        Err(e) => Err(Error::decode(
+            &format!("error when decode image {}", path),
+            e,
+        )),
+    }
+}
+
+pub fn save_jpeg(img: &RgbImage, path: &str) -> Result<()> {
+    let fs = File::create(path)
+        .map_err(|e| Error::io(&format!("can't open the file to save image {}", path), e))?;
+    match img.write_with_encoder(JpegEncoder::new_with_quality(fs, JPEG_QUALITY)) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(Error::decode("error when saving image", e)),
+    }
+}
+
+fn scale_brightness(img: &mut RgbImage, k: f32) {
+    for p in img.pixels_mut() {
+        for v in p.0.iter_mut() {
+            *v = (*v as f32 * k).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+}
+
+/// 解析`#rrggbb`或`rrggbb`格式的颜色
+pub fn parse_color(s: &str) -> Option<[u8; 3]> {
+    let s = s.strip_prefix('#').unwrap_or(s);
+    if s.len() != 6 || !s.is_ascii() {
+        return None;
+    }
+    let mut c = [0u8; 3];
+    for (i, v) in c.iter_mut().enumerate() {
+        *v = u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(c)
+}
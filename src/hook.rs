@@ -0,0 +1,273 @@
+use super::config::Config;
+use super::error::{Error, Result};
+use super::logger;
+use super::meta::ImageMeta;
+use bincode::{Decode, Encode};
+use clap::ValueEnum;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::time::timeout;
+
+/// 钩子执行的时机
+#[derive(ValueEnum, Clone, Copy, PartialEq)]
+pub enum HookKind {
+    /// 获取图片之前
+    PreFetch,
+    /// 图片下载完成、处理之前
+    PostDownload,
+    /// 设置壁纸之前，失败时放弃这张图片
+    PreApply,
+    /// 设置壁纸之后
+    PostApply,
+}
+
+impl HookKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            HookKind::PreFetch => "pre_fetch",
+            HookKind::PostDownload => "post_download",
+            HookKind::PreApply => "pre_apply",
+            HookKind::PostApply => "post_apply",
+        }
+    }
+
+    pub fn all() -> [HookKind; 4] {
+        [
+            HookKind::PreFetch,
+            HookKind::PostDownload,
+            HookKind::PreApply,
+            HookKind::PostApply,
+        ]
+    }
+}
+
+/// 更新壁纸各阶段执行的命令，
+/// 通过`sh -c`执行，为空则不执行
+#[derive(Encode, Decode, Clone)]
+pub struct HookConf {
+    pre_fetch: String,
+    post_download: String,
+    pre_apply: String,
+    post_apply: String,
+    // 秒，超时后结束钩子并视为失败
+    pub timeout: u64,
+}
+
+impl Default for HookConf {
+    fn default() -> Self {
+        HookConf {
+            pre_fetch: String::new(),
+            post_download: String::new(),
+            pre_apply: String::new(),
+            post_apply: String::new(),
+            timeout: 30,
+        }
+    }
+}
+
+impl HookConf {
+    pub fn get(&self, kind: HookKind) -> &str {
+        match kind {
+            HookKind::PreFetch => &self.pre_fetch,
+            HookKind::PostDownload => &self.post_download,
+            HookKind::PreApply => &self.pre_apply,
+            HookKind::PostApply => &self.post_apply,
+        }
+    }
+
+    pub fn set(&mut self, kind: HookKind, cmd: String) {
+        match kind {
+            HookKind::PreFetch => self.pre_fetch = cmd,
+            HookKind::PostDownload => self.post_download = cmd,
+            HookKind::PreApply => self.pre_apply = cmd,
+            HookKind::PostApply => self.post_apply = cmd,
+        }
+    }
+
+    /// 执行`kind`对应的钩子，
+    /// 图片路径与信息通过`WOW_*`环境变量和标准输入的JSON传递，
+    /// 未设置时直接返回
+    pub async fn run(&self, kind: HookKind, image: &str, meta: &ImageMeta) -> Result<()> {
+        let cmd = self.get(kind);
+        if cmd.is_empty() {
+            return Ok(());
+        }
+        let vars = [
+            ("WOW_HOOK", kind.name().to_string()),
+            ("WOW_IMAGE", image.to_string()),
+            ("WOW_TITLE", meta.title.clone()),
+            ("WOW_COPYRIGHT", meta.copyright.clone()),
+            ("WOW_URL", meta.url.clone()),
+            ("WOW_SOURCE", meta.source.to_string()),
+            ("WOW_SOURCE_NAME", Config::name_of(meta.source).to_string()),
+            ("WOW_WIDTH", meta.width.to_string()),
+            ("WOW_HEIGHT", meta.height.to_string()),
+            ("WOW_SIZE", meta.size.to_string()),
+        ];
+        let json = serde_json::json!({
+            "hook": kind.name(),
+            "image": image,
+            "title": meta.title,
+            "copyright": meta.copyright,
+            "url": meta.url,
+            "source": meta.source,
+            "source_name": Config::name_of(meta.source),
+            "width": meta.width,
+            "height": meta.height,
+            "size": meta.size,
+        });
+
+        let failed = |e: &dyn std::fmt::Display| {
+            Err(Error::command(
+                &format!("钩子{}执行失败", kind.name()),
+                e.to_string(),
+            ))
+        };
+        let mut child = match Command::new("sh")
+            .arg("-c")
+            .arg(cmd)
+            .envs(vars)
+            .stdin(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+        {
+            Ok(c) => c,
+            Err(e) => return failed(&e),
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            // 钩子可能不读取标准输入
+            let _ = stdin.write_all((json.to_string() + "\n").as_bytes()).await;
+        }
+
+        match timeout(Duration::from_secs(self.timeout), child.wait()).await {
+            Ok(Ok(s)) if s.success() => {
+                logger::info(&format!("钩子{}执行完成: {}", kind.name(), s));
+                Ok(())
+            }
+            Ok(Ok(s)) => failed(&s),
+            Ok(Err(e)) => failed(&e),
+            Err(_) => {
+                let _ = child.kill().await;
+                failed(&format!("{}秒内未结束", self.timeout))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::Instant;
+
+    fn temp_dir(name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("wow-hook-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.to_string_lossy().to_string()
+    }
+
+    fn meta() -> ImageMeta {
+        ImageMeta {
+            url: "https://example.com/a.jpg".to_string(),
+            title: "海 \"边\"".to_string(),
+            copyright: "© 某人".to_string(),
+            source: 2,
+            width: 1920,
+            height: 1080,
+            size: 12345,
+        }
+    }
+
+    fn hook(kind: HookKind, cmd: &str) -> HookConf {
+        let mut conf = HookConf::default();
+        conf.set(kind, cmd.to_string());
+        conf
+    }
+
+    #[tokio::test]
+    async fn env_and_stdin() {
+        let dir = temp_dir("env");
+        let cmd = format!("env | grep ^WOW_ | sort > {dir}/env; cat > {dir}/stdin");
+        hook(HookKind::PostApply, &cmd)
+            .run(HookKind::PostApply, "/tmp/1700000000.jpg", &meta())
+            .await
+            .unwrap();
+
+        let env = fs::read_to_string(format!("{}/env", dir)).unwrap();
+        let expected = [
+            "WOW_COPYRIGHT=© 某人",
+            "WOW_HEIGHT=1080",
+            "WOW_HOOK=post_apply",
+            "WOW_IMAGE=/tmp/1700000000.jpg",
+            "WOW_SIZE=12345",
+            "WOW_SOURCE=2",
+            &format!("WOW_SOURCE_NAME={}", Config::name_of(2)),
+            "WOW_TITLE=海 \"边\"",
+            "WOW_URL=https://example.com/a.jpg",
+            "WOW_WIDTH=1920",
+        ];
+        for line in expected {
+            assert!(env.lines().any(|l| l == line), "{}\n{}", line, env);
+        }
+
+        let stdin = fs::read_to_string(format!("{}/stdin", dir)).unwrap();
+        assert!(stdin.ends_with('\n') && stdin.lines().count() == 1);
+        let v: serde_json::Value = serde_json::from_str(&stdin).unwrap();
+        assert_eq!(v["hook"], "post_apply");
+        assert_eq!(v["image"], "/tmp/1700000000.jpg");
+        assert_eq!(v["title"], "海 \"边\"");
+        assert_eq!(v["copyright"], "© 某人");
+        assert_eq!(v["source"], 2);
+        assert_eq!(v["width"], 1920);
+        assert_eq!(v["size"], 12345);
+    }
+
+    #[tokio::test]
+    async fn unset_hook() {
+        // 只执行对应时机的钩子
+        let dir = temp_dir("unset");
+        let conf = hook(HookKind::PreFetch, &format!("touch {}/ran", dir));
+        conf.run(HookKind::PostApply, "", &meta()).await.unwrap();
+        assert!(!std::path::Path::new(&format!("{}/ran", dir)).exists());
+    }
+
+    #[tokio::test]
+    async fn pre_apply_veto() {
+        let conf = hook(HookKind::PreApply, r#"test "$WOW_WIDTH" -ge 3840"#);
+        let err = conf
+            .run(HookKind::PreApply, "/tmp/a.jpg", &meta())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Command { .. }), "{}", err);
+        let uhd = ImageMeta {
+            width: 3840,
+            ..meta()
+        };
+        conf.run(HookKind::PreApply, "/tmp/a.jpg", &uhd)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn timeout_kills() {
+        let dir = temp_dir("timeout");
+        let mut conf = hook(
+            HookKind::PostDownload,
+            &format!("sleep 3; touch {}/done", dir),
+        );
+        conf.timeout = 1;
+        let start = Instant::now();
+        let err = conf
+            .run(HookKind::PostDownload, "/tmp/a.jpg", &meta())
+            .await
+            .unwrap_err();
+        assert!(start.elapsed() < Duration::from_secs(3));
+        assert!(err.report().contains("1秒内未结束"), "{}", err.report());
+        // 被结束的钩子不会继续执行
+        tokio::time::sleep(Duration::from_secs(3)).await;
+        assert!(!std::path::Path::new(&format!("{}/done", dir)).exists());
+    }
+}
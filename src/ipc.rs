@@ -0,0 +1,100 @@
+#[cfg(target_family = "unix")]
+use super::error::Error;
+use super::error::Result;
+#[cfg(target_family = "unix")]
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+#[cfg(target_family = "unix")]
+use tokio::net::{UnixListener, UnixStream};
+#[cfg(target_family = "unix")]
+use tokio::sync::watch;
+
+/// 守护进程推送状态的套接字
+pub fn socket_path(runtime_dir: &str) -> String {
+    runtime_dir.to_string() + "/wow.sock"
+}
+
+/// 守护进程一侧，
+/// 向所有连接推送最新状态，每条为一行JSON
+#[cfg(target_family = "unix")]
+pub struct Broadcaster {
+    tx: watch::Sender<String>,
+    path: String,
+}
+
+#[cfg(target_family = "unix")]
+impl Broadcaster {
+    pub fn bind(path: &str) -> Result<Self> {
+        // 上次异常退出时遗留的套接字文件
+        let _ = std::fs::remove_file(path);
+        let listener = match UnixListener::bind(path) {
+            Ok(l) => l,
+            Err(e) => return Err(Error::io(&format!("无法创建{}", path), e)),
+        };
+        let (tx, _) = watch::channel(String::new());
+        let sender = tx.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(push(stream, sender.subscribe()));
+            }
+        });
+        Ok(Broadcaster {
+            tx,
+            path: path.to_string(),
+        })
+    }
+
+    /// 状态有变化时推送
+    pub fn send(&self, msg: String) {
+        self.tx.send_if_modified(|cur| {
+            if *cur == msg {
+                return false;
+            }
+            *cur = msg;
+            true
+        });
+    }
+}
+
+#[cfg(target_family = "unix")]
+impl Drop for Broadcaster {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(target_family = "unix")]
+async fn push(mut stream: UnixStream, mut rx: watch::Receiver<String>) {
+    loop {
+        let msg = rx.borrow_and_update().clone();
+        if !msg.is_empty() && stream.write_all((msg + "\n").as_bytes()).await.is_err() {
+            return;
+        }
+        if rx.changed().await.is_err() {
+            return;
+        }
+    }
+}
+
+/// 客户端一侧，
+/// 连接守护进程并逐行读取推送的状态
+#[cfg(target_family = "unix")]
+pub async fn subscribe(path: &str) -> Result<Lines<BufReader<UnixStream>>> {
+    match UnixStream::connect(path).await {
+        Ok(s) => Ok(BufReader::new(s).lines()),
+        Err(e) => Err(Error::io("无法连接到守护进程", e)),
+    }
+}
+
+/// 没有Unix套接字时不推送，
+/// 状态栏只在类Unix系统上可用
+#[cfg(target_family = "windows")]
+pub struct Broadcaster;
+
+#[cfg(target_family = "windows")]
+impl Broadcaster {
+    pub fn bind(_: &str) -> Result<Self> {
+        Ok(Broadcaster)
+    }
+
+    pub fn send(&self, _: String) {}
+}
@@ -0,0 +1,65 @@
+//! 自动更新壁纸，
+//! `wow`命令行程序也是基于这些模块实现的，
+//! 日志在调用`logger::init`之后才输出
+//!
+//! ```no_run
+//! # async fn demo() -> wow::error::Result<()> {
+//! let dirs = wow::paths::Dirs::detect()?;
+//! let path = dirs.config_file();
+//! let mut config = wow::config::Config::open(&path)?;
+//! let applied = wow::update_once(&mut config, &dirs).await?;
+//! applied.store(&mut config);
+//! config.flush(&path)?;
+//! # Ok(())
+//! # }
+//! ```
+
+#[cfg(target_family = "unix")]
+mod bar;
+mod bye;
+mod cli;
+pub mod config;
+pub mod duration;
+pub mod dynamic;
+pub mod error;
+pub mod external;
+pub mod filter;
+pub mod hook;
+mod ipc;
+pub mod logger;
+pub mod meta;
+pub mod mirror;
+pub mod monitor;
+pub mod net;
+pub mod notify;
+pub mod palette;
+pub mod paths;
+pub mod quiet;
+pub mod rule;
+pub mod schedule;
+pub mod service;
+pub mod setter;
+pub mod source;
+pub mod status;
+pub mod sun;
+#[cfg(test)]
+mod test_server;
+pub mod update;
+pub mod upgrade;
+mod wow;
+
+pub use source::Source;
+pub use update::{AppliedWallpaper, update_once};
+
+/// `wow`命令行程序，返回退出码
+#[doc(hidden)]
+pub use wow::main as run_cli;
+
+// WOW文件布局
+// $XDG_CONFIG_HOME/wow/wow.conf
+// $XDG_DATA_HOME/wow/<时间戳>.jpg
+// $XDG_DATA_HOME/wow/liked/
+// $XDG_DATA_HOME/wow/wow.log
+// $XDG_CACHE_HOME/wow/palette/
+// $XDG_RUNTIME_DIR/wow/wow.sock&wow.pid
+// 设置`WOW_HOME`时都放在该目录中
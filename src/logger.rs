@@ -0,0 +1,398 @@
+use bincode::{Decode, Encode};
+use clap::ValueEnum;
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::sync::Mutex;
+
+/// 日志级别，由低到高
+#[derive(ValueEnum, Encode, Decode, Clone, Copy, PartialEq, PartialOrd)]
+pub enum Level {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    pub fn name(self) -> &'static str {
+        match self {
+            Level::Debug => "DEBUG",
+            Level::Info => "INFO",
+            Level::Warn => "WARN",
+            Level::Error => "ERROR",
+        }
+    }
+
+    fn from_name(s: &str) -> Option<Self> {
+        [Level::Debug, Level::Info, Level::Warn, Level::Error]
+            .into_iter()
+            .find(|l| l.name() == s)
+    }
+
+    // syslog的严重程度
+    fn severity(self) -> u8 {
+        match self {
+            Level::Debug => 7,
+            Level::Info => 6,
+            Level::Warn => 4,
+            Level::Error => 3,
+        }
+    }
+}
+
+/// 日志额外发送到的系统日志
+#[derive(ValueEnum, Encode, Decode, Clone, Copy, PartialEq)]
+pub enum SystemLog {
+    Off,
+    Journald,
+    Syslog,
+}
+
+#[derive(Encode, Decode, Clone)]
+pub struct LogConf {
+    // 低于该级别的日志不输出也不写入文件
+    pub level: Level,
+    // 字节，超过后轮转
+    pub max_size: u64,
+    // 保留的旧日志文件数
+    pub max_files: u8,
+    pub system: SystemLog,
+}
+
+impl Default for LogConf {
+    fn default() -> Self {
+        LogConf {
+            level: Level::Info,
+            max_size: 1024 * 1024,
+            max_files: 3,
+            system: SystemLog::Off,
+        }
+    }
+}
+
+struct Logger {
+    path: String,
+    conf: LogConf,
+}
+
+static LOGGER: Mutex<Option<Logger>> = Mutex::new(None);
+
+/// 设置日志文件与配置，
+/// `path`为空时只输出到终端，
+/// 设置之前不输出任何日志，作为库使用时由调用者决定
+pub fn init(path: &str, conf: LogConf) {
+    if let Ok(mut l) = LOGGER.lock() {
+        *l = Some(Logger {
+            path: path.to_string(),
+            conf,
+        });
+    }
+}
+
+pub fn debug(msg: &str) {
+    log(Level::Debug, msg);
+}
+
+pub fn info(msg: &str) {
+    log(Level::Info, msg);
+}
+
+pub fn warn(msg: &str) {
+    log(Level::Warn, msg);
+}
+
+pub fn error(msg: &str) {
+    log(Level::Error, msg);
+}
+
+/// 不低于配置级别时输出到标准错误，
+/// 并写入日志文件和系统日志，
+/// 标准输出留给`--json`、状态栏等可解析的输出
+pub fn log(level: Level, msg: &str) {
+    let Ok(guard) = LOGGER.lock() else {
+        return;
+    };
+    let Some(logger) = guard.as_ref() else {
+        return;
+    };
+    if level < logger.conf.level {
+        return;
+    }
+    eprintln!("{}", msg);
+    logger.write(level, msg);
+}
+
+impl Logger {
+    /// 写入日志文件和系统日志
+    fn write(&self, level: Level, msg: &str) {
+        if self.path.is_empty() {
+            return;
+        }
+        // 每条日志占一行
+        let msg = msg.replace('\n', " | ");
+        let line = format!(
+            "{} {:<5} {}\n",
+            chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+            level.name(),
+            msg
+        );
+        self.rotate(line.len() as u64);
+        if let Ok(mut f) = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+        {
+            let _ = f.write_all(line.as_bytes());
+        }
+        match self.conf.system {
+            SystemLog::Off => {}
+            SystemLog::Journald => journald(level, &msg),
+            SystemLog::Syslog => syslog(level, &msg),
+        }
+    }
+
+    /// 写入`incoming`字节后超过大小限制时，
+    /// `wow.log` -> `wow.log.1` -> `wow.log.2` ...
+    fn rotate(&self, incoming: u64) {
+        let size = fs::metadata(&self.path).map(|m| m.len()).unwrap_or(0);
+        if size == 0 || size + incoming <= self.conf.max_size {
+            return;
+        }
+        if self.conf.max_files == 0 {
+            let _ = fs::remove_file(&self.path);
+            return;
+        }
+        let _ = fs::remove_file(format!("{}.{}", self.path, self.conf.max_files));
+        for i in (1..self.conf.max_files).rev() {
+            let _ = fs::rename(
+                format!("{}.{}", self.path, i),
+                format!("{}.{}", self.path, i + 1),
+            );
+        }
+        let _ = fs::rename(&self.path, format!("{}.1", self.path));
+    }
+}
+
+#[cfg(target_family = "unix")]
+fn journald(level: Level, msg: &str) {
+    send_datagram(
+        "/run/systemd/journal/socket",
+        journald_entry(level, msg).as_bytes(),
+    );
+}
+
+#[cfg(target_family = "unix")]
+fn syslog(level: Level, msg: &str) {
+    send_datagram("/dev/log", syslog_line(level, msg).as_bytes());
+}
+
+/// journald原生协议的一条日志
+#[cfg(target_family = "unix")]
+fn journald_entry(level: Level, msg: &str) -> String {
+    format!(
+        "PRIORITY={}\nSYSLOG_IDENTIFIER=wow\nMESSAGE={}\n",
+        level.severity(),
+        msg
+    )
+}
+
+/// RFC 3164格式的一条日志，facility为user(1)
+#[cfg(target_family = "unix")]
+fn syslog_line(level: Level, msg: &str) -> String {
+    format!(
+        "<{}>wow[{}]: {}",
+        8 + level.severity(),
+        std::process::id(),
+        msg
+    )
+}
+
+#[cfg(target_family = "unix")]
+fn send_datagram(path: &str, data: &[u8]) {
+    if let Ok(s) = std::os::unix::net::UnixDatagram::unbound() {
+        let _ = s.send_to(data, path);
+    }
+}
+
+#[cfg(target_family = "windows")]
+fn journald(_: Level, _: &str) {}
+
+#[cfg(target_family = "windows")]
+fn syslog(_: Level, _: &str) {}
+
+/// 解析日志行的级别
+pub fn level_of(line: &str) -> Option<Level> {
+    line.split_whitespace().nth(2).and_then(Level::from_name)
+}
+
+/// 最后`n`行不低于`level`的日志，
+/// 无法解析级别的行总是保留
+fn last_lines(content: &str, level: Level, n: usize) -> Vec<&str> {
+    let all: Vec<&str> = content
+        .lines()
+        .filter(|l| level_of(l).is_none_or(|lv| lv >= level))
+        .collect();
+    all[all.len().saturating_sub(n)..].to_vec()
+}
+
+/// 输出日志文件最后`lines`行中不低于`level`的日志，
+/// `follow`时持续输出新写入的日志
+pub async fn tail(path: &str, level: Level, lines: usize, follow: bool) {
+    let show = |l: &str| {
+        if level_of(l).is_none_or(|lv| lv >= level) {
+            println!("{}", l);
+        }
+    };
+    let content = fs::read_to_string(path).unwrap_or_default();
+    for l in last_lines(&content, level, lines) {
+        println!("{}", l);
+    }
+    if !follow {
+        return;
+    }
+
+    let mut pos = content.len() as u64;
+    let mut rest = String::new();
+    loop {
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+        let size = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+        if size < pos {
+            // 已轮转，从新文件开头读取
+            pos = 0;
+        }
+        if size == pos {
+            continue;
+        }
+        let Ok(mut f) = fs::File::open(path) else {
+            continue;
+        };
+        let mut buf = Vec::new();
+        if f.seek(SeekFrom::Start(pos)).is_err() || f.read_to_end(&mut buf).is_err() {
+            continue;
+        }
+        pos += buf.len() as u64;
+        rest += &String::from_utf8_lossy(&buf);
+        // 只输出完整的行
+        while let Some(i) = rest.find('\n') {
+            show(&rest[..i]);
+            rest.drain(..=i);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn logger(name: &str, max_size: u64, max_files: u8) -> Logger {
+        let dir = std::env::temp_dir().join(format!("wow-log-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        Logger {
+            path: dir.join("wow.log").to_string_lossy().to_string(),
+            conf: LogConf {
+                level: Level::Debug,
+                max_size,
+                max_files,
+                system: SystemLog::Off,
+            },
+        }
+    }
+
+    fn read(path: &str) -> Vec<String> {
+        fs::read_to_string(path)
+            .unwrap_or_default()
+            .lines()
+            .map(|l| l.split_once("  ").map_or(l, |(_, m)| m).trim().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn line_format() {
+        let l = logger("format", 1024, 1);
+        l.write(Level::Warn, "第一行\n第二行");
+        l.write(Level::Error, "出错");
+        let content = fs::read_to_string(&l.path).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 2);
+        // `2024-06-15 12:00:00 WARN  消息`
+        assert_eq!(
+            lines[0].len(),
+            "2024-06-15 12:00:00 WARN  第一行 | 第二行".len()
+        );
+        assert!(lines[0].ends_with(" WARN  第一行 | 第二行"), "{}", lines[0]);
+        assert!(lines[1].ends_with(" ERROR 出错"), "{}", lines[1]);
+        assert!(level_of(lines[0]) == Some(Level::Warn));
+        assert!(level_of(lines[1]) == Some(Level::Error));
+    }
+
+    #[test]
+    fn rotation() {
+        // 每行28字节，每个文件最多两行
+        let l = logger("rotate", 70, 2);
+        for i in 1..=7 {
+            l.write(Level::Info, &format!("{}", i));
+        }
+        assert_eq!(read(&l.path), ["7"]);
+        assert_eq!(read(&format!("{}.1", l.path)), ["5", "6"]);
+        assert_eq!(read(&format!("{}.2", l.path)), ["3", "4"]);
+        assert!(!std::path::Path::new(&format!("{}.3", l.path)).exists());
+
+        // 不保留旧文件时直接删除
+        let l = logger("rotate-none", 70, 0);
+        for i in 1..=3 {
+            l.write(Level::Info, &format!("{}", i));
+        }
+        assert_eq!(read(&l.path), ["3"]);
+        assert!(!std::path::Path::new(&format!("{}.1", l.path)).exists());
+    }
+
+    #[test]
+    fn level_filter() {
+        let content = "\
+2024-06-15 12:00:00 DEBUG 调试
+2024-06-15 12:00:01 INFO  信息
+2024-06-15 12:00:02 WARN  警告
+不是日志的行
+2024-06-15 12:00:03 ERROR 错误
+2024-06-15 12:00:04 INFO  信息2
+";
+        assert!(level_of("2024-06-15 12:00:00 DEBUG 调试") == Some(Level::Debug));
+        assert!(level_of("不是日志的行").is_none());
+        assert!(level_of("").is_none());
+        assert_eq!(
+            last_lines(content, Level::Warn, 10),
+            [
+                "2024-06-15 12:00:02 WARN  警告",
+                "不是日志的行",
+                "2024-06-15 12:00:03 ERROR 错误"
+            ]
+        );
+        assert_eq!(
+            last_lines(content, Level::Info, 2),
+            [
+                "2024-06-15 12:00:03 ERROR 错误",
+                "2024-06-15 12:00:04 INFO  信息2"
+            ]
+        );
+        assert_eq!(last_lines(content, Level::Debug, 10).len(), 6);
+        assert!(last_lines("", Level::Debug, 10).is_empty());
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn system_log_format() {
+        assert_eq!(
+            journald_entry(Level::Warn, "网络错误 | 重试"),
+            "PRIORITY=4\nSYSLOG_IDENTIFIER=wow\nMESSAGE=网络错误 | 重试\n"
+        );
+        assert_eq!(
+            syslog_line(Level::Error, "出错"),
+            format!("<11>wow[{}]: 出错", std::process::id())
+        );
+        assert_eq!(
+            syslog_line(Level::Debug, "调试"),
+            format!("<15>wow[{}]: 调试", std::process::id())
+        );
+    }
+}
@@ -0,0 +1,52 @@
+use bincode::{Decode, Encode};
+
+/// 当前壁纸的信息
+#[derive(Encode, Decode, Clone, Default)]
+pub struct ImageMeta {
+    // 重定向后的图片地址
+    pub url: String,
+    pub title: String,
+    pub copyright: String,
+    pub source: u8,
+    pub width: u32,
+    pub height: u32,
+    // 字节
+    pub size: u64,
+}
+
+impl ImageMeta {
+    /// 根据图片地址和本地文件生成信息
+    pub fn new(url: &str, source: u8, path: &str) -> Self {
+        let (width, height) = image::image_dimensions(path).unwrap_or((0, 0));
+        ImageMeta {
+            url: url.to_string(),
+            title: title_from_url(url),
+            copyright: String::new(),
+            source,
+            width,
+            height,
+            size: std::fs::metadata(path).map(|m| m.len()).unwrap_or(0),
+        }
+    }
+}
+
+/// 从必应图片地址中提取标题
+///
+/// `https://www.bing.com/th?id=OHR.LoftyPeaks_ZH-CN8237461234_UHD.jpg` -> `Lofty Peaks`
+pub fn title_from_url(url: &str) -> String {
+    let Some(start) = url.find("OHR.") else {
+        return String::new();
+    };
+    let name: String = url[start + 4..]
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric())
+        .collect();
+    let mut title = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if i > 0 && c.is_ascii_uppercase() {
+            title.push(' ');
+        }
+        title.push(c);
+    }
+    title
+}
@@ -0,0 +1,164 @@
+use super::config::{self, Config};
+use bincode::{Decode, Encode};
+use std::time::{Duration, Instant, SystemTime};
+
+// 连续失败后的冷却时间，每次失败翻倍
+const COOLDOWN_BASE: Duration = Duration::from_secs(60);
+const COOLDOWN_MAX: Duration = Duration::from_secs(60 * 60);
+// 新的延迟在平均延迟中的权重
+const LATENCY_WEIGHT: f64 = 0.3;
+
+/// 图片来源的一个地址与它的健康状况
+#[derive(Encode, Decode, Clone)]
+pub struct Mirror {
+    pub source: u8,
+    pub url: String,
+    pub ok: u32,
+    pub failed: u32,
+    // 成功请求的平均延迟
+    pub latency: Duration,
+    pub fails_in_row: u32,
+    // 在此之前不使用，除非所有镜像都在冷却
+    pub cooldown_until: SystemTime,
+}
+
+impl Mirror {
+    fn new(source: u8, url: &str) -> Self {
+        Mirror {
+            source,
+            url: url.to_string(),
+            ok: 0,
+            failed: 0,
+            latency: Duration::ZERO,
+            fails_in_row: 0,
+            cooldown_until: SystemTime::UNIX_EPOCH,
+        }
+    }
+
+    /// 成功率，没有记录时为0.5
+    pub fn success_rate(&self) -> f64 {
+        (self.ok as f64 + 1.0) / ((self.ok + self.failed) as f64 + 2.0)
+    }
+
+    pub fn cooling(&self, now: SystemTime) -> bool {
+        self.cooldown_until > now
+    }
+
+    /// 记录`at`时的一次请求，成功时`latency`为延迟
+    fn record(&mut self, latency: Option<Duration>, at: SystemTime) {
+        match latency {
+            Some(latency) => {
+                self.ok += 1;
+                self.latency = if self.ok == 1 {
+                    latency
+                } else {
+                    self.latency.mul_f64(1.0 - LATENCY_WEIGHT) + latency.mul_f64(LATENCY_WEIGHT)
+                };
+                self.fails_in_row = 0;
+                self.cooldown_until = SystemTime::UNIX_EPOCH;
+            }
+            None => {
+                self.failed += 1;
+                self.fails_in_row += 1;
+                let cooldown = COOLDOWN_BASE
+                    .saturating_mul(1 << (self.fails_in_row - 1).min(16))
+                    .min(COOLDOWN_MAX);
+                self.cooldown_until = at + cooldown;
+            }
+        }
+    }
+}
+
+/// 必应来源的镜像列表
+#[derive(Encode, Decode, Clone)]
+pub struct MirrorConf {
+    pub mirrors: Vec<Mirror>,
+}
+
+impl Default for MirrorConf {
+    fn default() -> Self {
+        MirrorConf {
+            mirrors: sources()
+                .map(|s| Mirror::new(s, Config::url_of(s)))
+                .collect(),
+        }
+    }
+}
+
+impl MirrorConf {
+    /// `source`的所有镜像，
+    /// 冷却中的排在最后，其余按成功率与平均延迟排序
+    pub fn ordered(&self, source: u8, now: SystemTime) -> Vec<&Mirror> {
+        let mut out: Vec<&Mirror> = self.mirrors.iter().filter(|m| m.source == source).collect();
+        out.sort_by(|a, b| {
+            a.cooling(now)
+                .cmp(&b.cooling(now))
+                .then(b.success_rate().total_cmp(&a.success_rate()))
+                .then(a.latency.cmp(&b.latency))
+        });
+        out
+    }
+
+    /// 更新时依次尝试的镜像，跳过冷却中的，
+    /// 都在冷却时按冷却结束的先后全部尝试
+    pub fn candidates(&self, source: u8, now: SystemTime) -> Vec<String> {
+        let mut all = self.ordered(source, now);
+        if all.iter().any(|m| !m.cooling(now)) {
+            all.retain(|m| !m.cooling(now));
+        } else {
+            all.sort_by_key(|m| m.cooldown_until);
+        }
+        all.into_iter().map(|m| m.url.clone()).collect()
+    }
+
+    pub fn add(&mut self, source: u8, url: &str) -> bool {
+        if self
+            .mirrors
+            .iter()
+            .any(|m| m.source == source && m.url == url)
+        {
+            return false;
+        }
+        self.mirrors.push(Mirror::new(source, url));
+        true
+    }
+
+    pub fn remove(&mut self, source: u8, url: &str) -> bool {
+        let len = self.mirrors.len();
+        self.mirrors
+            .retain(|m| !(m.source == source && m.url == url));
+        self.mirrors.len() != len
+    }
+
+    /// 记录请求`url`的结果，成功时`latency`为延迟
+    pub fn record(&mut self, url: &str, latency: Option<Duration>) {
+        let now = SystemTime::now();
+        for m in self.mirrors.iter_mut().filter(|m| m.url == url) {
+            m.record(latency, now);
+        }
+    }
+}
+
+/// 有镜像的图片来源
+pub fn sources() -> impl Iterator<Item = u8> {
+    (1..config::CMD_SOURCE).filter(|s| !Config::url_of(*s).is_empty())
+}
+
+/// 请求`url`检查镜像是否可用，
+/// 只读取响应头，返回延迟或失败原因，
+/// 由调用者用`MirrorConf::record`记录
+pub async fn probe(client: &reqwest::Client, url: &str) -> std::result::Result<Duration, String> {
+    let start = Instant::now();
+    match client.get(url).send().await {
+        Ok(resp) if resp.status().is_success() => Ok(start.elapsed()),
+        Ok(resp) => Err(format!("服务器返回{}", resp.status().as_u16())),
+        // reqwest的错误说明较笼统，取最底层的原因
+        Err(e) => {
+            let mut cause: &dyn std::error::Error = &e;
+            while let Some(s) = cause.source() {
+                cause = s;
+            }
+            Err(cause.to_string())
+        }
+    }
+}
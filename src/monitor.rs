@@ -0,0 +1,309 @@
+use super::error::Result;
+use super::filter;
+use bincode::{Decode, Encode};
+use clap::ValueEnum;
+use image::{RgbImage, imageops, imageops::FilterType};
+
+/// 多显示器模式
+#[derive(Encode, Decode, Clone, PartialEq, ValueEnum)]
+pub enum MonitorMode {
+    /// 所有显示器使用同一张图片
+    Same,
+    /// 每个显示器单独获取一张图片
+    Each,
+    /// 一张图片按显示器布局切分
+    Span,
+}
+
+#[derive(Encode, Decode, Clone)]
+pub struct MonitorConf {
+    pub mode: MonitorMode,
+    // 显示器名 -> 图片来源，
+    // 未配置的显示器使用全局图片来源
+    pub map: Vec<(String, u8)>,
+}
+
+impl Default for MonitorConf {
+    fn default() -> Self {
+        MonitorConf {
+            mode: MonitorMode::Same,
+            map: vec![],
+        }
+    }
+}
+
+impl MonitorConf {
+    pub fn source_of(&self, output: &str) -> Option<u8> {
+        self.map
+            .iter()
+            .find(|(name, _)| name == output)
+            .map(|(_, s)| *s)
+    }
+}
+
+/// 显示器及其在整个桌面中的位置(像素)
+#[derive(Clone)]
+pub struct Output {
+    pub name: String,
+    // 设置壁纸时指定显示器的标识，
+    // xrandr为Xinerama序号，macOS为显示器ID，sway同名称
+    pub id: String,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// 所有显示器的外接矩形
+fn bounding_box(outputs: &[Output]) -> (i32, i32, u32, u32) {
+    let min_x = outputs.iter().map(|o| o.x).min().unwrap_or(0);
+    let min_y = outputs.iter().map(|o| o.y).min().unwrap_or(0);
+    let max_x = outputs
+        .iter()
+        .map(|o| o.x + o.width as i32)
+        .max()
+        .unwrap_or(0);
+    let max_y = outputs
+        .iter()
+        .map(|o| o.y + o.height as i32)
+        .max()
+        .unwrap_or(0);
+    (min_x, min_y, (max_x - min_x) as u32, (max_y - min_y) as u32)
+}
+
+/// 将`path`处的图片铺满整个桌面后，
+/// 按显示器位置切分，
+/// 第`i`块保存到`dst[i]`
+pub fn slice(path: &str, outputs: &[Output], dst: &[String]) -> Result<()> {
+    let img = filter::load_rgb(path)?;
+    let (min_x, min_y, w, h) = bounding_box(outputs);
+    let img = image::DynamicImage::ImageRgb8(img)
+        .resize_to_fill(w, h, FilterType::Lanczos3)
+        .into_rgb8();
+    for (o, d) in outputs.iter().zip(dst) {
+        let part = imageops::crop_imm(
+            &img,
+            (o.x - min_x) as u32,
+            (o.y - min_y) as u32,
+            o.width,
+            o.height,
+        )
+        .to_image();
+        filter::save_jpeg(&part, d)?;
+    }
+    Ok(())
+}
+
+/// 按显示器位置将多张图片拼接为一张，
+/// 用于只支持跨屏铺满的后端
+pub fn compose(parts: &[(Output, String)], dst: &str) -> Result<()> {
+    let outputs: Vec<Output> = parts.iter().map(|(o, _)| o.clone()).collect();
+    let (min_x, min_y, w, h) = bounding_box(&outputs);
+    let mut canvas = RgbImage::new(w, h);
+    for (o, path) in parts {
+        let img = filter::load_rgb(path)?;
+        let img = image::DynamicImage::ImageRgb8(img)
+            .resize_to_fill(o.width, o.height, FilterType::Lanczos3)
+            .into_rgb8();
+        imageops::replace(
+            &mut canvas,
+            &img,
+            (o.x - min_x) as i64,
+            (o.y - min_y) as i64,
+        );
+    }
+    filter::save_jpeg(&canvas, dst)
+}
+
+/// 解析`xrandr --listmonitors`的输出，
+/// 行首的序号与feh等按Xinerama分配屏幕的顺序一致
+///
+/// ` 0: +*HDMI-1 1920/527x1080/296+0+0  HDMI-1`
+pub fn parse_xrandr(s: &str) -> Vec<Output> {
+    let mut outputs = vec![];
+    for line in s.lines() {
+        let Some((index, rest)) = line.trim().split_once(':') else {
+            continue;
+        };
+        let mut words = rest.split_whitespace();
+        let (Ok(index), Some(name), Some(geometry)) =
+            (index.parse::<usize>(), words.next(), words.next())
+        else {
+            continue;
+        };
+        // 宽高带有毫米尺寸，如`1920/527`
+        let nums: Vec<&str> = geometry
+            .split(['x', '+'])
+            .map(|n| n.split('/').next().unwrap_or(n))
+            .collect();
+        if let [w, h, x, y] = nums[..]
+            && let (Ok(width), Ok(height), Ok(x), Ok(y)) =
+                (w.parse(), h.parse(), x.parse(), y.parse())
+        {
+            outputs.push(Output {
+                // `+`为已启用，`*`为主显示器
+                name: name.trim_start_matches(['+', '*']).to_string(),
+                id: index.to_string(),
+                x,
+                y,
+                width,
+                height,
+            });
+        }
+    }
+    outputs
+}
+
+/// 解析`swaymsg -t get_outputs`的输出
+pub fn parse_sway(s: &str) -> Vec<Output> {
+    let Ok(serde_json::Value::Array(list)) = serde_json::from_str(s) else {
+        return vec![];
+    };
+    list.iter()
+        .filter(|o| o["active"].as_bool().unwrap_or(false))
+        .filter_map(|o| {
+            let rect = &o["rect"];
+            let name = o["name"].as_str()?.to_string();
+            Some(Output {
+                id: name.clone(),
+                name,
+                x: rect["x"].as_i64()? as i32,
+                y: rect["y"].as_i64()? as i32,
+                width: rect["width"].as_u64()? as u32,
+                height: rect["height"].as_u64()? as u32,
+            })
+        })
+        .collect()
+}
+
+/// 解析`updater --list`的输出，
+/// 每行依次为名称、x、y、宽、高、显示器ID，以制表符分隔，
+/// 旧版本没有显示器ID，以名称代替
+pub fn parse_updater(s: &str) -> Vec<Output> {
+    s.lines()
+        .filter_map(|line| {
+            let cols: Vec<&str> = line.split('\t').collect();
+            if !(5..=6).contains(&cols.len()) {
+                return None;
+            }
+            Some(Output {
+                name: cols[0].to_string(),
+                id: cols.get(5).unwrap_or(&cols[0]).to_string(),
+                x: cols[1].parse().ok()?,
+                y: cols[2].parse().ok()?,
+                width: cols[3].parse().ok()?,
+                height: cols[4].parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(outputs: &[Output]) -> Vec<(String, String, i32, i32, u32, u32)> {
+        outputs
+            .iter()
+            .map(|o| (o.name.clone(), o.id.clone(), o.x, o.y, o.width, o.height))
+            .collect()
+    }
+
+    #[test]
+    fn xrandr_monitors() {
+        // 左边的显示器不一定是0号
+        let s = "Monitors: 2
+ 0: +*HDMI-1 2560/597x1440/336+1920+0  HDMI-1
+ 1: +eDP-1 1920/344x1080/194+0+360  eDP-1
+";
+        let outputs = parse_xrandr(s);
+        assert_eq!(
+            summary(&outputs),
+            vec![
+                ("HDMI-1".to_string(), "0".to_string(), 1920, 0, 2560, 1440),
+                ("eDP-1".to_string(), "1".to_string(), 0, 360, 1920, 1080),
+            ]
+        );
+        assert!(parse_xrandr("Monitors: 0\n").is_empty());
+    }
+
+    #[test]
+    fn updater_screens() {
+        let s = "Built-in Retina Display\t0\t0\t3024\t1964\t1
+DELL U2720Q\t3024\t-200\t3840\t2160\t4
+broken line
+";
+        assert_eq!(
+            summary(&parse_updater(s)),
+            vec![
+                (
+                    "Built-in Retina Display".to_string(),
+                    "1".to_string(),
+                    0,
+                    0,
+                    3024,
+                    1964
+                ),
+                (
+                    "DELL U2720Q".to_string(),
+                    "4".to_string(),
+                    3024,
+                    -200,
+                    3840,
+                    2160
+                ),
+            ]
+        );
+        // 旧版本没有显示器ID
+        let old = parse_updater("DELL U2720Q\t0\t0\t3840\t2160\n");
+        assert_eq!(old[0].id, "DELL U2720Q");
+    }
+
+    #[test]
+    fn compose_at_positions() {
+        let dir = std::env::temp_dir().join(format!("wow-compose-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_string_lossy().to_string();
+        // 图片尺寸与显示器不同时缩放裁剪
+        image::RgbImage::from_pixel(80, 40, image::Rgb([255, 0, 0]))
+            .save(path("red.png"))
+            .unwrap();
+        image::RgbImage::from_pixel(10, 10, image::Rgb([0, 0, 255]))
+            .save(path("blue.png"))
+            .unwrap();
+        let output = |name: &str, x, y, width, height| Output {
+            name: name.to_string(),
+            id: String::new(),
+            x,
+            y,
+            width,
+            height,
+        };
+        // 左边的显示器低10像素，坐标可以为负
+        let parts = [
+            (output("eDP-1", -40, 10, 40, 20), path("red.png")),
+            (output("HDMI-1", 0, 0, 30, 30), path("blue.png")),
+        ];
+        compose(&parts, &path("span.jpg")).unwrap();
+
+        let span = filter::load_rgb(&path("span.jpg")).unwrap();
+        assert_eq!(span.dimensions(), (70, 30));
+        let near = |x, y, c: [u8; 3]| {
+            let p = span.get_pixel(x, y).0;
+            assert!(
+                (0..3).all(|i| p[i].abs_diff(c[i]) < 40),
+                "({}, {}) {:?}",
+                x,
+                y,
+                p
+            );
+        };
+        near(5, 20, [255, 0, 0]);
+        near(35, 25, [255, 0, 0]);
+        near(45, 5, [0, 0, 255]);
+        near(65, 28, [0, 0, 255]);
+        // 没有显示器的区域为黑色
+        near(5, 3, [0, 0, 0]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,107 @@
+use super::error::{Error, Result};
+use bincode::{Decode, Encode};
+use reqwest::{Certificate, NoProxy, Proxy, redirect};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// 默认的User-Agent
+pub const USER_AGENT: &str = concat!("wow/", env!("CARGO_PKG_VERSION"));
+/// 不使用代理
+pub const PROXY_OFF: &str = "off";
+
+// 按设置复用的客户端
+static SHARED: Mutex<Option<(NetConf, reqwest::Client)>> = Mutex::new(None);
+
+/// 下载图片、检查升级等网络请求的设置
+#[derive(Encode, Decode, Clone, PartialEq)]
+pub struct NetConf {
+    // http://、https://或socks5://开头的代理，
+    // 为空时读取`ALL_PROXY`、`HTTPS_PROXY`、`HTTP_PROXY`与`NO_PROXY`，
+    // `PROXY_OFF`为不使用代理
+    pub proxy: String,
+    pub connect_timeout: Duration,
+    // 两次读取数据之间的最长间隔
+    pub read_timeout: Duration,
+    // 为空时使用`USER_AGENT`
+    pub user_agent: String,
+    // 额外信任的PEM格式根证书
+    pub ca_certs: Vec<String>,
+    // 0为不跟随重定向
+    pub max_redirects: u8,
+}
+
+impl Default for NetConf {
+    fn default() -> Self {
+        NetConf {
+            proxy: String::new(),
+            connect_timeout: Duration::from_secs(10),
+            read_timeout: Duration::from_secs(60),
+            user_agent: String::new(),
+            ca_certs: vec![],
+            max_redirects: 10,
+        }
+    }
+}
+
+impl NetConf {
+    pub fn user_agent(&self) -> &str {
+        if self.user_agent.is_empty() {
+            USER_AGENT
+        } else {
+            &self.user_agent
+        }
+    }
+
+    /// 按设置创建客户端，
+    /// 代理地址或证书无效时返回错误
+    pub fn build(&self) -> Result<reqwest::Client> {
+        let mut builder = reqwest::Client::builder()
+            .connect_timeout(self.connect_timeout)
+            .read_timeout(self.read_timeout)
+            .user_agent(self.user_agent())
+            .redirect(if self.max_redirects == 0 {
+                redirect::Policy::none()
+            } else {
+                redirect::Policy::limited(self.max_redirects as usize)
+            });
+        match self.proxy.as_str() {
+            // reqwest默认读取代理环境变量
+            "" => {}
+            PROXY_OFF => builder = builder.no_proxy(),
+            url => {
+                let proxy = Proxy::all(url)
+                    .map_err(|e| Error::config(&format!("代理地址{}无效: {}", url, e)))?;
+                builder = builder.proxy(proxy.no_proxy(NoProxy::from_env()));
+            }
+        }
+        for path in &self.ca_certs {
+            let pem =
+                std::fs::read(path).map_err(|e| Error::io(&format!("无法读取证书{}", path), e))?;
+            let certs = Certificate::from_pem_bundle(&pem)
+                .map_err(|e| Error::config(&format!("证书{}无效: {}", path, e)))?;
+            if certs.is_empty() {
+                return Err(Error::config(&format!("{}中没有PEM格式的证书", path)));
+            }
+            for cert in certs {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+        builder
+            .build()
+            .map_err(|e| Error::config(&format!("无法创建网络客户端: {}", e)))
+    }
+}
+
+/// 按`conf`设置的共享客户端，
+/// 设置不变时复用连接
+pub fn client(conf: &NetConf) -> Result<reqwest::Client> {
+    let mut shared = SHARED.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((c, client)) = shared.as_ref()
+        && c == conf
+    {
+        return Ok(client.clone());
+    }
+    let client = conf.build()?;
+    *shared = Some((conf.clone(), client.clone()));
+    Ok(client)
+}
@@ -0,0 +1,317 @@
+use super::error::{Error, Result};
+use super::filter;
+use bincode::{Decode, Encode};
+use futures_util::StreamExt;
+use image::imageops;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::select;
+use tokio::time::sleep;
+use zbus::proxy;
+use zbus::zvariant::Value;
+
+// 等待用户点击按钮的最长时间
+const ACTION_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+// 通知的按钮，为按钮的键与显示的文字，
+// 键同时是点击后执行的wow命令
+const ACTIONS: [(&str, &str); 2] = [("next", "换一张"), ("like", "收藏")];
+
+#[derive(Encode, Decode, Clone, Default)]
+pub struct NotifyConf {
+    // 壁纸更新后通知
+    pub enabled: bool,
+    // 更新失败时通知
+    pub on_error: bool,
+}
+
+#[proxy(
+    interface = "org.freedesktop.Notifications",
+    default_service = "org.freedesktop.Notifications",
+    default_path = "/org/freedesktop/Notifications"
+)]
+trait Notifications {
+    #[allow(clippy::too_many_arguments)]
+    fn notify(
+        &self,
+        app_name: &str,
+        replaces_id: u32,
+        app_icon: &str,
+        summary: &str,
+        body: &str,
+        actions: &[&str],
+        hints: HashMap<&str, Value<'_>>,
+        expire_timeout: i32,
+    ) -> zbus::Result<u32>;
+
+    #[zbus(signal)]
+    fn action_invoked(&self, id: u32, action_key: String) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn notification_closed(&self, id: u32, reason: u32) -> zbus::Result<()>;
+}
+
+/// 一条桌面通知
+pub struct Notice {
+    pub summary: String,
+    pub body: String,
+    // 图标的路径，为空则不显示
+    pub icon: String,
+    // 是否带有`next`、`like`按钮
+    pub actions: bool,
+    pub critical: bool,
+}
+
+impl Notice {
+    /// 通过会话总线发送通知，
+    /// 总线地址取自`DBUS_SESSION_BUS_ADDRESS`，
+    /// 带按钮时等待用户点击，返回点击的按钮
+    pub async fn send(&self) -> Result<Option<String>> {
+        let conn = zbus::Connection::session()
+            .await
+            .map_err(|e| Error::notify("发送通知失败", e))?;
+        self.send_to(&conn).await
+    }
+
+    /// 通过已有的连接发送通知
+    pub async fn send_to(&self, conn: &zbus::Connection) -> Result<Option<String>> {
+        let err = |e: zbus::Error| Error::notify("发送通知失败", e);
+        let proxy = NotificationsProxy::new(conn).await.map_err(err)?;
+
+        // 先订阅信号，避免错过发送后立即点击的按钮
+        let mut invoked = proxy.receive_action_invoked().await.map_err(err)?;
+        let mut closed = proxy.receive_notification_closed().await.map_err(err)?;
+
+        let mut hints: HashMap<&str, Value> = HashMap::new();
+        hints.insert("urgency", Value::U8(if self.critical { 2 } else { 1 }));
+        let uri = format!("file://{}", self.icon);
+        if !self.icon.is_empty() {
+            hints.insert("image-path", Value::from(uri.as_str()));
+        }
+        let actions: Vec<&str> = if self.actions {
+            ACTIONS
+                .iter()
+                .flat_map(|(key, text)| [*key, *text])
+                .collect()
+        } else {
+            vec![]
+        };
+        let id = proxy
+            .notify(
+                "wow",
+                0,
+                &self.icon,
+                &self.summary,
+                &self.body,
+                &actions,
+                hints,
+                -1,
+            )
+            .await
+            .map_err(err)?;
+        if !self.actions {
+            return Ok(None);
+        }
+
+        let timeout = sleep(ACTION_TIMEOUT);
+        tokio::pin!(timeout);
+        loop {
+            select! {
+                Some(s) = invoked.next() => {
+                    if let Ok(args) = s.args()
+                        && args.id == id
+                    {
+                        return Ok(Some(args.action_key));
+                    }
+                }
+                Some(s) = closed.next() => {
+                    if s.args().is_ok_and(|a| a.id == id) {
+                        return Ok(None);
+                    }
+                }
+                _ = &mut timeout => return Ok(None),
+                else => return Ok(None),
+            }
+        }
+    }
+}
+
+/// 点击按钮`action`后执行的wow参数，
+/// 使用守护进程的配置文件`config_path`，
+/// 点击通知本身等其它动作为None
+pub fn action_args(action: &str, config_path: &str) -> Option<Vec<String>> {
+    let (key, _) = ACTIONS.iter().find(|(key, _)| *key == action)?;
+    Some(vec![
+        "--config".to_string(),
+        config_path.to_string(),
+        key.to_string(),
+    ])
+}
+
+/// 生成用作通知图标的缩略图
+pub fn thumbnail(src: &str, dst: &str) -> Result<()> {
+    let img = filter::load_rgb(src)?;
+    let w = 256.min(img.width()).max(1);
+    let h = (img.height() as u64 * w as u64 / img.width().max(1) as u64).max(1) as u32;
+    filter::save_jpeg(&imageops::thumbnail(&img, w, h), dst)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use zbus::object_server::SignalEmitter;
+
+    // 收到的通知
+    #[derive(Default)]
+    struct Received {
+        summary: String,
+        body: String,
+        actions: Vec<String>,
+        urgency: Option<u8>,
+        icon: Option<String>,
+    }
+
+    /// 收到通知后立即点击`click`按钮的通知服务
+    struct MockServer {
+        received: Arc<Mutex<Received>>,
+        click: Option<&'static str>,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.Notifications")]
+    impl MockServer {
+        #[allow(clippy::too_many_arguments)]
+        async fn notify(
+            &self,
+            #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+            _app_name: &str,
+            _replaces_id: u32,
+            _app_icon: &str,
+            summary: &str,
+            body: &str,
+            actions: Vec<String>,
+            hints: HashMap<String, zbus::zvariant::OwnedValue>,
+            _expire_timeout: i32,
+        ) -> u32 {
+            *self.received.lock().unwrap() = Received {
+                summary: summary.to_string(),
+                body: body.to_string(),
+                actions,
+                urgency: hints.get("urgency").and_then(|v| u8::try_from(v).ok()),
+                icon: hints
+                    .get("image-path")
+                    .and_then(|v| String::try_from(v.clone()).ok()),
+            };
+            // 先发送一个其它通知的点击，应被忽略
+            let _ = Self::action_invoked(&emitter, 6, "next").await;
+            match self.click {
+                Some(key) => {
+                    let _ = Self::action_invoked(&emitter, 7, key).await;
+                }
+                None => {
+                    let _ = Self::notification_closed(&emitter, 7, 2).await;
+                }
+            }
+            7
+        }
+
+        #[zbus(signal)]
+        async fn action_invoked(
+            emitter: &SignalEmitter<'_>,
+            id: u32,
+            action_key: &str,
+        ) -> zbus::Result<()>;
+
+        #[zbus(signal)]
+        async fn notification_closed(
+            emitter: &SignalEmitter<'_>,
+            id: u32,
+            reason: u32,
+        ) -> zbus::Result<()>;
+    }
+
+    /// 通过点对点连接发送`notice`，
+    /// 返回点击的按钮与通知服务收到的内容
+    async fn send_to_mock(
+        notice: &Notice,
+        click: Option<&'static str>,
+    ) -> (Result<Option<String>>, Received) {
+        let (client, server) = tokio::net::UnixStream::pair().unwrap();
+        let received = Arc::new(Mutex::new(Received::default()));
+        let mock = MockServer {
+            received: received.clone(),
+            click,
+        };
+        let guid = zbus::Guid::generate();
+        let server = zbus::connection::Builder::unix_stream(server)
+            .server(guid)
+            .unwrap()
+            .p2p()
+            .serve_at("/org/freedesktop/Notifications", mock)
+            .unwrap()
+            .build();
+        let client = zbus::connection::Builder::unix_stream(client).p2p().build();
+        let (server, client) = tokio::join!(server, client);
+        let (_server, client) = (server.unwrap(), client.unwrap());
+        let result = notice.send_to(&client).await;
+        let received = std::mem::take(&mut *received.lock().unwrap());
+        (result, received)
+    }
+
+    #[tokio::test]
+    async fn send_with_actions() {
+        let notice = Notice {
+            summary: "壁纸已更新".to_string(),
+            body: "海边".to_string(),
+            icon: "/tmp/thumb.jpg".to_string(),
+            actions: true,
+            critical: false,
+        };
+        let (result, r) = send_to_mock(&notice, Some("like")).await;
+        assert_eq!(result.unwrap(), Some("like".to_string()));
+        assert_eq!(r.summary, "壁纸已更新");
+        assert_eq!(r.body, "海边");
+        assert_eq!(r.actions, ["next", "换一张", "like", "收藏"]);
+        assert_eq!(r.urgency, Some(1));
+        assert_eq!(r.icon.as_deref(), Some("file:///tmp/thumb.jpg"));
+
+        // 关闭通知时没有点击的按钮
+        let (result, _) = send_to_mock(&notice, None).await;
+        assert_eq!(result.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn send_critical() {
+        let notice = Notice {
+            summary: "更新失败".to_string(),
+            body: "网络错误".to_string(),
+            icon: String::new(),
+            actions: false,
+            critical: true,
+        };
+        let (result, r) = send_to_mock(&notice, Some("next")).await;
+        assert_eq!(result.unwrap(), None);
+        assert_eq!(r.summary, "更新失败");
+        assert!(r.actions.is_empty());
+        assert_eq!(r.urgency, Some(2));
+        assert_eq!(r.icon, None);
+    }
+
+    #[test]
+    fn actions_map_to_commands() {
+        assert_eq!(
+            action_args("next", "/tmp/wow.conf"),
+            Some(vec![
+                "--config".to_string(),
+                "/tmp/wow.conf".to_string(),
+                "next".to_string()
+            ])
+        );
+        assert_eq!(
+            action_args("like", "/tmp/wow.conf").unwrap()[2],
+            "like".to_string()
+        );
+        // 点击通知本身
+        assert_eq!(action_args("default", "/tmp/wow.conf"), None);
+        assert_eq!(action_args("", "/tmp/wow.conf"), None);
+    }
+}
@@ -0,0 +1,417 @@
+use super::error::{Error, Result};
+use super::filter;
+use bincode::{Decode, Encode};
+use image::imageops::{self, FilterType};
+use std::fs;
+use std::process::Command;
+
+// 提取的主色数量
+const DOMINANT_COLORS: usize = 8;
+
+/// 调色板导出配置
+#[derive(Encode, Decode, Clone, Default)]
+pub struct PaletteConf {
+    pub enabled: bool,
+    // 导出后执行的命令，为空则不执行
+    pub reload_cmd: String,
+}
+
+/// 缓存目录中的配色导出目录
+pub fn dir(cache_dir: &str) -> String {
+    cache_dir.to_string() + "/palette"
+}
+
+/// 终端配色，
+/// 与常见终端的16色约定一致
+pub struct Palette {
+    pub background: [u8; 3],
+    pub foreground: [u8; 3],
+    pub colors: [[u8; 3]; 16],
+}
+
+impl Palette {
+    /// 从图片中提取调色板
+    pub fn from_image(path: &str) -> Result<Self> {
+        let img = filter::load_rgb(path)?;
+        // 缩小后再统计，避免在4K图片上逐像素计算
+        let img = imageops::resize(&img, 160, 90, FilterType::Triangle);
+        let pixels: Vec<[u8; 3]> = img.pixels().map(|p| p.0).collect();
+        let mut dominant = median_cut(pixels, DOMINANT_COLORS);
+        dominant.sort_by(|a, b| luma(a).total_cmp(&luma(b)));
+
+        let darkest = dominant[0];
+        let lightest = dominant[dominant.len() - 1];
+        let background = mix(darkest, [0, 0, 0], 0.6);
+        let foreground = mix(lightest, [255, 255, 255], 0.7);
+
+        // 中间的颜色作为1~6号色，不足时循环使用
+        let mut accents: Vec<[u8; 3]> = dominant[1..dominant.len() - 1].to_vec();
+        if accents.is_empty() {
+            accents.push(lightest);
+        }
+        accents.sort_by(|a, b| hue(a).total_cmp(&hue(b)));
+
+        let mut colors = [[0u8; 3]; 16];
+        colors[0] = background;
+        colors[7] = mix(foreground, background, 0.25);
+        colors[8] = mix(background, foreground, 0.3);
+        colors[15] = foreground;
+        for i in 1..7 {
+            let c = accents[(i - 1) % accents.len()];
+            colors[i] = c;
+            colors[i + 8] = mix(c, [255, 255, 255], 0.25);
+        }
+
+        Ok(Palette {
+            background,
+            foreground,
+            colors,
+        })
+    }
+
+    /// 将调色板导出到`dir`目录，
+    /// 并执行`reload_cmd`
+    pub fn export(&self, dir: &str, img: &str, reload_cmd: &str) -> Result<()> {
+        fs::create_dir_all(dir).map_err(|e| Error::io("can't create palette dir", e))?;
+        let files = [
+            ("colors.json", self.to_json(img)),
+            ("colors.sh", self.to_shell(img)),
+            ("colors.Xresources", self.to_xresources()),
+            ("colors-kitty.conf", self.to_kitty()),
+            ("colors-alacritty.toml", self.to_alacritty()),
+        ];
+        for (name, content) in files {
+            fs::write(format!("{}/{}", dir, name), content)
+                .map_err(|e| Error::io(&format!("error when writing {}", name), e))?;
+        }
+
+        if reload_cmd.is_empty() {
+            return Ok(());
+        }
+        match Command::new("sh").arg("-c").arg(reload_cmd).status() {
+            Ok(s) if s.success() => Ok(()),
+            Ok(s) => Err(Error::command("调色板重载命令执行失败", s.to_string())),
+            Err(e) => Err(Error::command("调色板重载命令执行失败", e)),
+        }
+    }
+
+    fn to_json(&self, img: &str) -> String {
+        let colors: serde_json::Map<String, serde_json::Value> = self
+            .colors
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (format!("color{}", i), hex(c).into()))
+            .collect();
+        let json = serde_json::json!({
+            "wallpaper": img,
+            "special": {
+                "background": hex(&self.background),
+                "foreground": hex(&self.foreground),
+                "cursor": hex(&self.foreground),
+            },
+            "colors": colors,
+        });
+        serde_json::to_string_pretty(&json).unwrap_or_default() + "\n"
+    }
+
+    fn to_shell(&self, img: &str) -> String {
+        let mut s = format!("wallpaper='{}'\n", img.replace('\'', "'\\''"));
+        s += &format!("background='{}'\n", hex(&self.background));
+        s += &format!("foreground='{}'\n", hex(&self.foreground));
+        s += &format!("cursor='{}'\n", hex(&self.foreground));
+        for (i, c) in self.colors.iter().enumerate() {
+            s += &format!("color{}='{}'\n", i, hex(c));
+        }
+        s
+    }
+
+    fn to_xresources(&self) -> String {
+        let mut s = format!("*background: {}\n", hex(&self.background));
+        s += &format!("*foreground: {}\n", hex(&self.foreground));
+        s += &format!("*cursorColor: {}\n", hex(&self.foreground));
+        for (i, c) in self.colors.iter().enumerate() {
+            s += &format!("*color{}: {}\n", i, hex(c));
+        }
+        s
+    }
+
+    fn to_kitty(&self) -> String {
+        let mut s = format!("background {}\n", hex(&self.background));
+        s += &format!("foreground {}\n", hex(&self.foreground));
+        s += &format!("cursor {}\n", hex(&self.foreground));
+        for (i, c) in self.colors.iter().enumerate() {
+            s += &format!("color{} {}\n", i, hex(c));
+        }
+        s
+    }
+
+    fn to_alacritty(&self) -> String {
+        let names = [
+            "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+        ];
+        let mut s = format!(
+            "[colors.primary]\nbackground = \"{}\"\nforeground = \"{}\"\n",
+            hex(&self.background),
+            hex(&self.foreground)
+        );
+        for (section, offset) in [("normal", 0), ("bright", 8)] {
+            s += &format!("\n[colors.{}]\n", section);
+            for (i, name) in names.iter().enumerate() {
+                s += &format!("{} = \"{}\"\n", name, hex(&self.colors[i + offset]));
+            }
+        }
+        s
+    }
+}
+
+/// 中位切分法，
+/// 返回按像素数量从多到少排列的`n`个颜色
+fn median_cut(pixels: Vec<[u8; 3]>, n: usize) -> Vec<[u8; 3]> {
+    let mut buckets = vec![pixels];
+    while buckets.len() < n {
+        // 找出跨度最大的桶和通道
+        let mut target = None;
+        let mut widest = 0;
+        for (i, b) in buckets.iter().enumerate() {
+            if b.len() < 2 {
+                continue;
+            }
+            for ch in 0..3 {
+                let min = b.iter().map(|p| p[ch]).min().unwrap_or(0);
+                let max = b.iter().map(|p| p[ch]).max().unwrap_or(0);
+                if max - min >= widest {
+                    widest = max - min;
+                    target = Some((i, ch));
+                }
+            }
+        }
+        let Some((i, ch)) = target else {
+            break;
+        };
+        let mut b = buckets.swap_remove(i);
+        b.sort_unstable_by_key(|p| p[ch]);
+        let upper = b.split_off(b.len() / 2);
+        buckets.push(b);
+        buckets.push(upper);
+    }
+
+    buckets.retain(|b| !b.is_empty());
+    buckets.sort_by_key(|b| std::cmp::Reverse(b.len()));
+    buckets
+        .iter()
+        .map(|b| {
+            let mut sum = [0u64; 3];
+            for p in b {
+                for ch in 0..3 {
+                    sum[ch] += p[ch] as u64;
+                }
+            }
+            let len = b.len() as u64;
+            [
+                (sum[0] / len) as u8,
+                (sum[1] / len) as u8,
+                (sum[2] / len) as u8,
+            ]
+        })
+        .collect()
+}
+
+fn luma(c: &[u8; 3]) -> f32 {
+    0.299 * c[0] as f32 + 0.587 * c[1] as f32 + 0.114 * c[2] as f32
+}
+
+fn hue(c: &[u8; 3]) -> f32 {
+    let (r, g, b) = (c[0] as f32, c[1] as f32, c[2] as f32);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    if max == min {
+        return 0.0;
+    }
+    let h = if max == r {
+        (g - b) / (max - min)
+    } else if max == g {
+        2.0 + (b - r) / (max - min)
+    } else {
+        4.0 + (r - g) / (max - min)
+    };
+    (h * 60.0).rem_euclid(360.0)
+}
+
+fn mix(a: [u8; 3], b: [u8; 3], k: f32) -> [u8; 3] {
+    let mut c = [0u8; 3];
+    for i in 0..3 {
+        c[i] = (a[i] as f32 * (1.0 - k) + b[i] as f32 * k).round() as u8;
+    }
+    c
+}
+
+fn hex(c: &[u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", c[0], c[1], c[2])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn palette() -> Palette {
+        let mut colors = [[0u8; 3]; 16];
+        for (i, c) in colors.iter_mut().enumerate() {
+            *c = [i as u8 * 16, 0, 255 - i as u8 * 16];
+        }
+        Palette {
+            background: [0x10, 0x20, 0x30],
+            foreground: [0xee, 0xdd, 0xcc],
+            colors,
+        }
+    }
+
+    #[test]
+    fn two_colors() {
+        let (red, blue) = ([200, 0, 0], [0, 0, 200]);
+        let pixels = [[red; 4], [blue; 4]].concat();
+        assert_eq!(median_cut(pixels.clone(), 2), [red, blue]);
+        // 颜色不够时切分出相同的颜色
+        let cut = median_cut(pixels, 8);
+        assert_eq!(cut.len(), 8);
+        assert!(cut.iter().all(|c| *c == red || *c == blue));
+        assert_eq!(median_cut(vec![red], 8), [red]);
+
+        let path = std::env::temp_dir()
+            .join(format!("wow-palette-{}.png", std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        image::RgbImage::from_fn(160, 90, |x, _| image::Rgb(if x < 80 { red } else { blue }))
+            .save(&path)
+            .unwrap();
+        let p = Palette::from_image(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        // 最暗的蓝色混入黑色作为背景，最亮的红色混入白色作为前景
+        assert_eq!(hex(&p.background), "#000050");
+        assert_eq!(hex(&p.foreground), "#efb3b3");
+        assert_eq!(p.colors[0], p.background);
+        assert_eq!(p.colors[15], p.foreground);
+        for i in 1..7 {
+            assert!(p.colors[i] == red || p.colors[i] == blue, "{}", i);
+        }
+    }
+
+    #[test]
+    fn json() {
+        let out = palette().to_json("/tmp/a \"b\".jpg");
+        let v: serde_json::Value = serde_json::from_str(&out).unwrap();
+        assert_eq!(v["wallpaper"], "/tmp/a \"b\".jpg");
+        assert_eq!(v["special"]["background"], "#102030");
+        assert_eq!(v["special"]["foreground"], "#eeddcc");
+        assert_eq!(v["special"]["cursor"], "#eeddcc");
+        assert_eq!(v["colors"]["color0"], "#0000ff");
+        assert_eq!(v["colors"]["color15"], "#f0000f");
+        assert_eq!(v["colors"].as_object().unwrap().len(), 16);
+        assert!(out.ends_with("}\n"));
+    }
+
+    #[test]
+    fn shell() {
+        let expected = "wallpaper='/tmp/it'\\''s.jpg'
+background='#102030'
+foreground='#eeddcc'
+cursor='#eeddcc'
+color0='#0000ff'
+color1='#1000ef'
+color2='#2000df'
+color3='#3000cf'
+color4='#4000bf'
+color5='#5000af'
+color6='#60009f'
+color7='#70008f'
+color8='#80007f'
+color9='#90006f'
+color10='#a0005f'
+color11='#b0004f'
+color12='#c0003f'
+color13='#d0002f'
+color14='#e0001f'
+color15='#f0000f'
+";
+        assert_eq!(palette().to_shell("/tmp/it's.jpg"), expected);
+    }
+
+    #[test]
+    fn xresources() {
+        let expected = "*background: #102030
+*foreground: #eeddcc
+*cursorColor: #eeddcc
+*color0: #0000ff
+*color1: #1000ef
+*color2: #2000df
+*color3: #3000cf
+*color4: #4000bf
+*color5: #5000af
+*color6: #60009f
+*color7: #70008f
+*color8: #80007f
+*color9: #90006f
+*color10: #a0005f
+*color11: #b0004f
+*color12: #c0003f
+*color13: #d0002f
+*color14: #e0001f
+*color15: #f0000f
+";
+        assert_eq!(palette().to_xresources(), expected);
+    }
+
+    #[test]
+    fn kitty() {
+        let expected = "background #102030
+foreground #eeddcc
+cursor #eeddcc
+color0 #0000ff
+color1 #1000ef
+color2 #2000df
+color3 #3000cf
+color4 #4000bf
+color5 #5000af
+color6 #60009f
+color7 #70008f
+color8 #80007f
+color9 #90006f
+color10 #a0005f
+color11 #b0004f
+color12 #c0003f
+color13 #d0002f
+color14 #e0001f
+color15 #f0000f
+";
+        assert_eq!(palette().to_kitty(), expected);
+    }
+
+    #[test]
+    fn alacritty() {
+        let out = palette().to_alacritty();
+        let expected = "[colors.primary]
+background = \"#102030\"
+foreground = \"#eeddcc\"
+
+[colors.normal]
+black = \"#0000ff\"
+red = \"#1000ef\"
+green = \"#2000df\"
+yellow = \"#3000cf\"
+blue = \"#4000bf\"
+magenta = \"#5000af\"
+cyan = \"#60009f\"
+white = \"#70008f\"
+
+[colors.bright]
+black = \"#80007f\"
+red = \"#90006f\"
+green = \"#a0005f\"
+yellow = \"#b0004f\"
+blue = \"#c0003f\"
+magenta = \"#d0002f\"
+cyan = \"#e0001f\"
+white = \"#f0000f\"
+";
+        assert_eq!(out, expected);
+    }
+}
@@ -0,0 +1,361 @@
+use super::config::Config;
+use super::error::{Error, Result};
+use super::logger;
+use std::fs;
+use std::path::Path;
+
+/// 设置后所有文件都放在该目录中
+const HOME_ENV: &str = "WOW_HOME";
+
+/// wow使用的目录，
+/// 遵循XDG基础目录规范
+#[derive(Clone, Default)]
+pub struct Dirs {
+    // 配置文件，`$XDG_CONFIG_HOME/wow`
+    pub config: String,
+    // 壁纸、收藏与日志，`$XDG_DATA_HOME/wow`
+    pub data: String,
+    // 缩略图、配色等可重新生成的文件，`$XDG_CACHE_HOME/wow`
+    pub cache: String,
+    // 套接字与PID文件，`$XDG_RUNTIME_DIR/wow`，未设置时同缓存目录
+    pub runtime: String,
+    // 可执行文件所在目录，macOS下的`updater`与其放在一起
+    pub exe: String,
+}
+
+impl Dirs {
+    /// 确定并创建各个目录
+    pub fn detect() -> Result<Self> {
+        Dirs::detect_in(&|var| std::env::var(var).ok(), exe_dir()?)
+    }
+
+    /// 按`env`给出的环境变量确定目录
+    fn detect_in(env: &dyn Fn(&str) -> Option<String>, exe: String) -> Result<Self> {
+        let var = |v: &str| absolute(env(v));
+        // `WOW_HOME`，或XDG基础目录中的`wow`目录
+        let dir = |xdg: &str, fallback: &str| -> Result<String> {
+            if let Some(home) = var(HOME_ENV) {
+                return create(home);
+            }
+            let base = match var(xdg) {
+                Some(base) => base,
+                None => format!(
+                    "{}/{}",
+                    var("HOME").ok_or(Error::config("没有设置HOME"))?,
+                    fallback
+                ),
+            };
+            create(base + "/wow")
+        };
+        let cache = dir("XDG_CACHE_HOME", ".cache")?;
+        let runtime = match var("XDG_RUNTIME_DIR") {
+            Some(r) if var(HOME_ENV).is_none() => create(r + "/wow")?,
+            _ => cache.clone(),
+        };
+        Ok(Dirs {
+            config: dir("XDG_CONFIG_HOME", ".config")?,
+            data: dir("XDG_DATA_HOME", ".local/share")?,
+            cache,
+            runtime,
+            exe,
+        })
+    }
+
+    pub fn config_file(&self) -> String {
+        self.config.clone() + "/wow.conf"
+    }
+
+    pub fn log_file(&self) -> String {
+        self.data.clone() + "/wow.log"
+    }
+
+    pub fn pid_file(&self) -> String {
+        self.runtime.clone() + "/wow.pid"
+    }
+
+    /// 旧版本把所有文件放在可执行文件旁边，
+    /// 新目录中还没有配置文件时迁移过去
+    pub fn migrate(&self) -> Result<()> {
+        let old = self.exe.as_str();
+        let old_conf = old.to_string() + "/wow.conf";
+        if !Path::new(&old_conf).exists()
+            || Path::new(&self.config_file()).exists()
+            || old_conf == self.config_file()
+        {
+            return Ok(());
+        }
+
+        // 无法读取配置时也迁移壁纸、收藏与日志
+        let mut config = match Config::open(&old_conf) {
+            Ok(c) => c,
+            Err(e) => {
+                logger::warn(&format!("{}，迁移后使用默认配置", e.report()));
+                Config::default()
+            }
+        };
+        let moved = |p: &str| -> Result<String> {
+            if p.is_empty() || Path::new(p).parent() != Some(Path::new(old)) {
+                return Ok(p.to_string());
+            }
+            let to = format!("{}/{}", self.data, file_name(p));
+            if Path::new(p).exists() {
+                move_path(p, &to)?;
+            }
+            Ok(to)
+        };
+        let img = moved(config.get_cur_img())?;
+        let dark = moved(config.get_cur_dark_img())?;
+        let outputs = config
+            .get_cur_output_imgs()
+            .iter()
+            .map(|p| moved(p))
+            .collect::<Result<Vec<String>>>()?;
+        config.set_cur_img(&img);
+        config.set_cur_dark_img(&dark);
+        config.set_cur_output_imgs(outputs);
+
+        let mut rest = vec![
+            ("liked".to_string(), self.data.as_str()),
+            ("palette".to_string(), self.cache.as_str()),
+        ];
+        if let Ok(entries) = fs::read_dir(old) {
+            for e in entries.flatten() {
+                let name = e.file_name().to_string_lossy().to_string();
+                if name.starts_with("wow.log") || is_wallpaper(&name) {
+                    rest.push((name, self.data.as_str()));
+                }
+            }
+        }
+        for (name, to) in rest {
+            let from = format!("{}/{}", old, name);
+            if Path::new(&from).exists() {
+                move_path(&from, &format!("{}/{}", to, name))?;
+            }
+        }
+
+        config.flush(&self.config_file())?;
+        fs::remove_file(&old_conf).map_err(|e| Error::io("无法删除旧的配置文件", e))?;
+        let _ = fs::remove_file(old.to_string() + "/notify-thumb.jpg");
+        logger::info(&format!(
+            "已将{}中的文件迁移到\n  {}\n  {}\n  {}",
+            old, self.config, self.data, self.cache
+        ));
+        Ok(())
+    }
+}
+
+/// 是否设置了`WOW_HOME`，
+/// 此时目录可能与其它程序共用
+pub fn shared_home() -> bool {
+    env_dir(HOME_ENV).is_some()
+}
+
+/// wow在各个目录中创建的文件与目录，壁纸除外
+pub fn is_wow_file(name: &str) -> bool {
+    matches!(
+        name,
+        "wow.conf"
+            | "wow.conf.bak"
+            | "wow.pid"
+            | "wow.sock"
+            | "liked"
+            | "palette"
+            | "dynamic"
+            | "notify-thumb.jpg"
+    ) || name.starts_with("wow.log")
+}
+
+/// 按时间戳命名的壁纸，如`1700000000.jpg`、`1700000000-dark.jpg`
+pub fn is_wallpaper(name: &str) -> bool {
+    let Some((stem, ext)) = name.rsplit_once('.') else {
+        return false;
+    };
+    let stamp = stem.split('-').next().unwrap_or_default();
+    (ext == "jpg" || ext == "png") && !stamp.is_empty() && stamp.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// 可执行文件所在的目录
+pub fn exe_dir() -> Result<String> {
+    let exe =
+        std::env::current_exe().map_err(|e| Error::io("can't access current working space", e))?;
+    match exe.parent().and_then(|p| p.to_str()) {
+        Some(w) => Ok(w.to_string()),
+        None => Err(Error::config("can't access current working space")),
+    }
+}
+
+/// XDG基础目录，`$var`或`$HOME/fallback`
+pub fn base_dir(var: &str, fallback: &str) -> Result<String> {
+    if let Some(base) = env_dir(var) {
+        return Ok(base);
+    }
+    Ok(format!("{}/{}", home()?, fallback))
+}
+
+/// 用户主目录
+pub fn home() -> Result<String> {
+    env_dir("HOME").ok_or(Error::config("没有设置HOME"))
+}
+
+/// 规范要求忽略相对路径
+fn env_dir(var: &str) -> Option<String> {
+    absolute(std::env::var(var).ok())
+}
+
+fn absolute(dir: Option<String>) -> Option<String> {
+    dir.filter(|v| Path::new(v).is_absolute())
+        .map(|v| v.trim_end_matches('/').to_string())
+}
+
+fn create(dir: String) -> Result<String> {
+    fs::create_dir_all(&dir).map_err(|e| Error::io(&format!("无法创建{}", dir), e))?;
+    Ok(dir)
+}
+
+fn file_name(p: &str) -> String {
+    Path::new(p)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// 移动文件或目录，
+/// 跨文件系统时复制后删除
+fn move_path(from: &str, to: &str) -> Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    let failed = |e| Error::io(&format!("无法将{}移动到{}", from, to), e);
+    if Path::new(from).is_dir() {
+        fs::create_dir_all(to).map_err(failed)?;
+        for e in fs::read_dir(from).map_err(failed)?.flatten() {
+            let name = e.file_name().to_string_lossy().to_string();
+            move_path(&format!("{}/{}", from, name), &format!("{}/{}", to, name))?;
+        }
+        fs::remove_dir(from).map_err(failed)
+    } else {
+        fs::copy(from, to).map_err(failed)?;
+        fs::remove_file(from).map_err(failed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn temp_dir(name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("wow-paths-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.to_string_lossy().to_string()
+    }
+
+    fn detect(vars: &[(&str, String)]) -> Dirs {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect();
+        Dirs::detect_in(&|v| vars.get(v).cloned(), "/opt/wow".to_string()).unwrap()
+    }
+
+    #[test]
+    fn xdg_dirs() {
+        let root = temp_dir("xdg");
+        let dirs = detect(&[
+            ("HOME", root.clone() + "/home"),
+            ("XDG_CONFIG_HOME", root.clone() + "/config/"),
+            ("XDG_RUNTIME_DIR", root.clone() + "/run"),
+            // 相对路径被忽略
+            ("XDG_DATA_HOME", "share".to_string()),
+        ]);
+        assert_eq!(dirs.config, root.clone() + "/config/wow");
+        assert_eq!(dirs.data, root.clone() + "/home/.local/share/wow");
+        assert_eq!(dirs.cache, root.clone() + "/home/.cache/wow");
+        assert_eq!(dirs.runtime, root.clone() + "/run/wow");
+        assert_eq!(dirs.exe, "/opt/wow");
+        assert!(Path::new(&dirs.data).is_dir());
+        assert_eq!(dirs.config_file(), root + "/config/wow/wow.conf");
+    }
+
+    #[test]
+    fn wow_home() {
+        let root = temp_dir("home");
+        let dirs = detect(&[
+            ("HOME", "/nonexistent".to_string()),
+            ("WOW_HOME", root.clone()),
+            ("XDG_RUNTIME_DIR", root.clone() + "/run"),
+        ]);
+        for dir in [&dirs.config, &dirs.data, &dirs.cache, &dirs.runtime] {
+            assert_eq!(dir, &root);
+        }
+    }
+
+    #[test]
+    fn missing_home() {
+        let vars: HashMap<String, String> = HashMap::new();
+        assert!(Dirs::detect_in(&|v| vars.get(v).cloned(), String::new()).is_err());
+    }
+
+    #[test]
+    fn migrate_from_exe_dir() {
+        let root = temp_dir("migrate");
+        let dirs = Dirs {
+            config: root.clone() + "/config",
+            data: root.clone() + "/data",
+            cache: root.clone() + "/cache",
+            runtime: root.clone() + "/cache",
+            exe: root.clone() + "/exe",
+        };
+        for d in [&dirs.config, &dirs.data, &dirs.cache, &dirs.exe] {
+            fs::create_dir_all(d).unwrap();
+        }
+        let old = |name: &str| format!("{}/{}", dirs.exe, name);
+        let mut config = Config::default();
+        config.set_url(2);
+        config.set_cur_img(&old("1700000000.jpg"));
+        config.set_cur_dark_img(&old("1700000000-dark.jpg"));
+        config.flush(&old("wow.conf")).unwrap();
+        for name in [
+            "1700000000.jpg",
+            "1700000000-dark.jpg",
+            "wow.log",
+            "wow.log.1",
+        ] {
+            fs::write(old(name), name).unwrap();
+        }
+        fs::create_dir_all(old("liked")).unwrap();
+        fs::write(old("liked/1600000000.jpg"), "liked/1600000000.jpg").unwrap();
+        fs::write(old("updater"), "updater").unwrap();
+
+        dirs.migrate().unwrap();
+        let config = Config::open(&dirs.config_file()).unwrap();
+        assert_eq!(config.get_source(), 2);
+        assert_eq!(config.get_cur_img(), dirs.data.clone() + "/1700000000.jpg");
+        assert_eq!(
+            config.get_cur_dark_img(),
+            dirs.data.clone() + "/1700000000-dark.jpg"
+        );
+        for name in [
+            "1700000000.jpg",
+            "1700000000-dark.jpg",
+            "wow.log",
+            "wow.log.1",
+            "liked/1600000000.jpg",
+        ] {
+            assert_eq!(
+                fs::read_to_string(format!("{}/{}", dirs.data, name)).unwrap(),
+                name
+            );
+            assert!(!Path::new(&old(name)).exists(), "{}", name);
+        }
+        assert!(!Path::new(&old("wow.conf")).exists());
+        // 不是wow的文件留在原处
+        assert!(Path::new(&old("updater")).exists());
+
+        // 新目录中已有配置时不再迁移
+        fs::write(old("wow.conf"), "old").unwrap();
+        dirs.migrate().unwrap();
+        assert!(Path::new(&old("wow.conf")).exists());
+    }
+}
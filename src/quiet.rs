@@ -0,0 +1,346 @@
+use super::logger;
+use bincode::{Decode, Encode};
+use chrono::{DateTime, Local, Timelike};
+use std::fmt;
+use std::process::{Command, Stdio};
+use std::time::{Duration, SystemTime};
+use tokio::time::timeout;
+use zbus::proxy;
+
+// 检查命令的超时时间
+const CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+const DAY_MINUTES: u16 = 24 * 60;
+// GNOME会话管理器中阻止空闲的标记
+const GNOME_INHIBIT_IDLE: u32 = 8;
+
+/// 本地时间的时段，如`22:00-07:00`，
+/// 起点早于终点时跨过午夜
+#[derive(Encode, Decode, Clone, Copy, PartialEq)]
+pub struct Window {
+    // 从0点起的分钟
+    start: u16,
+    end: u16,
+}
+
+impl Window {
+    /// `minute`在时段内时，返回到时段结束的分钟数
+    fn left(&self, minute: u16) -> Option<u16> {
+        let inside = if self.start < self.end {
+            self.start <= minute && minute < self.end
+        } else {
+            minute >= self.start || minute < self.end
+        };
+        inside.then(|| (self.end + DAY_MINUTES - minute) % DAY_MINUTES)
+    }
+}
+
+impl fmt::Display for Window {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:02}:{:02}-{:02}:{:02}",
+            self.start / 60,
+            self.start % 60,
+            self.end / 60,
+            self.end % 60
+        )
+    }
+}
+
+/// 解析`HH:MM-HH:MM`
+pub fn parse_window(s: &str) -> Result<Window, String> {
+    let minute = |t: &str| -> Option<u16> {
+        let (h, m) = t.trim().split_once(':')?;
+        let (h, m): (u16, u16) = (h.parse().ok()?, m.parse().ok()?);
+        // 24:00表示午夜
+        (h < 24 && m < 60 || h == 24 && m == 0).then_some(h * 60 % DAY_MINUTES + m)
+    };
+    let err = || "需要HH:MM-HH:MM格式的时段，如22:00-07:00".to_string();
+    let (start, end) = s.split_once('-').ok_or_else(err)?;
+    let (start, end) = (minute(start).ok_or_else(err)?, minute(end).ok_or_else(err)?);
+    if start == end {
+        return Err("时段的起止时间不能相同".to_string());
+    }
+    Ok(Window { start, end })
+}
+
+/// 暂缓更新的条件，
+/// 条件解除后再执行错过的更新
+#[derive(Encode, Decode, Clone, Default)]
+pub struct QuietConf {
+    // 安静时段
+    pub hours: Vec<Window>,
+    // 当前窗口全屏时暂缓
+    pub fullscreen: bool,
+    // 有程序阻止空闲时暂缓，如演示、视频会议、屏幕共享
+    pub inhibit: bool,
+    // 通过`sh -c`执行，退出码不为0时暂缓，为空则不执行
+    pub check: String,
+}
+
+impl QuietConf {
+    /// `t`处于安静时段时，返回时段结束的时间，
+    /// 相连的时段一并跳过
+    pub fn quiet_until(&self, t: SystemTime) -> Option<SystemTime> {
+        let mut end = t;
+        for _ in 0..self.hours.len() {
+            let local = DateTime::<Local>::from(end);
+            let minute = (local.hour() * 60 + local.minute()) as u16;
+            let Some(left) = self.hours.iter().filter_map(|w| w.left(minute)).max() else {
+                break;
+            };
+            // 对齐到整分钟
+            let into_minute =
+                Duration::new(local.second() as u64, local.nanosecond().min(999_999_999));
+            end = end - into_minute + Duration::from_secs(left as u64 * 60);
+        }
+        (end != t).then_some(end)
+    }
+
+    /// 现在需要暂缓更新的原因，
+    /// 依次检查安静时段、全屏、阻止空闲与检查命令
+    pub async fn reason(&self, now: SystemTime) -> Option<String> {
+        if let Some(end) = self.quiet_until(now) {
+            return Some(format!(
+                "安静时段，{}结束",
+                DateTime::<Local>::from(end).format("%H:%M")
+            ));
+        }
+        if self.fullscreen && fullscreen() {
+            return Some("当前窗口全屏".to_string());
+        }
+        if self.inhibit
+            && let Some(who) = inhibitor().await
+        {
+            return Some(format!("{}阻止了空闲", who));
+        }
+        if !self.check.is_empty() && !check(&self.check).await {
+            return Some(format!("检查命令`{}`未通过", self.check));
+        }
+        None
+    }
+}
+
+/// 当前聚焦的窗口是否全屏，
+/// 支持sway、Hyprland与X11，
+/// 无法判断时视为没有全屏
+pub fn fullscreen() -> bool {
+    if std::env::var("SWAYSOCK").is_ok() {
+        return output(Command::new("swaymsg").args(["-t", "get_tree", "-r"]))
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .is_some_and(|tree| sway_fullscreen(&tree, false));
+    }
+    if std::env::var("HYPRLAND_INSTANCE_SIGNATURE").is_ok() {
+        return output(Command::new("hyprctl").args(["activewindow", "-j"]))
+            .and_then(|s| serde_json::from_str::<serde_json::Value>(&s).ok())
+            .is_some_and(|w| match &w["fullscreen"] {
+                serde_json::Value::Bool(b) => *b,
+                v => v.as_u64().unwrap_or(0) > 0,
+            });
+    }
+    if std::env::var("DISPLAY").is_ok() {
+        // _NET_ACTIVE_WINDOW(WINDOW): window id # 0x3a00007
+        let Some(id) = output(Command::new("xprop").args(["-root", "_NET_ACTIVE_WINDOW"]))
+            .and_then(|s| s.split_whitespace().last().map(str::to_string))
+        else {
+            return false;
+        };
+        return output(Command::new("xprop").args(["-id", &id, "_NET_WM_STATE"]))
+            .is_some_and(|s| s.contains("_NET_WM_STATE_FULLSCREEN"));
+    }
+    false
+}
+
+/// 聚焦的节点或其所在的容器是否全屏
+fn sway_fullscreen(node: &serde_json::Value, parent: bool) -> bool {
+    let fullscreen = parent || node["fullscreen_mode"].as_u64().unwrap_or(0) > 0;
+    if node["focused"].as_bool().unwrap_or(false) {
+        return fullscreen;
+    }
+    ["nodes", "floating_nodes"]
+        .iter()
+        .filter_map(|k| node[k].as_array())
+        .flatten()
+        .any(|n| sway_fullscreen(n, fullscreen))
+}
+
+#[proxy(
+    interface = "org.gnome.SessionManager",
+    default_service = "org.gnome.SessionManager",
+    default_path = "/org/gnome/SessionManager"
+)]
+trait SessionManager {
+    fn is_inhibited(&self, flags: u32) -> zbus::Result<bool>;
+}
+
+#[proxy(
+    interface = "org.freedesktop.PowerManagement.Inhibit",
+    default_service = "org.freedesktop.PowerManagement",
+    default_path = "/org/freedesktop/PowerManagement/Inhibit"
+)]
+trait PowerInhibit {
+    fn has_inhibit(&self) -> zbus::Result<bool>;
+}
+
+#[proxy(
+    interface = "org.freedesktop.login1.Manager",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1"
+)]
+trait Login {
+    #[allow(clippy::type_complexity)]
+    fn list_inhibitors(&self) -> zbus::Result<Vec<(String, String, String, String, u32, u32)>>;
+}
+
+/// 阻止空闲的程序，
+/// 依次查询GNOME会话管理器、KDE电源管理与logind，
+/// 都查询不到时视为没有
+pub async fn inhibitor() -> Option<String> {
+    if let Ok(conn) = zbus::Connection::session().await {
+        if let Ok(p) = SessionManagerProxy::new(&conn).await
+            && matches!(p.is_inhibited(GNOME_INHIBIT_IDLE).await, Ok(true))
+        {
+            return Some("GNOME会话".to_string());
+        }
+        if let Ok(p) = PowerInhibitProxy::new(&conn).await
+            && matches!(p.has_inhibit().await, Ok(true))
+        {
+            return Some("电源管理".to_string());
+        }
+    }
+    let conn = zbus::Connection::system().await.ok()?;
+    let inhibitors = LoginProxy::new(&conn)
+        .await
+        .ok()?
+        .list_inhibitors()
+        .await
+        .ok()?;
+    // (what, who, why, mode, uid, pid)
+    inhibitors
+        .into_iter()
+        .find(|i| i.0.split(':').any(|w| w == "idle") && i.3 == "block")
+        .map(|i| i.1)
+}
+
+/// 执行检查命令，退出码为0时通过，
+/// 无法执行或超时视为通过，避免一直不更新
+async fn check(cmd: &str) -> bool {
+    let child = tokio::process::Command::new("sh")
+        .arg("-c")
+        .arg(cmd)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .kill_on_drop(true)
+        .status();
+    match timeout(CHECK_TIMEOUT, child).await {
+        Ok(Ok(s)) => s.success(),
+        Ok(Err(e)) => {
+            logger::warn(&format!("无法执行检查命令`{}`: {}", cmd, e));
+            true
+        }
+        Err(_) => {
+            logger::warn(&format!(
+                "检查命令`{}`{}秒内未结束",
+                cmd,
+                CHECK_TIMEOUT.as_secs()
+            ));
+            true
+        }
+    }
+}
+
+fn output(cmd: &mut Command) -> Option<String> {
+    match cmd.output() {
+        Ok(o) if o.status.success() => Some(String::from_utf8_lossy(&o.stdout).to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn local(day: u32, h: u32, m: u32, s: u32) -> SystemTime {
+        Local
+            .with_ymd_and_hms(2024, 6, day, h, m, s)
+            .unwrap()
+            .into()
+    }
+
+    fn quiet(windows: &[&str]) -> QuietConf {
+        QuietConf {
+            hours: windows.iter().map(|w| parse_window(w).unwrap()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parse() {
+        let w = parse_window("22:00-07:00").unwrap();
+        assert_eq!(w.to_string(), "22:00-07:00");
+        assert_eq!(
+            parse_window(" 7:05 - 8:00 ").unwrap().to_string(),
+            "07:05-08:00"
+        );
+        // 24:00为午夜
+        assert!(parse_window("22:00-24:00").unwrap() == parse_window("22:00-00:00").unwrap());
+        for bad in [
+            "22:00",
+            "22:00-",
+            "25:00-01:00",
+            "22:60-01:00",
+            "24:01-01:00",
+            "10-12",
+            "22:00-07:00-08:00",
+            "aa:bb-07:00",
+        ] {
+            assert!(parse_window(bad).is_err(), "{}", bad);
+        }
+        assert!(parse_window("22:00-22:00").err() == Some("时段的起止时间不能相同".to_string()));
+    }
+
+    #[test]
+    fn wrap_midnight() {
+        let w = parse_window("22:00-07:00").unwrap();
+        assert_eq!(w.left(22 * 60), Some(9 * 60));
+        assert_eq!(w.left(23 * 60 + 30), Some(7 * 60 + 30));
+        assert_eq!(w.left(0), Some(7 * 60));
+        assert_eq!(w.left(6 * 60 + 59), Some(1));
+        assert_eq!(w.left(7 * 60), None);
+        assert_eq!(w.left(21 * 60 + 59), None);
+        assert_eq!(w.left(12 * 60), None);
+
+        let w = parse_window("12:00-13:00").unwrap();
+        assert_eq!(w.left(12 * 60 + 30), Some(30));
+        assert_eq!(w.left(13 * 60), None);
+        assert_eq!(w.left(11 * 60 + 59), None);
+    }
+
+    #[test]
+    fn until_end_of_window() {
+        let q = quiet(&["22:00-07:00"]);
+        assert!(q.quiet_until(local(15, 23, 30, 15)) == Some(local(16, 7, 0, 0)));
+        assert!(q.quiet_until(local(16, 6, 59, 59)) == Some(local(16, 7, 0, 0)));
+        assert!(q.quiet_until(local(16, 7, 0, 0)).is_none());
+        assert!(q.quiet_until(local(15, 12, 0, 0)).is_none());
+        assert!(
+            QuietConf::default()
+                .quiet_until(local(15, 23, 0, 0))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn until_joined_windows() {
+        // 相连的时段一并跳过，顺序无关
+        let q = quiet(&["07:00-08:30", "22:00-07:00"]);
+        assert!(q.quiet_until(local(15, 23, 0, 0)) == Some(local(16, 8, 30, 0)));
+        assert!(q.quiet_until(local(16, 7, 15, 0)) == Some(local(16, 8, 30, 0)));
+        // 重叠时取较晚的结束
+        let q = quiet(&["22:00-01:00", "23:00-02:00"]);
+        assert!(q.quiet_until(local(15, 23, 30, 0)) == Some(local(16, 2, 0, 0)));
+        let q = quiet(&["22:00-07:00", "09:00-10:00"]);
+        assert!(q.quiet_until(local(15, 23, 0, 0)) == Some(local(16, 7, 0, 0)));
+    }
+}
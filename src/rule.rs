@@ -0,0 +1,233 @@
+use super::error::{Error, Result};
+use bincode::{Decode, Encode};
+use clap::ValueEnum;
+use image::ImageReader;
+
+#[derive(Encode, Decode, Clone, PartialEq, ValueEnum)]
+pub enum Orientation {
+    Any,
+    Landscape,
+    Portrait,
+}
+
+/// 图片筛选规则
+///
+/// 各项为0时不限制
+#[derive(Encode, Decode, Clone)]
+pub struct ImageRule {
+    pub min_width: u32,
+    pub min_height: u32,
+    // 宽高比范围
+    pub min_ratio: f32,
+    pub max_ratio: f32,
+    pub orientation: Orientation,
+    // 字节
+    pub max_size: u64,
+    // 不符合规则时重新获取的次数
+    pub retry: u8,
+}
+
+impl Default for ImageRule {
+    fn default() -> Self {
+        ImageRule {
+            min_width: 0,
+            min_height: 0,
+            min_ratio: 0.0,
+            max_ratio: 0.0,
+            orientation: Orientation::Any,
+            max_size: 0,
+            retry: 3,
+        }
+    }
+}
+
+impl ImageRule {
+    /// 检查`path`处的图片是否符合规则，
+    /// 只读取文件头，
+    /// 返回不符合的原因
+    pub fn check(&self, path: &str) -> Result<()> {
+        let size = match std::fs::metadata(path) {
+            Ok(m) => m.len(),
+            Err(e) => return Err(Error::io("can't access image", e)),
+        };
+        self.check_size(size)?;
+
+        let (w, h) = match ImageReader::open(path)
+            .and_then(|r| r.with_guessed_format())
+            .map(|r| r.into_dimensions())
+        {
+            Ok(Ok(d)) => d,
+            Ok(Err(e)) => return Err(Error::decode("error when decode image", e)),
+            Err(e) => return Err(Error::io("can't open image", e)),
+        };
+        if w < self.min_width || h < self.min_height {
+            return Err(Error::rejected(&format!(
+                "分辨率过低: {}x{} < {}x{}",
+                w, h, self.min_width, self.min_height
+            )));
+        }
+        match self.orientation {
+            Orientation::Landscape if w < h => {
+                return Err(Error::rejected(&format!("不是横向图片: {}x{}", w, h)));
+            }
+            Orientation::Portrait if w > h => {
+                return Err(Error::rejected(&format!("不是纵向图片: {}x{}", w, h)));
+            }
+            _ => {}
+        }
+        let ratio = w as f32 / h.max(1) as f32;
+        if (self.min_ratio > 0.0 && ratio < self.min_ratio)
+            || (self.max_ratio > 0.0 && ratio > self.max_ratio)
+        {
+            return Err(Error::rejected(&format!(
+                "宽高比{:.2}不在{}范围内",
+                ratio,
+                self.ratio_text()
+            )));
+        }
+        Ok(())
+    }
+
+    /// 检查图片大小，
+    /// 下载时按`Content-Length`与已接收的字节提前检查
+    pub fn check_size(&self, size: u64) -> Result<()> {
+        if self.max_size > 0 && size > self.max_size {
+            return Err(Error::rejected(&format!(
+                "图片过大: {:.1}MB > {:.1}MB",
+                size as f64 / MB,
+                self.max_size as f64 / MB
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn ratio_text(&self) -> String {
+        let bound = |r: f32| {
+            if r > 0.0 {
+                format!("{:.2}", r)
+            } else {
+                "不限".to_string()
+            }
+        };
+        format!("{} ~ {}", bound(self.min_ratio), bound(self.max_ratio))
+    }
+}
+
+pub const MB: f64 = 1024.0 * 1024.0;
+
+/// 解析`16:9`或`1.78`格式的宽高比
+pub fn parse_ratio(s: &str) -> Option<f32> {
+    let r = match s.split_once(':') {
+        Some((w, h)) => w.parse::<f32>().ok()? / h.parse::<f32>().ok()?,
+        None => s.parse::<f32>().ok()?,
+    };
+    if r.is_finite() && r >= 0.0 {
+        Some(r)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 生成`w`x`h`的PNG图片
+    fn image(name: &str, w: u32, h: u32) -> String {
+        let path = std::env::temp_dir()
+            .join(format!("wow-rule-{}-{}.png", name, std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        image::RgbImage::new(w, h).save(&path).unwrap();
+        path
+    }
+
+    fn rejected(rule: &ImageRule, path: &str) -> bool {
+        matches!(rule.check(path), Err(Error::Rejected { .. }))
+    }
+
+    #[test]
+    fn orientation() {
+        let (wide, tall, square) = (
+            image("o-wide", 160, 90),
+            image("o-tall", 90, 160),
+            image("o-square", 100, 100),
+        );
+        let mut rule = ImageRule::default();
+        for p in [&wide, &tall, &square] {
+            rule.check(p).unwrap();
+        }
+
+        rule.orientation = Orientation::Landscape;
+        rule.check(&wide).unwrap();
+        assert!(rejected(&rule, &tall));
+        // 正方形既是横向也是纵向
+        rule.check(&square).unwrap();
+
+        rule.orientation = Orientation::Portrait;
+        assert!(rejected(&rule, &wide));
+        rule.check(&tall).unwrap();
+        rule.check(&square).unwrap();
+    }
+
+    #[test]
+    fn ratio_bounds() {
+        let (wide, ultrawide, square) = (
+            image("r-wide", 160, 90),
+            image("r-ultrawide", 210, 90),
+            image("r-square", 90, 90),
+        );
+        let rule = ImageRule {
+            min_ratio: parse_ratio("16:10").unwrap(),
+            max_ratio: parse_ratio("21:9").unwrap(),
+            ..Default::default()
+        };
+        rule.check(&wide).unwrap();
+        // 边界上的比例符合
+        rule.check(&ultrawide).unwrap();
+        assert!(rejected(&rule, &square));
+        assert_eq!(rule.ratio_text(), "1.60 ~ 2.33");
+
+        // 只限制一侧
+        let rule = ImageRule {
+            max_ratio: 1.5,
+            ..Default::default()
+        };
+        rule.check(&square).unwrap();
+        assert!(rejected(&rule, &wide));
+        assert_eq!(rule.ratio_text(), "不限 ~ 1.50");
+    }
+
+    #[test]
+    fn size_and_resolution() {
+        let wide = image("s-wide", 160, 90);
+        let rule = ImageRule {
+            min_width: 161,
+            ..Default::default()
+        };
+        assert!(rejected(&rule, &wide));
+        let rule = ImageRule {
+            max_size: 10,
+            ..Default::default()
+        };
+        assert!(rejected(&rule, &wide));
+        assert!(matches!(
+            ImageRule::default().check("/nonexistent/wow.png"),
+            Err(Error::Io { .. })
+        ));
+    }
+
+    #[test]
+    fn ratio_strings() {
+        assert_eq!(parse_ratio("16:9"), Some(16.0 / 9.0));
+        assert_eq!(parse_ratio("1.5"), Some(1.5));
+        assert_eq!(parse_ratio("0"), Some(0.0));
+        assert_eq!(parse_ratio("0:9"), Some(0.0));
+        for bad in [
+            "", ":", "16:", ":9", "16:0", "a:b", "16/9", "16:9:1", "-1", "-16:9", "inf", "NaN",
+            " 16:9",
+        ] {
+            assert_eq!(parse_ratio(bad), None, "{}", bad);
+        }
+    }
+}
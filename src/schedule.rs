@@ -0,0 +1,93 @@
+use super::config::Config;
+use std::time::SystemTime;
+
+/// 上次更新在`last`时，
+/// `now`之后的下一次更新时间，
+/// 已错过的更新在`now`执行，
+/// 处于安静时段时推迟到时段结束
+pub fn next_after(config: &Config, last: SystemTime, now: SystemTime) -> SystemTime {
+    let t = (last + config.get_freq()).max(now);
+    config.get_quiet().quiet_until(t).unwrap_or(t)
+}
+
+/// 是否已超过更新间隔还没有更新，
+/// 即错过了更新
+pub fn overdue(config: &Config, now: SystemTime) -> bool {
+    now.duration_since(config.get_update_at())
+        .is_ok_and(|d| d > config.get_freq())
+}
+
+/// 下一次更新时间
+pub fn next_update(config: &Config, now: SystemTime) -> SystemTime {
+    next_after(config, config.get_update_at(), now)
+}
+
+/// 从`now`起接下来`n`次计划的更新时间
+pub fn upcoming(config: &Config, now: SystemTime, n: usize) -> Vec<SystemTime> {
+    let mut out = Vec::with_capacity(n);
+    let (mut last, mut now) = (config.get_update_at(), now);
+    for _ in 0..n {
+        let t = next_after(config, last, now);
+        out.push(t);
+        (last, now) = (t, t);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quiet::{QuietConf, parse_window};
+    use chrono::{Local, TimeZone};
+    use std::time::Duration;
+
+    fn local(day: u32, h: u32, m: u32) -> SystemTime {
+        Local
+            .with_ymd_and_hms(2024, 6, day, h, m, 0)
+            .unwrap()
+            .into()
+    }
+
+    fn config(update_at: SystemTime, hours: u64, quiet: &[&str]) -> Config {
+        let mut config = Config::default();
+        config.set_update_at(update_at);
+        config.set_freq(Duration::from_secs(hours * 3600));
+        config.set_quiet(QuietConf {
+            hours: quiet.iter().map(|w| parse_window(w).unwrap()).collect(),
+            ..Default::default()
+        });
+        config
+    }
+
+    #[test]
+    fn on_time() {
+        let c = config(local(15, 10, 0), 4, &[]);
+        let now = local(15, 12, 0);
+        assert!(!overdue(&c, now));
+        assert!(next_update(&c, now) == local(15, 14, 0));
+        assert!(upcoming(&c, now, 3) == [local(15, 14, 0), local(15, 18, 0), local(15, 22, 0)]);
+    }
+
+    #[test]
+    fn missed_update() {
+        // 错过的更新立即执行，之后从执行时起计算
+        let c = config(local(15, 2, 0), 4, &[]);
+        let now = local(15, 12, 0);
+        assert!(overdue(&c, now));
+        assert!(next_update(&c, now) == now);
+        assert!(upcoming(&c, now, 3) == [now, local(15, 16, 0), local(15, 20, 0)]);
+        assert!(!overdue(&c, local(15, 6, 0)));
+        // 更新时间在将来时不算错过
+        assert!(!overdue(&c, local(15, 1, 0)));
+    }
+
+    #[test]
+    fn quiet_deferral() {
+        let c = config(local(15, 18, 0), 4, &["22:00-07:00"]);
+        let now = local(15, 19, 0);
+        assert!(next_update(&c, now) == local(16, 7, 0));
+        assert!(upcoming(&c, now, 3) == [local(16, 7, 0), local(16, 11, 0), local(16, 15, 0)]);
+        // 错过的更新处于安静时段时同样推迟
+        assert!(next_update(&c, local(15, 23, 0)) == local(16, 7, 0));
+    }
+}
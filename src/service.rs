@@ -0,0 +1,429 @@
+use super::error::{Error, Result};
+use super::paths;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+// 定时模式下检查更新的间隔，
+// 是否到更新时间由wow自己判断
+const TIMER_SECS: u32 = 600;
+// launchd服务的标识
+const LAUNCHD_LABEL: &str = "com.yawnbright.wow";
+const SYSTEMD_UNITS: [&str; 2] = ["wow.service", "wow.timer"];
+
+/// 系统服务启动wow的方式
+pub struct Service {
+    // wow可执行文件
+    pub exe: String,
+    // 额外的命令行参数，如`--config`
+    pub args: Vec<String>,
+    // 传递给服务的环境变量
+    pub envs: Vec<(String, String)>,
+    // 定时执行一次更新，不常驻后台
+    pub timer: bool,
+}
+
+impl Service {
+    fn command(&self) -> Vec<String> {
+        let mut cmd = vec![self.exe.clone()];
+        if !self.timer {
+            // 异常退出后运行中标记不会清除，
+            // 由服务管理器保证只有一个守护进程
+            cmd.extend(["run".to_string(), "--force".to_string()]);
+        }
+        cmd.extend(self.args.iter().cloned());
+        cmd
+    }
+
+    /// systemd用户服务的单元文件名与内容
+    pub fn systemd_units(&self) -> Vec<(&'static str, String)> {
+        let exec: Vec<String> = self.command().iter().map(|a| systemd_quote(a)).collect();
+        let mut service = String::from(
+            "[Unit]\nDescription=wow 自动壁纸更新\nPartOf=graphical-session.target\nAfter=graphical-session.target\n\n[Service]\n",
+        );
+        if self.timer {
+            service += "Type=oneshot\n";
+            // 未到更新时间
+            service += "SuccessExitStatus=3\n";
+        } else {
+            service += "Type=simple\nRestart=on-failure\nRestartSec=10\n";
+        }
+        service += &format!("ExecStart={}\n", exec.join(" "));
+        for (k, v) in &self.envs {
+            service += &format!("Environment={}\n", systemd_quote(&format!("{}={}", k, v)));
+        }
+        if self.timer {
+            let timer = format!(
+                "[Unit]\nDescription=定时检查壁纸更新\n\n[Timer]\nOnBootSec=1min\nOnUnitActiveSec={}s\n\n[Install]\nWantedBy=timers.target\n",
+                TIMER_SECS
+            );
+            return vec![("wow.service", service), ("wow.timer", timer)];
+        }
+        // 随图形会话启动，此时用户管理器中已有桌面相关的环境变量
+        service += "\n[Install]\nWantedBy=graphical-session.target\n";
+        vec![("wow.service", service)]
+    }
+
+    /// launchd LaunchAgent的plist
+    pub fn launchd_plist(&self) -> String {
+        let mut out = String::from(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+"#,
+        );
+        out += &format!("  <key>Label</key>\n  <string>{}</string>\n", LAUNCHD_LABEL);
+        out += "  <key>ProgramArguments</key>\n  <array>\n";
+        for a in self.command() {
+            out += &format!("    <string>{}</string>\n", xml_escape(&a));
+        }
+        out += "  </array>\n";
+        if !self.envs.is_empty() {
+            out += "  <key>EnvironmentVariables</key>\n  <dict>\n";
+            for (k, v) in &self.envs {
+                out += &format!(
+                    "    <key>{}</key>\n    <string>{}</string>\n",
+                    xml_escape(k),
+                    xml_escape(v)
+                );
+            }
+            out += "  </dict>\n";
+        }
+        out += "  <key>RunAtLoad</key>\n  <true/>\n";
+        if self.timer {
+            out += &format!(
+                "  <key>StartInterval</key>\n  <integer>{}</integer>\n",
+                TIMER_SECS
+            );
+        } else {
+            // 只在异常退出时重启
+            out += "  <key>KeepAlive</key>\n  <dict>\n    <key>SuccessfulExit</key>\n    <false/>\n  </dict>\n";
+        }
+        out += "</dict>\n</plist>\n";
+        out
+    }
+
+    /// 写入服务文件并启用，
+    /// 返回写入的文件
+    pub fn install(&self) -> Result<Vec<String>> {
+        if cfg!(target_os = "macos") {
+            let path = launchd_path()?;
+            // 重新安装时先卸载旧的
+            let _ = Command::new("launchctl").args(["unload", &path]).output();
+            write(&path, &self.launchd_plist())?;
+            run(Command::new("launchctl").args(["load", "-w", &path]))?;
+            return Ok(vec![path]);
+        }
+        if !cfg!(target_os = "linux") {
+            return Err(Error::config("当前系统不支持安装服务"));
+        }
+
+        // 切换模式时先删除另一种模式的单元
+        uninstall()?;
+        let dir = systemd_dir()?;
+        let mut written = vec![];
+        for (name, content) in self.systemd_units() {
+            let path = format!("{}/{}", dir, name);
+            write(&path, &content)?;
+            written.push(path);
+        }
+        systemctl(&["daemon-reload"])?;
+        let unit = if self.timer {
+            "wow.timer"
+        } else {
+            "wow.service"
+        };
+        systemctl(&["enable", "--now", unit])?;
+        Ok(written)
+    }
+}
+
+/// 停用并删除服务文件，
+/// 返回删除的文件
+pub fn uninstall() -> Result<Vec<String>> {
+    let installed = installed()?;
+    if installed.is_empty() {
+        return Ok(installed);
+    }
+    if cfg!(target_os = "macos") {
+        let _ = Command::new("launchctl")
+            .args(["unload", "-w", &installed[0]])
+            .output();
+    } else {
+        // 未启用时会失败
+        let _ = Command::new("systemctl")
+            .args(["--user", "disable", "--now"])
+            .args(SYSTEMD_UNITS)
+            .output();
+    }
+    for path in &installed {
+        fs::remove_file(path).map_err(|e| Error::io(&format!("无法删除{}", path), e))?;
+    }
+    if !cfg!(target_os = "macos") {
+        systemctl(&["daemon-reload"])?;
+    }
+    Ok(installed)
+}
+
+/// 重启服务管理的守护进程，
+/// 没有以常驻模式安装时返回false
+pub fn restart() -> Result<bool> {
+    let installed = installed()?;
+    let Some(path) = installed.first() else {
+        return Ok(false);
+    };
+    if cfg!(target_os = "macos") {
+        let daemon = fs::read_to_string(path).is_ok_and(|c| c.contains("<key>KeepAlive</key>"));
+        if !daemon {
+            return Ok(false);
+        }
+        let _ = Command::new("launchctl").args(["unload", path]).output();
+        run(Command::new("launchctl").args(["load", "-w", path]))?;
+        return Ok(true);
+    }
+    // 定时模式下每次执行的都是新的可执行文件
+    if installed.iter().any(|p| p.ends_with("wow.timer")) {
+        return Ok(false);
+    }
+    systemctl(&["restart", "wow.service"])?;
+    Ok(true)
+}
+
+/// 已安装的服务文件
+pub fn installed() -> Result<Vec<String>> {
+    let paths = if cfg!(target_os = "macos") {
+        vec![launchd_path()?]
+    } else {
+        let dir = systemd_dir()?;
+        SYSTEMD_UNITS
+            .iter()
+            .map(|u| format!("{}/{}", dir, u))
+            .collect()
+    };
+    Ok(paths
+        .into_iter()
+        .filter(|p| Path::new(p).exists())
+        .collect())
+}
+
+/// 服务文件与服务管理器报告的状态
+pub fn status() -> Result<Vec<(String, String)>> {
+    let mut out = vec![];
+    for path in installed()? {
+        let state = if cfg!(target_os = "macos") {
+            match Command::new("launchctl")
+                .args(["list", LAUNCHD_LABEL])
+                .output()
+            {
+                Ok(o) if o.status.success() => "已加载".to_string(),
+                _ => "未加载".to_string(),
+            }
+        } else {
+            let unit = Path::new(&path)
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let query = |what: &str| {
+                Command::new("systemctl")
+                    .args(["--user", what, &unit])
+                    .output()
+                    .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+                    .unwrap_or_default()
+            };
+            format!("{} | {}", query("is-enabled"), query("is-active"))
+        };
+        out.push((path, state));
+    }
+    Ok(out)
+}
+
+fn systemd_dir() -> Result<String> {
+    Ok(paths::base_dir("XDG_CONFIG_HOME", ".config")? + "/systemd/user")
+}
+
+fn launchd_path() -> Result<String> {
+    Ok(format!(
+        "{}/Library/LaunchAgents/{}.plist",
+        paths::home()?,
+        LAUNCHD_LABEL
+    ))
+}
+
+fn write(path: &str, content: &str) -> Result<()> {
+    if let Some(dir) = Path::new(path).parent() {
+        fs::create_dir_all(dir).map_err(|e| Error::io(&format!("无法创建{}", dir.display()), e))?;
+    }
+    fs::write(path, content).map_err(|e| Error::io(&format!("无法写入{}", path), e))
+}
+
+fn systemctl(args: &[&str]) -> Result<()> {
+    run(Command::new("systemctl").arg("--user").args(args))
+}
+
+fn run(cmd: &mut Command) -> Result<()> {
+    let name = format!("{:?}", cmd.get_program());
+    match cmd.output() {
+        Ok(o) if o.status.success() => Ok(()),
+        Ok(o) => Err(Error::command(
+            &format!("{}执行失败", name),
+            String::from_utf8_lossy(&o.stderr).trim().to_string(),
+        )),
+        Err(e) => Err(Error::command(&format!("{}执行失败", name), e)),
+    }
+}
+
+/// systemd单元文件中的参数，
+/// 含空白或引号时加双引号，`%`需要转义
+fn systemd_quote(s: &str) -> String {
+    let s = s.replace('%', "%%");
+    if s.contains(char::is_whitespace) || s.contains(['"', '\\', '\'']) {
+        format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        s
+    }
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service(timer: bool) -> Service {
+        Service {
+            exe: "/home/me/my apps/wow".to_string(),
+            args: vec!["--config".to_string(), "/tmp/a b/wow.conf".to_string()],
+            envs: vec![("WOW_HOME".to_string(), "/tmp/50%\"x\"".to_string())],
+            timer,
+        }
+    }
+
+    #[test]
+    fn systemd_daemon() {
+        let units = service(false).systemd_units();
+        assert_eq!(units.len(), 1);
+        assert_eq!(units[0].0, "wow.service");
+        assert_eq!(
+            units[0].1,
+            r#"[Unit]
+Description=wow 自动壁纸更新
+PartOf=graphical-session.target
+After=graphical-session.target
+
+[Service]
+Type=simple
+Restart=on-failure
+RestartSec=10
+ExecStart="/home/me/my apps/wow" run --force --config "/tmp/a b/wow.conf"
+Environment="WOW_HOME=/tmp/50%%\"x\""
+
+[Install]
+WantedBy=graphical-session.target
+"#
+        );
+    }
+
+    #[test]
+    fn systemd_timer() {
+        let units = service(true).systemd_units();
+        assert_eq!(units.len(), 2);
+        assert_eq!(units[0].0, "wow.service");
+        assert_eq!(
+            units[0].1,
+            r#"[Unit]
+Description=wow 自动壁纸更新
+PartOf=graphical-session.target
+After=graphical-session.target
+
+[Service]
+Type=oneshot
+SuccessExitStatus=3
+ExecStart="/home/me/my apps/wow" --config "/tmp/a b/wow.conf"
+Environment="WOW_HOME=/tmp/50%%\"x\""
+"#
+        );
+        assert_eq!(units[1].0, "wow.timer");
+        assert_eq!(
+            units[1].1,
+            "[Unit]
+Description=定时检查壁纸更新
+
+[Timer]
+OnBootSec=1min
+OnUnitActiveSec=600s
+
+[Install]
+WantedBy=timers.target
+"
+        );
+    }
+
+    const PLIST_HEAD: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+  <key>Label</key>
+  <string>com.yawnbright.wow</string>
+"#;
+
+    #[test]
+    fn launchd_daemon() {
+        let mut s = service(false);
+        s.args = vec!["--config".to_string(), "/tmp/<a&b>.conf".to_string()];
+        assert_eq!(
+            s.launchd_plist(),
+            PLIST_HEAD.to_string()
+                + r#"  <key>ProgramArguments</key>
+  <array>
+    <string>/home/me/my apps/wow</string>
+    <string>run</string>
+    <string>--force</string>
+    <string>--config</string>
+    <string>/tmp/&lt;a&amp;b&gt;.conf</string>
+  </array>
+  <key>EnvironmentVariables</key>
+  <dict>
+    <key>WOW_HOME</key>
+    <string>/tmp/50%"x"</string>
+  </dict>
+  <key>RunAtLoad</key>
+  <true/>
+  <key>KeepAlive</key>
+  <dict>
+    <key>SuccessfulExit</key>
+    <false/>
+  </dict>
+</dict>
+</plist>
+"#
+        );
+    }
+
+    #[test]
+    fn launchd_timer() {
+        let mut s = service(true);
+        s.envs.clear();
+        assert_eq!(
+            s.launchd_plist(),
+            PLIST_HEAD.to_string()
+                + r#"  <key>ProgramArguments</key>
+  <array>
+    <string>/home/me/my apps/wow</string>
+    <string>--config</string>
+    <string>/tmp/a b/wow.conf</string>
+  </array>
+  <key>RunAtLoad</key>
+  <true/>
+  <key>StartInterval</key>
+  <integer>600</integer>
+</dict>
+</plist>
+"#
+        );
+    }
+}
@@ -0,0 +1,346 @@
+use super::error::{Error, Result};
+use super::filter::Filter;
+use super::monitor::{self, Output};
+use bincode::{Decode, Encode};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// 锁屏壁纸配置
+#[derive(Encode, Decode, Clone, Default)]
+pub struct LockConf {
+    pub enabled: bool,
+    // 锁屏壁纸单独的处理，只使用亮度和模糊
+    pub filter: Filter,
+    // 额外复制一份到该路径，供登录界面读取，为空则不复制
+    pub copy_to: String,
+}
+
+/// 设置壁纸的后端
+pub enum Setter {
+    // macOS下随程序分发的`updater`
+    Updater(String),
+    // gsettings
+    Gnome,
+    // swaymsg
+    Sway,
+    // feh，适用于大多数X11窗口管理器
+    Feh,
+}
+
+impl Setter {
+    /// 根据当前桌面环境选择后端，
+    /// 可执行文件旁有`updater`时与旧版本一样使用它
+    pub fn detect(exe_dir: &str) -> Self {
+        let updater = exe_dir.to_string() + "/updater";
+        if cfg!(target_os = "macos") || Path::new(&updater).is_file() {
+            return Setter::Updater(updater);
+        }
+        let desktop = std::env::var("XDG_CURRENT_DESKTOP").unwrap_or_default();
+        if desktop.to_uppercase().contains("GNOME") {
+            Setter::Gnome
+        } else if std::env::var("SWAYSOCK").is_ok() {
+            Setter::Sway
+        } else {
+            Setter::Feh
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Setter::Updater(_) => "updater",
+            Setter::Gnome => "GNOME",
+            Setter::Sway => "sway",
+            Setter::Feh => "feh",
+        }
+    }
+
+    /// 是否能设置锁屏壁纸，
+    /// 不能时只能复制到`LockConf::copy_to`
+    pub fn supports_lock(&self) -> bool {
+        matches!(self, Setter::Gnome | Setter::Sway)
+    }
+
+    /// 是否由桌面自己在暗色模式下切换到暗色版本
+    pub fn switches_dark(&self) -> bool {
+        matches!(self, Setter::Gnome)
+    }
+
+    /// 设置壁纸，
+    /// `dark`为暗色版本，
+    /// GNOME写入`picture-uri-dark`，
+    /// 其它后端在设置时系统处于暗色模式则使用，
+    /// 之后系统切换时由守护进程重新设置
+    pub fn apply(&self, light: &str, dark: Option<&str>) -> Result<()> {
+        let img = match dark {
+            Some(d) if !self.switches_dark() && prefers_dark() => d,
+            _ => light,
+        };
+        match self {
+            Setter::Updater(updater) => run(Command::new(updater).arg(img)),
+            // 保留用户设置的缩放方式
+            Setter::Gnome => gnome_set(light, dark.unwrap_or(light), None),
+            Setter::Sway => run(Command::new("swaymsg").args(["output", "*", "bg", img, "fill"])),
+            Setter::Feh => run(Command::new("feh").args(["--bg-fill", img])),
+        }
+    }
+
+    /// 列出已启用的显示器
+    pub fn outputs(&self) -> Result<Vec<Output>> {
+        let outputs = match self {
+            Setter::Updater(updater) => {
+                output(Command::new(updater).arg("--list")).map(|s| monitor::parse_updater(&s))
+            }
+            Setter::Sway => output(Command::new("swaymsg").args(["-t", "get_outputs", "-r"]))
+                .map(|s| monitor::parse_sway(&s)),
+            Setter::Gnome | Setter::Feh => output(Command::new("xrandr").arg("--listmonitors"))
+                .map(|s| monitor::parse_xrandr(&s)),
+        };
+        match outputs {
+            Some(o) if !o.is_empty() => Ok(o),
+            _ => Err(Error::Setter {
+                msg: "无法获取显示器信息".to_string(),
+                source: None,
+            }),
+        }
+    }
+
+    /// 为每个显示器分别设置壁纸，
+    /// `parts`中依次为显示器、图片及其暗色版本，
+    /// 只支持跨屏铺满的后端会将图片拼接后写入`span`
+    pub fn apply_outputs(
+        &self,
+        parts: &[(Output, String, Option<String>)],
+        span: &str,
+    ) -> Result<()> {
+        let dark = prefers_dark();
+        let pick = |(_, light, d): &(Output, String, Option<String>)| -> String {
+            match d {
+                Some(d) if dark => d.clone(),
+                _ => light.clone(),
+            }
+        };
+        match self {
+            Setter::Updater(updater) => {
+                for p in parts {
+                    run(Command::new(updater).args(["--screen", &p.0.id, &pick(p)]))?;
+                }
+                Ok(())
+            }
+            Setter::Sway => {
+                for p in parts {
+                    run(Command::new("swaymsg").args([
+                        "output",
+                        &p.0.name,
+                        "bg",
+                        &pick(p),
+                        "fill",
+                    ]))?;
+                }
+                Ok(())
+            }
+            Setter::Feh => {
+                let mut cmd = Command::new("feh");
+                cmd.arg("--bg-fill");
+                for p in feh_order(parts)? {
+                    cmd.arg(pick(p));
+                }
+                run(&mut cmd)
+            }
+            Setter::Gnome => {
+                // GNOME不支持为每个显示器单独设置，
+                // 拼接为一张图片后跨屏铺满
+                let light: Vec<(Output, String)> = parts
+                    .iter()
+                    .map(|(o, l, _)| (o.clone(), l.clone()))
+                    .collect();
+                monitor::compose(&light, &(span.to_string() + ".jpg"))?;
+                let mut dark_span = span.to_string() + ".jpg";
+                if parts.iter().all(|(_, _, d)| d.is_some()) {
+                    let dark: Vec<(Output, String)> = parts
+                        .iter()
+                        .map(|(o, _, d)| (o.clone(), d.clone().unwrap_or_default()))
+                        .collect();
+                    dark_span = span.to_string() + "-dark.jpg";
+                    monitor::compose(&dark, &dark_span)?;
+                }
+                gnome_set(&(span.to_string() + ".jpg"), &dark_span, Some("spanned"))
+            }
+        }
+    }
+
+    /// 设置锁屏壁纸
+    ///
+    /// - GNOME写入`org.gnome.desktop.screensaver picture-uri`
+    /// - sway更新swaylock配置中的`image`
+    /// - 其它后端只复制到`copy_to`，没有设置时返回错误
+    pub fn apply_lock(&self, img: &str, copy_to: &str) -> Result<()> {
+        if !self.supports_lock() && copy_to.is_empty() {
+            return Err(self.lock_unsupported());
+        }
+        let err = match self {
+            Setter::Gnome => run(Command::new("gsettings").args([
+                "set",
+                "org.gnome.desktop.screensaver",
+                "picture-uri",
+                &file_uri(img),
+            ])),
+            Setter::Sway => swaylock_set(img),
+            Setter::Updater(_) | Setter::Feh => Ok(()),
+        };
+        if err.is_err() || copy_to.is_empty() {
+            return err;
+        }
+        match fs::copy(img, copy_to) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(Error::io(&format!("复制锁屏壁纸到{}失败", copy_to), e)),
+        }
+    }
+
+    pub fn lock_unsupported(&self) -> Error {
+        Error::Setter {
+            msg: format!(
+                "{}不支持设置锁屏壁纸\n使用`wow lock copy <路径>`复制到锁屏程序读取的位置",
+                self.name()
+            ),
+            source: None,
+        }
+    }
+}
+
+/// feh按Xinerama序号为每个屏幕分配一张图片，
+/// 序号来自`xrandr --listmonitors`
+fn feh_order(
+    parts: &[(Output, String, Option<String>)],
+) -> Result<Vec<&(Output, String, Option<String>)>> {
+    let mut sorted = vec![];
+    for p in parts {
+        match p.0.id.parse::<usize>() {
+            Ok(index) => sorted.push((index, p)),
+            Err(_) => {
+                return Err(Error::Setter {
+                    msg: format!("显示器{}的Xinerama序号`{}`无效", p.0.name, p.0.id),
+                    source: None,
+                });
+            }
+        }
+    }
+    sorted.sort_by_key(|(index, _)| *index);
+    Ok(sorted.into_iter().map(|(_, p)| p).collect())
+}
+
+/// 更新swaylock配置中的`image`，
+/// 保留其它配置项
+fn swaylock_set(img: &str) -> Result<()> {
+    let dir = match std::env::var("XDG_CONFIG_HOME") {
+        Ok(d) if !d.is_empty() => d,
+        _ => std::env::var("HOME").unwrap_or_default() + "/.config",
+    } + "/swaylock";
+    let path = dir.clone() + "/config";
+    let old = fs::read_to_string(&path).unwrap_or_default();
+    let mut lines: Vec<String> = old
+        .lines()
+        .filter(|l| !l.trim_start().starts_with("image="))
+        .map(|l| l.to_string())
+        .collect();
+    lines.push(format!("image={}", img));
+    if let Err(e) = fs::create_dir_all(&dir).and_then(|_| fs::write(&path, lines.join("\n") + "\n"))
+    {
+        return Err(Error::io("写入swaylock配置失败", e));
+    }
+    Ok(())
+}
+
+/// 系统当前是否为暗色模式
+pub fn prefers_dark() -> bool {
+    if cfg!(target_os = "macos") {
+        // 亮色模式下该键不存在
+        return output(Command::new("defaults").args(["read", "-g", "AppleInterfaceStyle"]))
+            .is_some_and(|s| s.trim() == "Dark");
+    }
+    if let Some(s) = output(Command::new("gsettings").args([
+        "get",
+        "org.gnome.desktop.interface",
+        "color-scheme",
+    ])) {
+        return s.contains("prefer-dark");
+    }
+    std::env::var("GTK_THEME").is_ok_and(|t| t.to_lowercase().contains("dark"))
+}
+
+/// 设置GNOME壁纸及其暗色版本，
+/// `options`为缩放方式，None时不修改
+fn gnome_set(light: &str, dark: &str, options: Option<&str>) -> Result<()> {
+    let mut settings = vec![
+        ("picture-uri", file_uri(light)),
+        ("picture-uri-dark", file_uri(dark)),
+    ];
+    if let Some(options) = options {
+        settings.insert(0, ("picture-options", options.to_string()));
+    }
+    for (key, value) in settings {
+        run(Command::new("gsettings").args(["set", "org.gnome.desktop.background", key, &value]))?;
+    }
+    Ok(())
+}
+
+fn file_uri(path: &str) -> String {
+    format!("file://{}", path)
+}
+
+fn run(cmd: &mut Command) -> Result<()> {
+    match cmd.status() {
+        Ok(s) if s.success() => Ok(()),
+        Ok(s) => Err(Error::setter("设置壁纸失败", s.to_string())),
+        Err(e) => Err(Error::setter("设置壁纸失败", e)),
+    }
+}
+
+fn output(cmd: &mut Command) -> Option<String> {
+    match cmd.output() {
+        Ok(o) if o.status.success() => Some(String::from_utf8_lossy(&o.stdout).to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(name: &str, id: &str) -> Output {
+        Output {
+            name: name.to_string(),
+            id: id.to_string(),
+            x: 0,
+            y: 0,
+            width: 1920,
+            height: 1080,
+        }
+    }
+
+    fn part(name: &str, id: &str, img: &str) -> (Output, String, Option<String>) {
+        (output(name, id), img.to_string(), None)
+    }
+
+    #[test]
+    fn feh_xinerama_order() {
+        let parts = [
+            part("HDMI-1", "2", "c"),
+            part("eDP-1", "0", "a"),
+            part("DP-1", "10", "d"),
+            part("DP-2", "1", "b"),
+        ];
+        let imgs: Vec<&str> = feh_order(&parts)
+            .unwrap()
+            .iter()
+            .map(|(_, img, _)| img.as_str())
+            .collect();
+        assert_eq!(imgs, ["a", "b", "c", "d"]);
+
+        let parts = [part("eDP-1", "0", "a"), part("HDMI-1", "HDMI-1", "b")];
+        let err = feh_order(&parts).err().unwrap();
+        assert!(matches!(err, Error::Setter { .. }), "{}", err);
+        assert!(err.to_string().contains("HDMI-1"), "{}", err);
+        assert!(feh_order(&[]).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,328 @@
+use super::config::{self, Config};
+use super::error::{Error, Result};
+use super::external::{self, Location, Picked};
+use super::logger;
+use super::meta::ImageMeta;
+use super::mirror::MirrorConf;
+use super::rule::ImageRule;
+use std::fs::{self, File};
+use std::io::Write;
+use std::time::{Instant, SystemTime};
+
+/// 图片来源，
+/// 配置中以编号保存，见`Config::name_of`
+#[derive(Clone, PartialEq)]
+pub enum Source {
+    /// 必应随机历史图片，编号1
+    BingRandom,
+    /// 必应每日图片，编号2
+    BingDaily,
+    /// 执行命令获取图片，编号3
+    Command(String),
+    /// 本地图片，用于设置动态壁纸的帧
+    File(String),
+}
+
+impl Source {
+    /// 编号为`id`的来源，
+    /// `cmd`为自定义命令，其它来源忽略
+    pub fn from_id(id: u8, cmd: &str) -> Self {
+        match id {
+            2 => Source::BingDaily,
+            config::CMD_SOURCE => Source::Command(cmd.to_string()),
+            _ => Source::BingRandom,
+        }
+    }
+
+    pub fn id(&self) -> u8 {
+        match self {
+            Source::BingRandom => 1,
+            Source::BingDaily => 2,
+            Source::Command(_) => config::CMD_SOURCE,
+            Source::File(_) => config::FILE_SOURCE,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        Config::name_of(self.id())
+    }
+
+    /// 是否每次获取的都是同一张图片，
+    /// 此时不符合规则也不重新获取
+    pub fn repeats(&self) -> bool {
+        matches!(self, Source::BingDaily | Source::File(_))
+    }
+}
+
+/// 从`source`获取符合`rule`的图片并保存到`save_path`，
+/// 返回图片的信息，
+/// 必应来源按`mirrors`的顺序尝试各个镜像并记录结果，
+/// 不符合时重新获取，超过重试次数或来源总是同一张图片时
+/// 返回最后一次不符合的原因
+pub async fn fetch(
+    client: &reqwest::Client,
+    source: &Source,
+    mirrors: &mut MirrorConf,
+    save_path: &str,
+    rule: &ImageRule,
+) -> Result<ImageMeta> {
+    // 用户指定的图片不检查规则
+    if let Source::File(_) = source {
+        return obtain(client, source, mirrors, save_path, &ImageRule::default()).await;
+    }
+    let mut tries = 0;
+    loop {
+        // 下载时发现过大也重新获取
+        let err = match obtain(client, source, mirrors, save_path, rule).await {
+            Ok(meta) => match rule.check(save_path) {
+                Ok(_) => return Ok(meta),
+                Err(e) => e,
+            },
+            Err(e @ Error::Rejected { .. }) => e,
+            Err(e) => return Err(e),
+        };
+        let _ = fs::remove_file(save_path);
+        if tries >= rule.retry || source.repeats() {
+            return Err(Error::Rejected {
+                msg: "没有获取到符合规则的图片".to_string(),
+                source: Some(Box::new(err)),
+            });
+        }
+        tries += 1;
+        logger::warn(&format!(
+            "{}，重新获取({}/{})",
+            err.report(),
+            tries,
+            rule.retry
+        ));
+    }
+}
+
+/// 从`source`获取一张图片保存到`save_path`，
+/// 超过`rule`的大小限制时停止下载
+async fn obtain(
+    client: &reqwest::Client,
+    source: &Source,
+    mirrors: &mut MirrorConf,
+    save_path: &str,
+    rule: &ImageRule,
+) -> Result<ImageMeta> {
+    let picked = match source {
+        Source::Command(cmd) => external::pick(cmd).await?,
+        Source::File(p) => Picked {
+            location: Location::Path(p.clone()),
+            title: String::new(),
+            copyright: String::new(),
+        },
+        _ => {
+            let real_url = download_mirrors(client, source.id(), mirrors, save_path, rule).await?;
+            return Ok(ImageMeta::new(&real_url, source.id(), save_path));
+        }
+    };
+    let url = match &picked.location {
+        Location::Url(u) => download(client, u, save_path, rule).await?,
+        Location::Path(p) => {
+            // 复制一份，避免删除旧壁纸时删除用户的文件
+            fs::copy(p, save_path).map_err(|e| Error::io(&format!("无法复制{}", p), e))?;
+            format!("file://{}", p)
+        }
+    };
+    if let Err(e) = external::to_jpeg(save_path) {
+        let _ = fs::remove_file(save_path);
+        return Err(e);
+    }
+    let mut meta = ImageMeta::new(&url, source.id(), save_path);
+    if !picked.title.is_empty() {
+        meta.title = picked.title;
+    }
+    meta.copyright = picked.copyright;
+    Ok(meta)
+}
+
+/// 依次尝试`source`的镜像，直到下载成功，
+/// 结果记入`mirrors`，都失败时返回最后一个错误
+async fn download_mirrors(
+    client: &reqwest::Client,
+    source: u8,
+    mirrors: &mut MirrorConf,
+    save_path: &str,
+    rule: &ImageRule,
+) -> Result<String> {
+    let mut urls = mirrors.candidates(source, SystemTime::now());
+    if urls.is_empty() {
+        urls.push(Config::url_of(source).to_string());
+    }
+    let mut last_err = Error::config(&format!("{}没有可用的镜像", Config::name_of(source)));
+    for (i, url) in urls.iter().enumerate() {
+        let start = Instant::now();
+        match download(client, url, save_path, rule).await {
+            Ok(real_url) => {
+                mirrors.record(url, Some(start.elapsed()));
+                return Ok(real_url);
+            }
+            // 无法写入文件与图片过大都与镜像无关
+            Err(e @ (Error::Io { .. } | Error::Rejected { .. })) => return Err(e),
+            Err(e) => {
+                mirrors.record(url, None);
+                if i + 1 < urls.len() {
+                    logger::warn(&format!("镜像{}失败，尝试下一个: {}", url, e.report()));
+                }
+                last_err = e;
+            }
+        }
+    }
+    Err(last_err)
+}
+
+/// 下载`url`处的图片到`save_path`，
+/// 返回重定向后的实际地址，
+/// 超过`rule`的大小限制时不保存
+async fn download(
+    client: &reqwest::Client,
+    url: &str,
+    save_path: &str,
+    rule: &ImageRule,
+) -> Result<String> {
+    let real_url = match client.get(url).send().await {
+        Err(e) => return Err(Error::network("fetch image error", e)),
+        Ok(resp) => resp.url().clone().to_string(),
+    };
+
+    let mut resp = match client.get(&real_url).send().await {
+        Err(e) => return Err(Error::network("fetch image error", e)),
+        Ok(resp) => resp,
+    };
+    if !resp.status().is_success() {
+        return Err(Error::Http {
+            status: resp.status().as_u16(),
+            url: real_url,
+        });
+    }
+    if let Some(len) = resp.content_length() {
+        rule.check_size(len)?;
+    }
+    let mut file =
+        File::create(save_path).map_err(|e| Error::io("can't open the file to save image", e))?;
+    // 没有`Content-Length`或与实际不符时，边接收边检查
+    let mut received = 0;
+    let res = loop {
+        match resp.chunk().await {
+            Ok(Some(data)) => {
+                received += data.len() as u64;
+                if let Err(e) = rule.check_size(received) {
+                    break Err(e);
+                }
+                if let Err(e) = file.write_all(&data) {
+                    break Err(Error::io("error when saving image", e));
+                }
+            }
+            Ok(None) => return Ok(real_url),
+            Err(e) => break Err(Error::network("error when read image data", e)),
+        }
+    };
+    drop(file);
+    let _ = fs::remove_file(save_path);
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{self, response, unsized_response};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn png(w: u32, h: u32) -> Vec<u8> {
+        let mut out = std::io::Cursor::new(vec![]);
+        image::DynamicImage::new_rgb8(w, h)
+            .write_to(&mut out, image::ImageFormat::Png)
+            .unwrap();
+        out.into_inner()
+    }
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("wow-source-{}-{}.png", name, std::process::id()))
+            .to_string_lossy()
+            .to_string()
+    }
+
+    /// 记录请求次数的服务器
+    async fn counting_server(respond: fn(&str) -> Vec<u8>) -> (String, Arc<AtomicUsize>) {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let h = hits.clone();
+        let url = test_server::serve(move |path| {
+            h.fetch_add(1, Ordering::SeqCst);
+            respond(path)
+        })
+        .await;
+        (url, hits)
+    }
+
+    fn limit(max_size: u64) -> ImageRule {
+        ImageRule {
+            max_size,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn reject_by_content_length() {
+        // 声明的长度超过限制时不读取内容，否则会因内容不完整而失败
+        let (url, _) = counting_server(|_| {
+            b"HTTP/1.1 200 OK\r\nContent-Length: 1048576\r\nConnection: close\r\n\r\nshort".to_vec()
+        })
+        .await;
+        let path = temp_path("length");
+        let err = download(&test_server::client(), &url, &path, &limit(1024))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Rejected { .. }), "{}", err);
+        assert!(!std::path::Path::new(&path).exists());
+    }
+
+    #[tokio::test]
+    async fn stop_reading_over_limit() {
+        let (url, _) = counting_server(|_| unsized_response(&[0u8; 64 * 1024])).await;
+        let path = temp_path("stream");
+        let err = download(&test_server::client(), &url, &path, &limit(1024))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Rejected { .. }), "{}", err);
+        assert!(!std::path::Path::new(&path).exists());
+
+        download(&test_server::client(), &url, &path, &limit(0))
+            .await
+            .unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().len(), 64 * 1024);
+        let _ = fs::remove_file(&path);
+    }
+
+    /// 获取不符合规则的图片，返回请求次数
+    async fn rejected_requests(source_id: u8) -> usize {
+        let (url, hits) = counting_server(|_| response(200, &png(4, 3))).await;
+        let mut mirrors = MirrorConf { mirrors: vec![] };
+        mirrors.add(source_id, &url);
+        let rule = ImageRule {
+            min_width: 100,
+            retry: 2,
+            ..Default::default()
+        };
+        let path = temp_path(&format!("retry-{}", source_id));
+        let source = Source::from_id(source_id, "");
+        let err = fetch(&test_server::client(), &source, &mut mirrors, &path, &rule)
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(err, Error::Rejected { .. }), "{}", err);
+        assert!(!std::path::Path::new(&path).exists());
+        // 每次下载先请求一次获取重定向后的地址
+        hits.load(Ordering::SeqCst) / 2
+    }
+
+    #[tokio::test]
+    async fn retry_random_but_not_daily() {
+        assert_eq!(rejected_requests(1).await, 3);
+        assert_eq!(rejected_requests(2).await, 1);
+    }
+}
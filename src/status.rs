@@ -0,0 +1,290 @@
+use super::config::Config;
+use super::error::{Error, Result};
+use super::meta::ImageMeta;
+use super::schedule;
+#[cfg(target_family = "windows")]
+use std::process::Command;
+use std::time::{Duration, SystemTime};
+use termion::color;
+
+// 等待守护进程退出的最长时间
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// `wow status`输出的运行状态
+pub struct Status {
+    pub running: bool,
+    pub pid: u32,
+    pub image: String,
+    pub meta: ImageMeta,
+    pub source: u8,
+    // 秒
+    pub freq: u64,
+    pub last_success: SystemTime,
+    pub last_error: String,
+    pub last_error_at: SystemTime,
+    pub next_update: SystemTime,
+    // 数据目录中图片占用的空间，字节
+    pub archive_size: u64,
+}
+
+impl Status {
+    pub fn collect(config: &Config, data_dir: &str) -> Self {
+        let pid = config.get_pid();
+        let running = config.working && pid != 0 && alive(pid);
+        let last_success = config.get_update_at();
+        Status {
+            running,
+            pid: if running { pid } else { 0 },
+            image: config.get_cur_img().to_string(),
+            meta: config.get_cur_meta().clone(),
+            source: config.get_source(),
+            freq: config.get_freq().as_secs(),
+            last_success,
+            last_error: config.get_last_error().to_string(),
+            last_error_at: config.get_last_error_at(),
+            next_update: schedule::next_update(config, SystemTime::now()),
+            archive_size: archive_size(data_dir),
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        let unix = |t: SystemTime| {
+            t.duration_since(SystemTime::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0)
+        };
+        serde_json::json!({
+            "running": self.running,
+            "pid": if self.running { Some(self.pid) } else { None },
+            "image": self.image,
+            "title": self.meta.title,
+            "copyright": self.meta.copyright,
+            "url": self.meta.url,
+            "width": self.meta.width,
+            "height": self.meta.height,
+            "size": self.meta.size,
+            "source": self.source,
+            "source_name": Config::name_of(self.source),
+            "freq": self.freq,
+            "last_success": unix(self.last_success),
+            "last_error": if self.last_error.is_empty() { None } else { Some(&self.last_error) },
+            "last_error_at": if self.last_error.is_empty() { None } else { Some(unix(self.last_error_at)) },
+            "next_update": if self.running { Some(unix(self.next_update)) } else { None },
+            "archive_size": self.archive_size,
+        })
+    }
+
+    pub fn print(&self) {
+        let label = |s: &str| {
+            print!(
+                "  {}{}{} ",
+                color::Fg(color::LightGreen),
+                s,
+                color::Fg(color::Reset)
+            )
+        };
+        label("守护进程:");
+        if self.running {
+            println!(
+                "{}运行中{} (PID {})",
+                color::Fg(color::LightCyan),
+                color::Fg(color::Reset),
+                self.pid
+            );
+        } else {
+            println!(
+                "{}未运行{}，使用`wow run`开启自动更新",
+                color::Fg(color::LightRed),
+                color::Fg(color::Reset)
+            );
+        }
+
+        label("当前壁纸:");
+        if self.image.is_empty() {
+            println!("无");
+        } else {
+            println!("{}", self.image);
+            if !self.meta.title.is_empty() {
+                println!("            {}", self.meta.title);
+            }
+            if !self.meta.copyright.is_empty() {
+                println!("            {}", self.meta.copyright);
+            }
+            println!(
+                "            {}x{} {:.1}MB",
+                self.meta.width,
+                self.meta.height,
+                self.meta.size as f64 / 1024.0 / 1024.0
+            );
+        }
+
+        label("图片来源:");
+        println!("{}", Config::name_of(self.source));
+        label("更新频率:");
+        println!("每{}", format_duration(self.freq));
+        label("上次更新:");
+        println!("{}", relative(self.last_success));
+        label("下次更新:");
+        if self.running {
+            println!("{}", relative(self.next_update));
+        } else {
+            println!("守护进程未运行");
+        }
+        label("上次错误:");
+        if self.last_error.is_empty() {
+            println!("无");
+        } else {
+            println!(
+                "{} {}{}{}",
+                relative(self.last_error_at),
+                color::Fg(color::LightRed),
+                self.last_error.replace('\n', " "),
+                color::Fg(color::Reset)
+            );
+        }
+        label("图片占用:");
+        println!("{:.1}MB", self.archive_size as f64 / 1024.0 / 1024.0);
+    }
+}
+
+/// 进程是否存在
+#[cfg(target_family = "unix")]
+pub fn alive(pid: u32) -> bool {
+    // 0与负数表示进程组
+    let Some(pid) = libc::pid_t::try_from(pid).ok().filter(|p| *p > 0) else {
+        return false;
+    };
+    // 信号0只检查进程，没有权限时进程也存在
+    let res = unsafe { libc::kill(pid, 0) };
+    res == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// 进程是否存在
+#[cfg(target_family = "windows")]
+pub fn alive(pid: u32) -> bool {
+    Command::new("tasklist")
+        .args(["/FI", &format!("PID eq {}", pid), "/FO", "CSV", "/NH"])
+        .output()
+        .is_ok_and(|o| String::from_utf8_lossy(&o.stdout).contains(&format!("\"{}\"", pid)))
+}
+
+/// 请求进程退出，
+/// Windows下没有SIGTERM，直接结束
+fn terminate(pid: u32) -> Result<()> {
+    #[cfg(target_family = "unix")]
+    {
+        let pid = libc::pid_t::try_from(pid)
+            .ok()
+            .filter(|p| *p > 0)
+            .ok_or(Error::config(&format!("无效的PID {}", pid)))?;
+        if unsafe { libc::kill(pid, libc::SIGTERM) } != 0 {
+            return Err(Error::command(
+                "无法停止守护进程",
+                std::io::Error::last_os_error(),
+            ));
+        }
+        Ok(())
+    }
+    #[cfg(target_family = "windows")]
+    {
+        match Command::new("taskkill")
+            .args(["/PID", &pid.to_string(), "/F"])
+            .output()
+        {
+            Ok(o) if o.status.success() => Ok(()),
+            Ok(o) => Err(Error::command(
+                "无法停止守护进程",
+                String::from_utf8_lossy(&o.stderr).trim().to_string(),
+            )),
+            Err(e) => Err(Error::command("无法停止守护进程", e)),
+        }
+    }
+}
+
+/// 结束守护进程并等待其退出
+pub fn stop(pid: u32) -> Result<()> {
+    terminate(pid)?;
+    let start = std::time::Instant::now();
+    while alive(pid) {
+        if start.elapsed() > STOP_TIMEOUT {
+            return Err(Error::command(
+                "无法停止守护进程",
+                format!("PID {}在{}秒内没有退出", pid, STOP_TIMEOUT.as_secs()),
+            ));
+        }
+        std::thread::sleep(Duration::from_millis(200));
+    }
+    Ok(())
+}
+
+fn archive_size(data_dir: &str) -> u64 {
+    let Ok(dir) = std::fs::read_dir(data_dir) else {
+        return 0;
+    };
+    dir.flatten()
+        .filter(|e| {
+            e.path()
+                .extension()
+                .is_some_and(|ext| ext == "jpg" || ext == "png")
+        })
+        .filter_map(|e| e.metadata().ok())
+        .map(|m| m.len())
+        .sum()
+}
+
+/// `3小时20分钟`
+pub fn format_duration(secs: u64) -> String {
+    let (d, h, m, s) = (
+        secs / 86400,
+        secs % 86400 / 3600,
+        secs % 3600 / 60,
+        secs % 60,
+    );
+    let mut out = String::new();
+    if d > 0 {
+        out += &format!("{}天", d);
+    }
+    if h > 0 {
+        out += &format!("{}小时", h);
+    }
+    if m > 0 {
+        out += &format!("{}分钟", m);
+    }
+    if s > 0 || out.is_empty() {
+        out += &format!("{}秒", s);
+    }
+    out
+}
+
+/// `3小时前`或`20分钟后`
+pub fn relative(t: SystemTime) -> String {
+    match SystemTime::now().duration_since(t) {
+        Ok(d) => format!("{}前", format_duration(d.as_secs())),
+        Err(e) => format!("{}后", format_duration(e.duration().as_secs())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stop_process() {
+        assert!(alive(std::process::id()));
+        let mut child = std::process::Command::new("sleep")
+            .arg("30")
+            .spawn()
+            .unwrap();
+        let pid = child.id();
+        assert!(alive(pid));
+        // 由另一个线程回收，否则退出后仍是僵尸进程
+        let reaper = std::thread::spawn(move || child.wait().unwrap());
+        stop(pid).unwrap();
+        assert!(!reaper.join().unwrap().success());
+        assert!(!alive(pid));
+
+        assert!(!alive(0));
+        assert!(!alive(u32::MAX));
+        assert!(stop(u32::MAX).is_err());
+    }
+}
@@ -0,0 +1,317 @@
+use bincode::{Decode, Encode};
+use clap::ValueEnum;
+use std::f64::consts::PI;
+use std::time::{Duration, SystemTime};
+
+// 2000-01-01 12:00 UTC的儒略日
+const J2000: f64 = 2451545.0;
+// 1970-01-01 00:00 UTC的儒略日
+const UNIX_EPOCH_JD: f64 = 2440587.5;
+// 日出日落时太阳中心的高度角，包括大气折射与太阳半径
+const HORIZON: f64 = -0.833;
+// 黄赤交角
+const OBLIQUITY: f64 = 23.4397;
+
+/// 昼夜切换的方式
+#[derive(Encode, Decode, Clone, Copy, PartialEq, ValueEnum)]
+pub enum SunMode {
+    /// 白天与夜间使用不同的图片来源
+    Source,
+    /// 白天使用原图，夜间使用暗色壁纸
+    Variant,
+}
+
+#[derive(Encode, Decode, Clone)]
+pub struct SunConf {
+    pub enabled: bool,
+    // 纬度，经度，北纬与东经为正
+    pub location: Option<(f64, f64)>,
+    // 分钟，白天在日出后、夜间在日落后多久开始，可为负
+    pub dawn: i32,
+    pub dusk: i32,
+    pub mode: SunMode,
+    // 白天与夜间的图片来源，仅source模式
+    pub day_source: u8,
+    pub night_source: u8,
+}
+
+impl Default for SunConf {
+    fn default() -> Self {
+        SunConf {
+            enabled: false,
+            location: None,
+            dawn: 0,
+            dusk: 0,
+            mode: SunMode::Source,
+            day_source: 2,
+            night_source: 1,
+        }
+    }
+}
+
+impl SunConf {
+    /// `t`时是否为夜间，
+    /// 未开启或未设置位置时为None
+    pub fn night_at(&self, t: SystemTime) -> Option<bool> {
+        let events = self.events(t)?;
+        Some(match events.iter().rev().find(|(at, _)| *at <= t) {
+            Some((_, night)) => *night,
+            // 极昼或极夜
+            None => self.polar_night(t),
+        })
+    }
+
+    /// `t`之后的下一次昼夜切换
+    pub fn next_transition(&self, t: SystemTime) -> Option<SystemTime> {
+        let events = self.events(t)?;
+        let night = self.night_at(t)?;
+        // 偏移较大时相邻的事件可能相同，跳过不改变昼夜的
+        events
+            .into_iter()
+            .find(|(at, n)| *at > t && *n != night)
+            .map(|(at, _)| at)
+    }
+
+    /// `source`模式下`t`时的图片来源，
+    /// 其它情况为None
+    pub fn source_at(&self, t: SystemTime) -> Option<u8> {
+        if self.mode != SunMode::Source {
+            return None;
+        }
+        self.night_at(t).map(|night| {
+            if night {
+                self.night_source
+            } else {
+                self.day_source
+            }
+        })
+    }
+
+    /// `variant`模式下`t`时是否使用暗色壁纸，
+    /// 其它情况为None
+    pub fn variant_at(&self, t: SystemTime) -> Option<bool> {
+        if self.mode != SunMode::Variant {
+            return None;
+        }
+        self.night_at(t)
+    }
+
+    /// `t`前后几天按时间排序的昼夜切换，
+    /// 为`(时间, 之后是否为夜间)`
+    fn events(&self, t: SystemTime) -> Option<Vec<(SystemTime, bool)>> {
+        if !self.enabled {
+            return None;
+        }
+        let (lat, lon) = self.location?;
+        let day = (julian(t) - J2000 + lon / 360.0).round() as i64;
+        let mut events = vec![];
+        for n in day - 2..=day + 2 {
+            if let Some((rise, set)) = sunrise_sunset(n, lat, lon) {
+                events.push((offset(rise, self.dawn), false));
+                events.push((offset(set, self.dusk), true));
+            }
+        }
+        events.sort_by_key(|(at, _)| *at);
+        Some(events)
+    }
+
+    /// 没有日出日落的一天是否为极夜
+    fn polar_night(&self, t: SystemTime) -> bool {
+        let Some((lat, lon)) = self.location else {
+            return false;
+        };
+        let n = (julian(t) - J2000 + lon / 360.0).round() as i64;
+        let (_, declination) = solar_noon(n, lon);
+        // 正午太阳高度角
+        90.0 - (lat - declination).abs() < HORIZON
+    }
+}
+
+/// 第`n`天(从J2000起)的日出与日落，
+/// 极昼或极夜时为None
+pub fn sunrise_sunset(n: i64, lat: f64, lon: f64) -> Option<(SystemTime, SystemTime)> {
+    let (transit, declination) = solar_noon(n, lon);
+    let (lat, declination) = (lat.to_radians(), declination.to_radians());
+    let cos_hour = (HORIZON.to_radians().sin() - lat.sin() * declination.sin())
+        / (lat.cos() * declination.cos());
+    if !(-1.0..=1.0).contains(&cos_hour) {
+        return None;
+    }
+    let half_day = cos_hour.acos() / (2.0 * PI);
+    Some((
+        from_julian(transit - half_day),
+        from_julian(transit + half_day),
+    ))
+}
+
+/// 第`n`天太阳过中天的儒略日与太阳赤纬(度)
+fn solar_noon(n: i64, lon: f64) -> (f64, f64) {
+    let mean = n as f64 - lon / 360.0;
+    let anomaly = (357.5291 + 0.98560028 * mean)
+        .rem_euclid(360.0)
+        .to_radians();
+    let center =
+        1.9148 * anomaly.sin() + 0.02 * (2.0 * anomaly).sin() + 0.0003 * (3.0 * anomaly).sin();
+    let ecliptic = (anomaly.to_degrees() + center + 180.0 + 102.9372)
+        .rem_euclid(360.0)
+        .to_radians();
+    let transit = J2000 + mean + 0.0053 * anomaly.sin() - 0.0069 * (2.0 * ecliptic).sin();
+    let declination = (ecliptic.sin() * OBLIQUITY.to_radians().sin()).asin();
+    (transit, declination.to_degrees())
+}
+
+/// `t`时太阳的高度角与方位角(度)，
+/// 方位角从正北顺时针计算
+pub fn position(t: SystemTime, lat: f64, lon: f64) -> (f64, f64) {
+    let d = julian(t) - J2000;
+    let anomaly = (357.5291 + 0.98560028 * d).rem_euclid(360.0).to_radians();
+    let center =
+        1.9148 * anomaly.sin() + 0.02 * (2.0 * anomaly).sin() + 0.0003 * (3.0 * anomaly).sin();
+    let ecliptic = (anomaly.to_degrees() + center + 180.0 + 102.9372)
+        .rem_euclid(360.0)
+        .to_radians();
+    let obliquity = OBLIQUITY.to_radians();
+    let declination = (ecliptic.sin() * obliquity.sin()).asin();
+    let ascension = (ecliptic.sin() * obliquity.cos()).atan2(ecliptic.cos());
+    // 地方恒星时减去赤经为时角
+    let sidereal = (280.16 + 360.9856235 * d + lon).to_radians();
+    let hour = sidereal - ascension;
+    let lat = lat.to_radians();
+    let altitude =
+        (lat.sin() * declination.sin() + lat.cos() * declination.cos() * hour.cos()).asin();
+    // 从正南向西计算的方位角
+    let azimuth = hour
+        .sin()
+        .atan2(hour.cos() * lat.sin() - declination.tan() * lat.cos());
+    (
+        altitude.to_degrees(),
+        (azimuth.to_degrees() + 180.0).rem_euclid(360.0),
+    )
+}
+
+fn julian(t: SystemTime) -> f64 {
+    let secs = match t.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(d) => d.as_secs_f64(),
+        Err(e) => -e.duration().as_secs_f64(),
+    };
+    secs / 86400.0 + UNIX_EPOCH_JD
+}
+
+fn from_julian(jd: f64) -> SystemTime {
+    let secs = (jd - UNIX_EPOCH_JD) * 86400.0;
+    if secs >= 0.0 {
+        SystemTime::UNIX_EPOCH + Duration::from_secs_f64(secs)
+    } else {
+        SystemTime::UNIX_EPOCH - Duration::from_secs_f64(-secs)
+    }
+}
+
+fn offset(t: SystemTime, minutes: i32) -> SystemTime {
+    let d = Duration::from_secs(minutes.unsigned_abs() as u64 * 60);
+    if minutes >= 0 { t + d } else { t - d }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveDate, TimeZone, Utc};
+
+    const BEIJING: (f64, f64) = (39.9042, 116.4074);
+    const LONDON: (f64, f64) = (51.5074, -0.1278);
+    const TROMSO: (f64, f64) = (69.6496, 18.956);
+
+    fn day(y: i32, m: u32, d: u32) -> i64 {
+        (NaiveDate::from_ymd_opt(y, m, d).unwrap() - NaiveDate::from_ymd_opt(2000, 1, 1).unwrap())
+            .num_days()
+    }
+
+    fn utc(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> SystemTime {
+        Utc.with_ymd_and_hms(y, mo, d, h, mi, 0).unwrap().into()
+    }
+
+    fn assert_near(actual: SystemTime, expected: SystemTime) {
+        let diff = match actual.duration_since(expected) {
+            Ok(d) => d,
+            Err(e) => e.duration(),
+        };
+        assert!(diff <= Duration::from_secs(120), "相差{}秒", diff.as_secs());
+    }
+
+    fn conf(location: (f64, f64)) -> SunConf {
+        SunConf {
+            enabled: true,
+            location: Some(location),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn known_sunrise_sunset() {
+        // 北京 2024-06-21 日出04:46 日落19:46(UTC+8)
+        let (rise, set) = sunrise_sunset(day(2024, 6, 21), BEIJING.0, BEIJING.1).unwrap();
+        assert_near(rise, utc(2024, 6, 20, 20, 46));
+        assert_near(set, utc(2024, 6, 21, 11, 46));
+        // 伦敦 2024-12-21 日出08:04 日落15:53(UTC)
+        let (rise, set) = sunrise_sunset(day(2024, 12, 21), LONDON.0, LONDON.1).unwrap();
+        assert_near(rise, utc(2024, 12, 21, 8, 4));
+        assert_near(set, utc(2024, 12, 21, 15, 53));
+    }
+
+    #[test]
+    fn day_and_night() {
+        let s = conf(BEIJING);
+        assert!(s.night_at(utc(2024, 6, 21, 4, 0)) == Some(false));
+        assert!(s.night_at(utc(2024, 6, 21, 16, 0)) == Some(true));
+        assert_near(
+            s.next_transition(utc(2024, 6, 21, 4, 0)).unwrap(),
+            utc(2024, 6, 21, 11, 46),
+        );
+        // 日落后30分钟才切换到夜间
+        let s = SunConf { dusk: 30, ..s };
+        assert!(s.night_at(utc(2024, 6, 21, 12, 0)) == Some(false));
+        assert_near(
+            s.next_transition(utc(2024, 6, 21, 4, 0)).unwrap(),
+            utc(2024, 6, 21, 12, 16),
+        );
+        assert!(
+            SunConf::default()
+                .night_at(utc(2024, 6, 21, 16, 0))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn polar_day() {
+        assert!(sunrise_sunset(day(2024, 6, 21), TROMSO.0, TROMSO.1).is_none());
+        let s = conf(TROMSO);
+        // 午夜也是白天
+        assert!(s.night_at(utc(2024, 6, 21, 23, 0)) == Some(false));
+        assert!(s.next_transition(utc(2024, 6, 21, 23, 0)).is_none());
+    }
+
+    #[test]
+    fn polar_night() {
+        assert!(sunrise_sunset(day(2024, 12, 21), TROMSO.0, TROMSO.1).is_none());
+        let s = conf(TROMSO);
+        // 正午也是夜间
+        assert!(s.night_at(utc(2024, 12, 21, 11, 0)) == Some(true));
+        assert!(s.next_transition(utc(2024, 12, 21, 11, 0)).is_none());
+        // 南半球相反
+        let s = conf((-TROMSO.0, TROMSO.1));
+        assert!(s.night_at(utc(2024, 12, 21, 23, 0)) == Some(false));
+        assert!(s.night_at(utc(2024, 6, 21, 11, 0)) == Some(true));
+    }
+
+    #[test]
+    fn position_at_noon() {
+        let (transit, declination) = solar_noon(day(2024, 6, 21), BEIJING.1);
+        let (altitude, azimuth) = position(from_julian(transit), BEIJING.0, BEIJING.1);
+        assert!((altitude - (90.0 - (BEIJING.0 - declination))).abs() < 0.2);
+        assert!((azimuth - 180.0).abs() < 0.5, "{}", azimuth);
+        // 日出时在东北方的地平线
+        let (rise, _) = sunrise_sunset(day(2024, 6, 21), BEIJING.0, BEIJING.1).unwrap();
+        let (altitude, azimuth) = position(rise, BEIJING.0, BEIJING.1);
+        assert!((altitude - HORIZON).abs() < 0.2, "{}", altitude);
+        assert!((55.0..65.0).contains(&azimuth), "{}", azimuth);
+    }
+}
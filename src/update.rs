@@ -0,0 +1,405 @@
+use super::config::Config;
+use super::error::{Error, PrintErr, Result};
+use super::hook::HookKind;
+use super::meta::ImageMeta;
+use super::mirror::MirrorConf;
+use super::monitor::{self, MonitorMode, Output};
+use super::net;
+use super::palette::{self, Palette};
+use super::paths::Dirs;
+use super::setter::Setter;
+use super::source::{self, Source};
+use std::fs;
+use std::time::{Duration, SystemTime};
+
+/// 一次更新设置的壁纸
+pub struct AppliedWallpaper {
+    pub image: String,
+    // 未开启暗色壁纸时为空
+    pub dark_image: String,
+    // 多显示器、锁屏等额外生成的图片
+    pub output_images: Vec<String>,
+    pub meta: ImageMeta,
+    pub applied_at: SystemTime,
+}
+
+impl AppliedWallpaper {
+    /// 记录为配置中的当前壁纸
+    pub fn store(&self, config: &mut Config) {
+        config.set_cur_img(&self.image);
+        config.set_cur_dark_img(&self.dark_image);
+        config.set_cur_output_imgs(self.output_images.clone());
+        config.set_cur_meta(self.meta.clone());
+        config.set_update_at(self.applied_at);
+    }
+}
+
+/// 按`config`获取一张图片，处理后设置为壁纸，
+/// 图片保存在数据目录中，
+/// 设置成功后删除`config`中记录的上一张壁纸，
+/// 调用者需要用`AppliedWallpaper::store`保存新的壁纸，
+/// 镜像的请求结果无论成败都记入`config`
+pub async fn update_once(config: &mut Config, dirs: &Dirs) -> Result<AppliedWallpaper> {
+    let mut mirrors = config.get_mirror().clone();
+    let res = update(config, dirs, &mut mirrors, None).await;
+    config.set_mirror(mirrors);
+    res
+}
+
+/// 与`update_once`相同，但所有显示器都使用本地图片`image`，
+/// 同样经过滤镜、多显示器、锁屏与钩子的处理，
+/// 用于设置动态壁纸的帧
+pub async fn apply_image(config: &Config, dirs: &Dirs, image: &str) -> Result<AppliedWallpaper> {
+    let source = Source::File(image.to_string());
+    update(
+        config,
+        dirs,
+        &mut config.get_mirror().clone(),
+        Some(&source),
+    )
+    .await
+}
+
+/// `fixed`为所有显示器使用的来源，
+/// None时按配置选择
+async fn update(
+    config: &Config,
+    dirs: &Dirs,
+    mirrors: &mut MirrorConf,
+    fixed: Option<&Source>,
+) -> Result<AppliedWallpaper> {
+    let client = net::client(config.get_net())?;
+    let t = SystemTime::now();
+
+    let stamp = t
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or(Duration::from_secs(114514))
+        .as_secs()
+        .to_string();
+
+    let base = dirs.data.clone() + "/" + &stamp;
+    let save_path = base.clone() + ".jpg";
+    let dark_path = base.clone() + "-dark.jpg";
+    let setter = Setter::detect(&dirs.exe);
+    let monitor = config.get_monitor();
+    let filter = config.get_filter();
+    let rule = config.get_rule();
+    let hook = config.get_hook();
+    let dark = |p: String| if filter.dark.enabled { Some(p) } else { None };
+    // 按日出日落切换的图片来源
+    let global_source = match fixed {
+        Some(s) => s.clone(),
+        None => config.image_source(config.get_sun().source_at(t).unwrap_or(config.get_source())),
+    };
+    let variant = config.get_sun().variant_at(t);
+
+    let pre = ImageMeta {
+        source: global_source.id(),
+        url: Config::url_of(global_source.id()).to_string(),
+        ..Default::default()
+    };
+    // 只有pre_apply钩子失败会放弃图片
+    hook.run(HookKind::PreFetch, "", &pre).await.print_err();
+
+    // 多显示器模式下需要先获取显示器布局
+    let outputs = if monitor.mode == MonitorMode::Same {
+        vec![]
+    } else {
+        setter.outputs()?
+    };
+    // 已写入的图片，出错时全部删除
+    let mut written = vec![save_path.clone(), dark_path.clone()];
+    let fetched = if monitor.mode == MonitorMode::Each {
+        let sources: Vec<(Output, Source)> = outputs
+            .iter()
+            .map(|o| match (fixed, monitor.source_of(&o.name)) {
+                (None, Some(id)) => (o.clone(), config.image_source(id)),
+                _ => (o.clone(), global_source.clone()),
+            })
+            .collect();
+        fetch_each(&client, config, &sources, mirrors, &base, &mut written).await
+    } else {
+        async {
+            let m = source::fetch(&client, &global_source, mirrors, &save_path, rule).await?;
+            hook.run(HookKind::PostDownload, &save_path, &m)
+                .await
+                .print_err();
+            filter.process(&save_path, &dark_path)?;
+            let mut parts = vec![];
+            if monitor.mode == MonitorMode::Span {
+                let lights: Vec<String> =
+                    outputs.iter().map(|o| output_path(&base, o, "")).collect();
+                let darks: Vec<String> = outputs
+                    .iter()
+                    .map(|o| output_path(&base, o, "-dark"))
+                    .collect();
+                written.extend(lights.iter().chain(&darks).cloned());
+                monitor::slice(&save_path, &outputs, &lights)?;
+                if filter.dark.enabled {
+                    monitor::slice(&dark_path, &outputs, &darks)?;
+                }
+                for ((o, l), d) in outputs.iter().zip(lights).zip(darks) {
+                    parts.push((o.clone(), l, dark(d)));
+                }
+            }
+            Ok((m, parts))
+        }
+        .await
+    };
+    let (meta, parts) = match fetched {
+        Ok(f) => f,
+        Err(e) => {
+            discard(&written);
+            return Err(e);
+        }
+    };
+
+    let mut output_imgs: Vec<String> = vec![];
+    for (_, l, d) in &parts {
+        output_imgs.push(l.clone());
+        output_imgs.extend(d.clone());
+    }
+    let main_img = match monitor.mode {
+        MonitorMode::Each => parts[0].1.clone(),
+        _ => save_path.clone(),
+    };
+    if let Err(veto) = hook.run(HookKind::PreApply, &main_img, &meta).await {
+        discard(&written);
+        return Err(Error::Rejected {
+            msg: "图片被pre_apply钩子拒绝".to_string(),
+            source: Some(Box::new(veto)),
+        });
+    }
+
+    for suffix in ["-span.jpg", "-span-dark.jpg", "-lock.jpg"] {
+        output_imgs.push(base.clone() + suffix);
+        written.push(base.clone() + suffix);
+    }
+    let applied = AppliedWallpaper {
+        // 每个显示器一张图片时，以第一个显示器的图片作为当前壁纸
+        image: main_img.clone(),
+        dark_image: match monitor.mode {
+            MonitorMode::Each => String::new(),
+            _ => dark(dark_path.clone()).unwrap_or_default(),
+        },
+        output_images: output_imgs,
+        meta,
+        applied_at: t,
+    };
+
+    let res = if monitor.mode == MonitorMode::Same {
+        let (light, dark) = pick_variant(variant, &save_path, dark(dark_path.clone()).as_deref());
+        setter.apply(&light, dark.as_deref())
+    } else {
+        let parts: Vec<(Output, String, Option<String>)> = parts
+            .iter()
+            .map(|(o, l, d)| {
+                let (l, d) = pick_variant(variant, l, d.as_deref());
+                (o.clone(), l, d)
+            })
+            .collect();
+        setter.apply_outputs(&parts, &(base.clone() + "-span"))
+    };
+    if let Err(e) = res {
+        discard(&written);
+        return Err(e);
+    }
+    delete_images(config, &applied);
+    if config.get_lock().enabled {
+        // 锁屏壁纸设置失败不影响壁纸更新
+        apply_lock(config, &setter, &main_img, &(base + "-lock.jpg")).print_err();
+    }
+    if config.get_palette().enabled {
+        // 配色导出失败不影响壁纸更新
+        export_palette(config, &dirs.cache, &main_img).print_err();
+    }
+    hook.run(HookKind::PostApply, &main_img, &applied.meta)
+        .await
+        .print_err();
+    Ok(applied)
+}
+
+/// 每个显示器分别从`sources`获取图片并处理，
+/// 返回第一张图片的信息与各显示器的图片，
+/// 写入的图片都记入`written`
+async fn fetch_each(
+    client: &reqwest::Client,
+    config: &Config,
+    sources: &[(Output, Source)],
+    mirrors: &mut MirrorConf,
+    base: &str,
+    written: &mut Vec<String>,
+) -> Result<(ImageMeta, Vec<(Output, String, Option<String>)>)> {
+    let filter = config.get_filter();
+    let mut meta: Option<ImageMeta> = None;
+    let mut parts = vec![];
+    for (o, source) in sources {
+        let (light, dark_light) = (output_path(base, o, ""), output_path(base, o, "-dark"));
+        written.push(light.clone());
+        written.push(dark_light.clone());
+        let m = source::fetch(client, source, mirrors, &light, config.get_rule()).await?;
+        config
+            .get_hook()
+            .run(HookKind::PostDownload, &light, &m)
+            .await
+            .print_err();
+        if meta.is_none() {
+            meta = Some(m);
+        }
+        filter.process(&light, &dark_light)?;
+        let dark = filter.dark.enabled.then_some(dark_light);
+        parts.push((o.clone(), light, dark));
+    }
+    Ok((meta.unwrap_or_default(), parts))
+}
+
+/// 显示器`o`的图片
+fn output_path(base: &str, o: &Output, suffix: &str) -> String {
+    format!("{}-{}{}.jpg", base, o.name.replace('/', "_"), suffix)
+}
+
+fn discard(imgs: &[String]) {
+    for img in imgs {
+        let _ = fs::remove_file(img);
+    }
+}
+
+/// 昼夜切换时重新设置当前壁纸的原图或暗色壁纸，
+/// 只支持所有显示器使用同一张图片，
+/// 返回false时需要重新更新
+pub fn apply_variant(config: &Config, dirs: &Dirs, night: bool) -> Result<bool> {
+    let (img, dark) = (config.get_cur_img(), config.get_cur_dark_img());
+    if config.get_monitor().mode != MonitorMode::Same || img.is_empty() || dark.is_empty() {
+        return Ok(false);
+    }
+    Setter::detect(&dirs.exe).apply(if night { dark } else { img }, None)?;
+    Ok(true)
+}
+
+/// 系统切换暗色模式时重新设置当前壁纸的原图或暗色壁纸，
+/// 桌面自己会切换或`variant`模式下跟随日出日落时不设置，
+/// 返回是否重新设置了
+pub fn follow_system_dark(config: &Config, dirs: &Dirs, dark: bool) -> Result<bool> {
+    if Setter::detect(&dirs.exe).switches_dark()
+        || config.get_sun().variant_at(SystemTime::now()).is_some()
+    {
+        return Ok(false);
+    }
+    apply_variant(config, dirs, dark)
+}
+
+/// `variant`模式下按昼夜选择原图或暗色壁纸，
+/// 不再跟随系统的暗色模式
+fn pick_variant(night: Option<bool>, light: &str, dark: Option<&str>) -> (String, Option<String>) {
+    match (night, dark) {
+        (Some(true), Some(d)) => (d.to_string(), None),
+        (Some(_), _) => (light.to_string(), None),
+        (None, d) => (light.to_string(), d.map(str::to_string)),
+    }
+}
+
+/// 以`img`生成锁屏壁纸
+pub fn apply_lock(config: &Config, setter: &Setter, img: &str, lock_path: &str) -> Result<()> {
+    let lock = config.get_lock();
+    fs::copy(img, lock_path).map_err(|e| Error::io("生成锁屏壁纸失败", e))?;
+    lock.filter.process(lock_path, "")?;
+    setter.apply_lock(lock_path, &lock.copy_to)
+}
+
+/// 从`img`导出配色到缓存目录
+pub fn export_palette(config: &Config, cache_dir: &str, img: &str) -> Result<()> {
+    Palette::from_image(img)?.export(
+        &palette::dir(cache_dir),
+        img,
+        &config.get_palette().reload_cmd,
+    )
+}
+
+/// 删除`config`中记录的上一张壁纸的图片，
+/// 同一秒内多次更新时路径相同，跳过新壁纸的图片
+fn delete_images(config: &Config, applied: &AppliedWallpaper) {
+    let old = [config.get_cur_img(), config.get_cur_dark_img()];
+    let old = old
+        .into_iter()
+        .chain(config.get_cur_output_imgs().iter().map(String::as_str));
+    for img in old {
+        if img != applied.image
+            && img != applied.dark_image
+            && !applied.output_images.iter().any(|o| o == img)
+        {
+            let _ = fs::remove_file(img);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::{DarkVariant, Filter};
+    use std::path::Path;
+
+    fn output(name: &str) -> Output {
+        Output {
+            name: name.to_string(),
+            id: String::new(),
+            x: 0,
+            y: 0,
+            width: 16,
+            height: 9,
+        }
+    }
+
+    #[tokio::test]
+    async fn each_failure_discards_earlier_outputs() {
+        let dir = std::env::temp_dir().join(format!("wow-update-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let dir = dir.to_string_lossy().to_string();
+        let src = format!("{}/src.png", dir);
+        image::RgbImage::new(16, 9).save(&src).unwrap();
+
+        let mut config = Config::default();
+        config.set_filter(Filter {
+            dark: DarkVariant {
+                enabled: true,
+                brightness: 0.5,
+            },
+            ..Default::default()
+        });
+        let sources = [
+            (output("HDMI-1"), Source::File(src)),
+            (
+                output("eDP-1"),
+                Source::File(format!("{}/missing.jpg", dir)),
+            ),
+        ];
+        let base = format!("{}/1700000000", dir);
+        let mut written = vec![];
+        let client = net::client(config.get_net()).unwrap();
+        let res = fetch_each(
+            &client,
+            &config,
+            &sources,
+            &mut config.get_mirror().clone(),
+            &base,
+            &mut written,
+        )
+        .await;
+        assert!(res.is_err());
+        // 第一个显示器的原图与暗色图片已经写入
+        let first = [
+            format!("{}-HDMI-1.jpg", base),
+            format!("{}-HDMI-1-dark.jpg", base),
+        ];
+        for p in &first {
+            assert!(written.contains(p), "{}", p);
+            assert!(Path::new(p).exists(), "{}", p);
+        }
+        assert!(written.contains(&format!("{}-eDP-1.jpg", base)));
+
+        discard(&written);
+        for p in &first {
+            assert!(!Path::new(p).exists(), "{}", p);
+        }
+    }
+}
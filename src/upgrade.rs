@@ -0,0 +1,279 @@
+use super::error::{Error, Result};
+use super::service;
+use super::status;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// 默认的发布清单
+pub const MANIFEST_URL: &str =
+    "https://gitee.com/yawnbright/wow/releases/download/latest/manifest.json";
+/// 设置后代替默认的发布清单
+pub const MANIFEST_ENV: &str = "WOW_MANIFEST_URL";
+
+/// 发布清单中当前平台的版本，格式为
+/// `{"version": "0.2.0", "platforms": {"linux-x86_64": {"url", "sha256", "updater_url", "updater_sha256"}}}`
+pub struct Release {
+    pub version: String,
+    pub url: String,
+    pub sha256: String,
+    // macOS下设置壁纸的`updater`，地址与校验和
+    pub updater: Option<(String, String)>,
+}
+
+/// 当前平台，如`linux-x86_64`
+pub fn platform() -> String {
+    format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH)
+}
+
+/// 获取`url`处的发布清单
+pub async fn fetch_manifest(client: &reqwest::Client, url: &str) -> Result<Release> {
+    let data = get(client, url).await?;
+    let v: serde_json::Value =
+        serde_json::from_slice(&data).map_err(|e| Error::decode("发布清单无效", e))?;
+    let version = v["version"].as_str().unwrap_or("").to_string();
+    let p = &v["platforms"][platform()];
+    let text = |k: &str| p[k].as_str().unwrap_or("").to_string();
+    if version.is_empty() || text("url").is_empty() || text("sha256").is_empty() {
+        return Err(Error::Decode {
+            msg: format!("发布清单中没有{}的版本", platform()),
+            source: None,
+        });
+    }
+    let updater = if text("updater_url").is_empty() {
+        None
+    } else {
+        Some((text("updater_url"), text("updater_sha256")))
+    };
+    Ok(Release {
+        version,
+        url: text("url"),
+        sha256: text("sha256"),
+        updater,
+    })
+}
+
+/// 版本`a`是否比`b`新，
+/// 逐段比较数字，忽略`v`前缀，`1.2`与`1.2.0`相同
+pub fn newer(a: &str, b: &str) -> bool {
+    let parse = |s: &str| -> Vec<u64> {
+        let mut parts: Vec<u64> = s
+            .trim()
+            .trim_start_matches('v')
+            .split('.')
+            .map(|p| {
+                let digits: String = p.chars().take_while(|c| c.is_ascii_digit()).collect();
+                digits.parse().unwrap_or(0)
+            })
+            .collect();
+        while parts.last() == Some(&0) {
+            parts.pop();
+        }
+        parts
+    };
+    parse(a) > parse(b)
+}
+
+/// 下载并校验新版本，
+/// 全部通过后替换`exe`与同目录的`updater`
+pub async fn install(client: &reqwest::Client, release: &Release, exe: &str) -> Result<()> {
+    let bin = download(client, &release.url, &release.sha256).await?;
+    let updater = match &release.updater {
+        Some((url, sha256)) => Some(download(client, url, sha256).await?),
+        None => None,
+    };
+    replace(exe, &bin)?;
+    if let Some(data) = updater {
+        let dir = Path::new(exe).parent().unwrap_or(Path::new("."));
+        replace(&dir.join("updater").to_string_lossy(), &data)?;
+    }
+    Ok(())
+}
+
+/// 重启运行中的守护进程，
+/// 由服务管理时通过服务管理器重启
+pub fn restart_daemon(exe: &str, config_path: &str, pid: u32) -> Result<()> {
+    if service::restart()? {
+        return Ok(());
+    }
+    status::stop(pid)?;
+    let mut cmd = Command::new(exe);
+    cmd.args(["run", "--force", "--config", config_path])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    #[cfg(target_family = "unix")]
+    {
+        // 不随终端退出
+        use std::os::unix::process::CommandExt;
+        cmd.process_group(0);
+    }
+    cmd.spawn()
+        .map(|_| ())
+        .map_err(|e| Error::command("无法启动守护进程", e))
+}
+
+async fn get(client: &reqwest::Client, url: &str) -> Result<Vec<u8>> {
+    let resp = client
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| Error::network(&format!("无法获取{}", url), e))?;
+    if !resp.status().is_success() {
+        return Err(Error::Http {
+            status: resp.status().as_u16(),
+            url: url.to_string(),
+        });
+    }
+    match resp.bytes().await {
+        Ok(b) => Ok(b.to_vec()),
+        Err(e) => Err(Error::network(&format!("无法获取{}", url), e)),
+    }
+}
+
+async fn download(client: &reqwest::Client, url: &str, sha256: &str) -> Result<Vec<u8>> {
+    let data = get(client, url).await?;
+    let digest: String = Sha256::digest(&data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    if !digest.eq_ignore_ascii_case(sha256.trim()) {
+        return Err(Error::rejected(&format!(
+            "{}校验失败\n  期望 {}\n  实际 {}",
+            url, sha256, digest
+        )));
+    }
+    Ok(data)
+}
+
+/// 先写入同目录的临时文件，
+/// 再重命名覆盖`path`
+fn replace(path: &str, data: &[u8]) -> Result<()> {
+    let tmp = format!("{}.new", path);
+    let failed = |e| Error::io(&format!("无法替换{}", path), e);
+    fs::write(&tmp, data).map_err(failed)?;
+    #[cfg(target_family = "unix")]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&tmp, fs::Permissions::from_mode(0o755)).map_err(failed)?;
+    }
+    if let Err(e) = fs::rename(&tmp, path) {
+        let _ = fs::remove_file(&tmp);
+        return Err(failed(e));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{self, response};
+
+    #[test]
+    fn version_order() {
+        assert!(newer("0.2.0", "0.1.9"));
+        assert!(newer("0.10.0", "0.9.0"));
+        assert!(newer("v1.0.1", "1.0.0"));
+        assert!(newer("1.0.0", "v0.99"));
+        assert!(newer("2", "1.9.9"));
+        assert!(newer("1.2.1", "1.2"));
+        assert!(!newer("1.2.0", "1.2"));
+        assert!(!newer("1.2", "1.2.0"));
+        assert!(!newer("v1.0.0", "1.0.0"));
+        assert!(!newer("0.9.0", "0.10.0"));
+        // 预发布后缀只取数字部分
+        assert!(!newer("1.0.0-beta", "1.0.0"));
+    }
+
+    fn sha256(data: &[u8]) -> String {
+        Sha256::digest(data)
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    /// 提供发布清单与新版本的服务器，
+    /// `checksum`为清单中的校验和
+    async fn release_server(checksum: Option<&str>) -> String {
+        let bin = b"new wow".to_vec();
+        let checksum = checksum.map(str::to_string).unwrap_or(sha256(&bin));
+        let base = std::sync::Arc::new(std::sync::OnceLock::<String>::new());
+        let b = base.clone();
+        let url = test_server::serve(move |path| match path {
+            "/manifest.json" => response(
+                200,
+                serde_json::json!({
+                    "version": "9.9.9",
+                    "platforms": {
+                        platform(): {"url": format!("{}/wow", b.get().unwrap()), "sha256": checksum}
+                    }
+                })
+                .to_string()
+                .as_bytes(),
+            ),
+            "/wow" => response(200, &bin),
+            _ => response(404, b""),
+        })
+        .await;
+        base.set(url.clone()).unwrap();
+        url
+    }
+
+    fn temp_exe(name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("wow-upgrade-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let exe = dir.join("wow").to_string_lossy().to_string();
+        fs::write(&exe, b"old wow").unwrap();
+        exe
+    }
+
+    #[tokio::test]
+    async fn install_from_manifest() {
+        let url = release_server(None).await;
+        let client = test_server::client();
+        let release = fetch_manifest(&client, &format!("{}/manifest.json", url))
+            .await
+            .unwrap();
+        assert_eq!(release.version, "9.9.9");
+        assert_eq!(release.url, format!("{}/wow", url));
+        assert!(release.updater.is_none());
+
+        let exe = temp_exe("ok");
+        install(&client, &release, &exe).await.unwrap();
+        assert_eq!(fs::read(&exe).unwrap(), b"new wow");
+        #[cfg(target_family = "unix")]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(
+                fs::metadata(&exe).unwrap().permissions().mode() & 0o777,
+                0o755
+            );
+        }
+        assert!(!Path::new(&format!("{}.new", exe)).exists());
+    }
+
+    #[tokio::test]
+    async fn reject_bad_checksum() {
+        let url = release_server(Some(&"0".repeat(64))).await;
+        let client = test_server::client();
+        let release = fetch_manifest(&client, &format!("{}/manifest.json", url))
+            .await
+            .unwrap();
+        let exe = temp_exe("bad");
+        let err = install(&client, &release, &exe).await.unwrap_err();
+        assert!(matches!(err, Error::Rejected { .. }), "{}", err);
+        assert_eq!(fs::read(&exe).unwrap(), b"old wow");
+    }
+
+    #[tokio::test]
+    async fn missing_manifest() {
+        let url = release_server(None).await;
+        let err = fetch_manifest(&test_server::client(), &format!("{}/nope.json", url))
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(err, Error::Http { status: 404, .. }), "{}", err);
+    }
+}
@@ -1,207 +1,388 @@
-use crate::config::Config;
+#[cfg(target_family = "unix")]
+use crate::bar;
+use crate::bye;
+use crate::cli::{
+    Cli, Command, DynamicCmd, FilterCmd, HookCmd, LockCmd, LogCmd, MirrorCmd, MonitorCmd, NetCmd,
+    NotifyCmd, PaletteCmd, QuietCmd, RuleCmd, ServiceCmd, SunCmd,
+};
+use crate::config::{self, Config};
+use crate::dynamic::DynamicConf;
+use crate::error::{Error, PrintErr, Result};
+use crate::filter::Filter;
+use crate::hook::HookKind;
+use crate::ipc::{self, Broadcaster};
+use crate::logger::{self, LogConf};
+use crate::mirror::{self, MirrorConf};
+use crate::net::{self, NetConf};
+use crate::notify::{self, Notice};
+use crate::palette;
+use crate::paths::Dirs;
+use crate::rule::{self, ImageRule};
+use crate::schedule;
+use crate::service::{self, Service};
+use crate::setter::{self, Setter};
+use crate::status::{self, Status};
+use crate::sun::{self, SunMode};
+use crate::update;
+use crate::upgrade;
+use clap::{CommandFactory, Parser, ValueEnum};
 use std::{
-    fs::{self, File},
+    fs,
     io::Write,
-    process::Command,
     time::{Duration, SystemTime},
 };
 use termion::color;
+use tokio::select;
 use tokio::time::sleep;
 
+// 守护进程检查更新的间隔
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// 命令行程序的入口，
+/// 处理退出信号并返回退出码
+pub async fn main() -> i32 {
+    let mut wow = Wow::new();
+    #[cfg(target_family = "unix")]
+    {
+        let mut sigint = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::interrupt())
+            .expect("信号创建失败");
+        let mut sigterm = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("信号创建失败");
+
+        // 不监听挂起信号
+        // 使得终端关闭后进程仍能运行
+        select! {
+            res = wow.run() => exit_code(res),
+            _ = sigint.recv() => {
+                wow._stop().print_err();
+                0
+            },
+            _ = sigterm.recv() => {
+                wow._stop().print_err();
+                0
+            },
+        }
+    }
+    #[cfg(target_family = "windows")]
+    {
+        select! {
+            res = wow.run() => exit_code(res),
+            _ = tokio::signal::ctrl_c() => {
+                wow._stop().print_err();
+                0
+            },
+        }
+    }
+}
+
+/// 记录错误并转换为退出码
+fn exit_code(res: Result<()>) -> i32 {
+    match res {
+        Ok(_) => 0,
+        Err(e) => {
+            e.log();
+            e.exit_code()
+        }
+    }
+}
+
 pub struct Wow {
-    args: Vec<String>,
     config: Config,
-    working_space: String,
+    config_path: String,
+    dirs: Dirs,
+}
+
+impl Default for Wow {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Wow {
     pub fn new() -> Self {
         Wow {
-            args: vec![],
             config: Config::default(),
-            working_space: String::new(),
+            config_path: String::new(),
+            dirs: Dirs::default(),
         }
     }
 
-    pub async fn run(&mut self) {
-        let err = self.init_workspace();
-        if !err.is_empty() {
-            err.print_err();
-            return;
+    /// 执行命令行指定的操作，
+    /// 错误由`main`转换为退出码
+    pub async fn run(&mut self) -> Result<()> {
+        let cli = Cli::parse();
+        if let Some(Command::Completions { shell }) = cli.command {
+            clap_complete::generate(shell, &mut Cli::command(), "wow", &mut std::io::stdout());
+            return Ok(());
         }
 
-        let load_err = self.load_config();
-        if !load_err.is_empty() {
-            load_err.print_err();
-        }
+        // 读取配置之前按默认级别输出到终端
+        logger::init("", LogConf::default());
+        self.init_workspace()?;
+        self.config_path = match cli.config {
+            Some(c) => c,
+            None => self.dirs.config_file(),
+        };
 
-        let args: Vec<String> = std::env::args().collect();
-        self.args = args;
-        match self.args.get(1) {
-            Some(a) => match a.as_str() {
-                "help" => {
-                    self.print_help();
-                }
-                "run" => {
-                    self._run().await;
-                }
-                "stop" => {
-                    self._stop();
-                }
-                "update" => {
-                    self.try_update(true).await.print_err();
-                }
-                "freq" => {
-                    self.set_update_frequance().print_err();
-                }
-                "from" => {
-                    self.set_img_souce().print_err();
-                }
-                "tip" => {
-                    self.show_tip_code();
-                }
-                "bye" => {
-                    self.self_remove();
+        self.load_config().print_err();
+
+        match cli.command {
+            Some(Command::Help) => {
+                self.print_help();
+                Ok(())
+            }
+            Some(Command::Run { force }) => self._run(force).await,
+            Some(Command::Stop) => self._stop(),
+            Some(Command::Status { json }) => {
+                let status = Status::collect(&self.config, &self.dirs.data);
+                if json {
+                    println!("{}", status.to_json());
+                } else {
+                    status.print();
                 }
-                _ => {
-                    self.print_help();
+                Ok(())
+            }
+            Some(Command::Update { source }) => self.update_from(source).await,
+            Some(Command::Like) => self.like(),
+            #[cfg(target_family = "unix")]
+            Some(Command::Bar { format }) => {
+                bar::run(format, &self.dirs, &self.config_path).await;
+                Ok(())
+            }
+            Some(Command::Freq { interval, min, max }) => {
+                self.set_update_frequance(interval, min, max)
+            }
+            Some(Command::Quiet { item }) => self.set_quiet(item).await,
+            Some(Command::Sun { item }) => self.set_sun(item),
+            Some(Command::Dynamic { item }) => self.set_dynamic(item),
+            Some(Command::Net { item }) => self.set_net(item),
+            Some(Command::Mirror { item }) => self.set_mirror(item),
+            Some(Command::Doctor) => self.doctor().await,
+            Some(Command::From { source, cmd }) => self.set_img_souce(source, cmd),
+            Some(Command::Filter { item }) => self.set_filter(item),
+            Some(Command::Lock { item }) => self.set_lock(item),
+            Some(Command::Rule { item }) => self.set_rule(item),
+            Some(Command::Monitor { json, item }) => self.set_monitor(item, json),
+            Some(Command::Notify { item }) => self.set_notify(item).await,
+            Some(Command::Hook { item }) => self.set_hook(item).await,
+            Some(Command::Palette { item }) => self.set_palette(item),
+            Some(Command::Log {
+                follow,
+                level,
+                lines,
+                item,
+            }) => match item {
+                Some(item) => self.set_log(item),
+                None => {
+                    logger::tail(&self.log_path(), level, lines, follow).await;
+                    Ok(())
                 }
             },
-            None => {
-                self.try_update(false).await.print_err();
+            Some(Command::Tip) => {
+                self.show_tip_code();
+                Ok(())
             }
+            Some(Command::Service { item }) => self.set_service(item),
+            Some(Command::Upgrade { check, manifest }) => self.upgrade(check, manifest).await,
+            Some(Command::Bye {
+                keep_images,
+                dry_run,
+                yes,
+            }) => self.self_remove(keep_images, dry_run, yes),
+            Some(Command::Completions { .. }) => Ok(()),
+            None => self.try_update(false).await,
         }
     }
 
-    async fn _run(&mut self) {
-        let config_path = self.working_space.clone() + "/wow.conf";
+    async fn _run(&mut self, force: bool) -> Result<()> {
+        let config_path = self.config_path.clone();
         self.config.load(&config_path);
-        if self.config.working {
-            println!("wow已在运行中");
-            return;
+        if self.config.working && !force {
+            return Err(Error::config(
+                "wow已在运行中\n如果上次异常退出，使用`wow run --force`启动",
+            ));
         }
 
         self.config.ask_stop = false;
         self.config.working = true;
-        let config_path = self.working_space.clone() + "/wow.conf";
-        let mut err = self.config.flush(&config_path);
-        if !err.is_empty() {
-            err.print_err();
+        self.config.set_pid(std::process::id());
+        if let Err(e) = self.config.flush(&config_path) {
             self.config = Config::default();
-            self.config.flush(&config_path);
-            return;
+            let _ = self.config.flush(&config_path);
+            return Err(e);
         }
 
+        logger::info(&format!("守护进程启动，PID {}", std::process::id()));
+        if let Err(e) = fs::write(self.dirs.pid_file(), std::process::id().to_string()) {
+            logger::warn(&format!("{}\n无法写入{}", e, self.dirs.pid_file()));
+        }
+
+        // 向状态栏等推送状态
+        let broadcaster = match Broadcaster::bind(&ipc::socket_path(&self.dirs.runtime)) {
+            Ok(b) => Some(b),
+            Err(e) => {
+                e.log();
+                None
+            }
+        };
+        let broadcast = |wow: &Self| {
+            if let Some(b) = &broadcaster {
+                b.send(
+                    Status::collect(&wow.config, &wow.dirs.data)
+                        .to_json()
+                        .to_string(),
+                );
+            }
+        };
+
+        let mut night = self.config.get_sun().night_at(SystemTime::now());
+        // 系统的暗色模式，有暗色壁纸时才检查
+        let mut system_dark: Option<bool> = None;
         loop {
+            let (update_at, error_at) =
+                (self.config.get_update_at(), self.config.get_last_error_at());
             self.try_update(false).await.print_err();
+            // 日出日落时切换壁纸，暂缓时在下次检查重试
+            let now_night = self.config.get_sun().night_at(SystemTime::now());
+            // 动态壁纸开启时不切换
+            if now_night.is_some() && now_night != night && !self.config.get_dynamic().enabled {
+                match self.switch_sun(now_night == Some(true)).await {
+                    Err(Error::NotDue) => {}
+                    res => {
+                        res.print_err();
+                        night = now_night;
+                    }
+                }
+            } else {
+                night = now_night;
+            }
+            // 只能在设置时选择暗色壁纸的后端，系统切换后重新设置
+            if !self.config.get_cur_dark_img().is_empty() {
+                let dark = setter::prefers_dark();
+                if system_dark.is_some_and(|d| d != dark) {
+                    update::follow_system_dark(&self.config, &self.dirs, dark).print_err();
+                }
+                system_dark = Some(dark);
+            }
+            broadcast(self);
+            self.notify_update(
+                self.config.get_update_at() != update_at,
+                self.config.get_last_error_at() != error_at,
+            );
 
-            sleep(Duration::from_secs(30)).await;
+            // 在昼夜切换与动态壁纸切换帧的时刻准时醒来
+            let now = SystemTime::now();
+            let dynamic = self.config.get_dynamic();
+            let next_frame = if dynamic.enabled {
+                dynamic.next_switch(self.config.get_sun().location, now)
+            } else {
+                None
+            };
+            let wait = [self.config.get_sun().next_transition(now), next_frame]
+                .into_iter()
+                .flatten()
+                .filter_map(|t| t.duration_since(now).ok())
+                .fold(CHECK_INTERVAL, Duration::min);
+            sleep(wait).await;
 
-            err = self.load_config();
-            if !err.is_empty() {
-                err.print_err();
+            if let Err(e) = self.load_config() {
                 self.config = Config::default();
-                self.config.flush(&config_path);
-                return;
+                let _ = self.config.flush(&config_path);
+                return Err(e);
             }
+            // 其它进程可能修改了配置或更新了壁纸
+            broadcast(self);
             if self.config.ask_stop {
-                println!("退出");
-                break;
+                logger::info("守护进程退出");
+                self.remove_pid_file();
+                return Ok(());
             }
         }
     }
 
-    pub fn _stop(&mut self) {
-        let config_path = self.working_space.clone() + "/wow.conf";
+    pub fn _stop(&mut self) -> Result<()> {
+        let config_path = self.config_path.clone();
         self.config.ask_stop = true;
         self.config.working = false;
-        let err = self.config.flush(&config_path);
-        if !err.is_empty() {
-            err.print_err();
+        self.config.set_pid(0);
+        self.remove_pid_file();
+        if let Err(e) = self.config.flush(&config_path) {
             self.config = Config::default();
-            self.config.flush(&config_path);
+            let _ = self.config.flush(&config_path);
+            return Err(e);
         }
+        Ok(())
     }
 
-    fn init_workspace(&mut self) -> ErrInfo {
-        match std::env::current_exe() {
-            Err(e) => ErrInfo::new(&format!("can't access current working space:\n{}", e)),
-            Ok(e) => {
-                let w_space = match e.parent() {
-                    Some(p) => match p.to_str() {
-                        Some(w) => w.to_string(),
-                        None => {
-                            return ErrInfo {
-                                info: "can't access current working space".to_string(),
-                            };
-                        }
-                    },
-                    None => {
-                        return ErrInfo {
-                            info: "can't access current working space".to_string(),
-                        };
-                    }
-                };
-                self.working_space = w_space;
-                ErrInfo::empty()
-            }
+    /// 只删除本进程写入的PID文件，
+    /// `wow stop`由守护进程自己删除
+    fn remove_pid_file(&self) {
+        let pid = fs::read_to_string(self.dirs.pid_file()).unwrap_or_default();
+        if pid.trim() == std::process::id().to_string() {
+            let _ = fs::remove_file(self.dirs.pid_file());
         }
     }
 
-    fn load_config(&mut self) -> ErrInfo {
+    fn init_workspace(&mut self) -> Result<()> {
+        self.dirs = Dirs::detect()?;
+        // 迁移失败时仍使用新目录
+        self.dirs.migrate().print_err();
+        Ok(())
+    }
+
+    fn load_config(&mut self) -> Result<()> {
         let mut _config = Config::default();
-        let conf_path = self.working_space.clone() + "/wow.conf";
-        if _config.load(&conf_path) {
+        let mut backup = None;
+        let res = if _config.load(&self.config_path) {
             self.config = _config;
+            Ok(())
+        } else if !std::path::Path::new(&self.config_path).exists() {
+            self.config.flush(&self.config_path)
         } else {
-            // 如果加载配置失败，
-            // 则重置配置
-            return self.config.flush(&conf_path);
+            // 无法解析时先备份，
+            // 无法备份则不覆盖原来的配置文件
+            let bak = format!("{}.bak", self.config_path);
+            match std::fs::rename(&self.config_path, &bak) {
+                Ok(_) => {
+                    backup = Some(bak);
+                    self.config.flush(&self.config_path)
+                }
+                Err(e) => Err(Error::io("无法备份配置文件", e)),
+            }
+        };
+        logger::init(&self.log_path(), self.config.get_log().clone());
+        if let Some(bak) = backup {
+            logger::warn(&format!(
+                "无法解析配置文件{}，已备份到{}并使用默认配置",
+                self.config_path, bak
+            ));
         }
-        ErrInfo::empty()
+        res
+    }
+
+    fn log_path(&self) -> String {
+        self.dirs.log_file()
     }
 
     fn print_help(&self) {
         println!("{}命令列表", color::Fg(color::LightGreen),);
+        for cmd in Cli::command().get_subcommands() {
+            let name = cmd.get_name();
+            println!(
+                "  {}{:<12}{}- {}",
+                color::Fg(color::Yellow),
+                name,
+                if name == "tip" {
+                    color::Fg(color::Rgb(53, 92, 125)).to_string()
+                } else {
+                    color::Fg(color::LightMagenta).to_string()
+                },
+                cmd.get_about().map(|a| a.to_string()).unwrap_or_default()
+            );
+        }
         println!(
-            "  {}help{}    - 显示此帮助信息",
-            color::Fg(color::Yellow),
-            color::Fg(color::LightMagenta)
-        );
-        println!(
-            "  {}run{}     - 开启自动更新",
-            color::Fg(color::Yellow),
-            color::Fg(color::LightMagenta)
-        );
-        println!(
-            "  {}stop{}    - 关闭自动更新",
-            color::Fg(color::Yellow),
-            color::Fg(color::LightMagenta)
-        );
-        println!(
-            "  {}update{}  - 更新壁纸",
-            color::Fg(color::Yellow),
-            color::Fg(color::LightMagenta)
-        );
-        println!(
-            "  {}freq{}    - 设置壁纸更新频率",
-            color::Fg(color::Yellow),
-            color::Fg(color::LightMagenta)
-        );
-        println!(
-            "  {}from{}    - 选择壁纸图片来源",
-            color::Fg(color::Yellow),
-            color::Fg(color::LightMagenta)
-        );
-        println!(
-            "  {}bye{}     - 卸载程序",
-            color::Fg(color::Yellow),
-            color::Fg(color::LightMagenta)
-        );
-        println!(
-            "  {}tip{}     - 赞赏",
-            color::Fg(color::Yellow),
-            color::Fg(color::Rgb(53, 92, 125))
+            "{}使用`wow <命令> --help`查看命令用法",
+            color::Fg(color::Reset)
         );
         println!();
 
@@ -213,169 +394,1049 @@ impl Wow {
         println!(
             "  图片来源: {}{}{}",
             color::Fg(color::LightCyan),
-            match self.config.get_url() {
-                "https://bing.img.run/rand_uhd.php" => "必应随机历史图片",
-                "https://bing.img.run/uhd.php" => "必应每日图片",
-                _ => "未知来源｜unknow",
-            },
+            Config::name_of(self.config.get_source()),
             color::Fg(color::Reset)
         );
         println!(
-            "  更新频率: {}每{}小时{}分{}",
+            "  更新频率: {}每{}{}",
             color::Fg(color::LightCyan),
-            self.config.get_freq() / 3600,
-            (self.config.get_freq() % 3600) / 60,
+            status::format_duration(self.config.get_freq().as_secs()),
+            color::Fg(color::Reset)
+        );
+        let log = self.config.get_log();
+        println!(
+            "  日志: {}{} | {:.2}MB x {} | 系统日志 {}{}",
+            color::Fg(color::LightCyan),
+            log.level.name(),
+            log.max_size as f64 / rule::MB,
+            log.max_files,
+            log.system
+                .to_possible_value()
+                .map(|v| v.get_name().to_string())
+                .unwrap_or_default(),
             color::Fg(color::Reset)
         );
         println!("{}", color::Fg(color::Reset));
     }
 
-    fn set_update_frequance(&mut self) -> ErrInfo {
+    fn set_update_frequance(
+        &mut self,
+        interval: Option<Duration>,
+        min: Option<Duration>,
+        max: Option<Duration>,
+    ) -> Result<()> {
+        if min.is_some() || max.is_some() {
+            self.set_freq_bounds(min, max)?;
+            if interval.is_none() {
+                return Ok(());
+            }
+        }
         let mut print_info = || {
             let time_now = SystemTime::now();
             let time_updated = self.config.get_update_at();
             match SystemTime::now().duration_since(time_updated) {
-                Ok(d) => {
-                    let freq_in_config = self.config.get_freq(); // 秒
-
-                    let dif = freq_in_config as isize - d.as_secs() as isize;
-                    let mut left_secs = freq_in_config - d.as_secs() as usize;
-                    if dif < 0 {
-                        left_secs = 0;
-                    }
-
-                    let hour = left_secs / 3600;
-                    let left_secs = left_secs % 3600;
-                    let min = left_secs / 60;
-                    let sec = left_secs % 60;
+                Ok(_) => {
                     println!(
-                        "当前更新频率:  {}每{}小时{}",
+                        "当前更新频率:  {}每{}{}",
                         color::Fg(color::LightBlue),
-                        self.config.get_freq() as f32 / 3600.0,
+                        status::format_duration(self.config.get_freq().as_secs()),
                         color::Fg(color::Reset)
                     );
                     println!(
-                        "下次更新在 {}{}小时{}分钟{}秒 {}后",
+                        "下次更新在 {}{} {}后",
                         color::Fg(color::LightBlue),
-                        hour,
-                        min,
-                        sec,
+                        status::format_duration(
+                            schedule::next_update(&self.config, time_now)
+                                .duration_since(time_now)
+                                .unwrap_or_default()
+                                .as_secs()
+                        ),
                         color::Fg(color::Reset)
                     );
-                    if dif < 0 {
+                    if schedule::overdue(&self.config, time_now) {
                         println!(":( 错过了更新\n将在下次更新时再次尝试");
                     }
+                    println!(
+                        "{}接下来的更新{}",
+                        color::Fg(color::LightGreen),
+                        color::Fg(color::Reset)
+                    );
+                    for t in schedule::upcoming(&self.config, time_now, 5) {
+                        println!(
+                            "  {}",
+                            chrono::DateTime::<chrono::Local>::from(t)
+                                .format("%Y-%m-%d %H:%M:%S %a")
+                        );
+                    }
+                    if self.config.get_dynamic().enabled {
+                        println!("动态壁纸已开启，自动更新暂停");
+                    }
+                    if self.config.get_sun().enabled {
+                        println!("不包括日出日落时的切换，使用`wow sun`查看");
+                    }
                     println!("使用`wow update`手动更新");
-                    ErrInfo::empty()
+                    Ok(())
                 }
                 Err(e) => {
                     self.config.set_update_at(time_now);
-                    self.config
-                        .flush(&(self.working_space.clone() + "/wow.conf"));
-                    ErrInfo::new(&format!(
+                    self.config.flush(&self.config_path)?;
+                    logger::warn(&format!(
                         "{}\n{}已重置时间{}",
                         e,
                         color::Fg(color::LightRed),
                         color::Fg(color::Reset)
-                    ))
+                    ));
+                    Ok(())
                 }
             }
         };
-        let mut print_help = || {
-            println!(
-                "{}设置壁纸更新频率{}",
-                color::Fg(color::LightGreen),
-                color::Fg(color::Reset),
-            );
-            println!(
-                "{}usage:{} wow freq x    --  x > 0",
-                color::Fg(color::LightRed),
-                color::Fg(color::Reset)
-            );
+        match interval {
+            Some(freq) => {
+                self._set_update_frequance(freq)?;
+                println!("设置成功");
+                Ok(())
+            }
+            None => print_info(),
+        }
+    }
+
+    fn _set_update_frequance(&mut self, f: Duration) -> Result<()> {
+        let (min, max) = self.config.get_freq_bounds();
+        if f < min || f > max {
+            return Err(Error::config(&format!(
+                "更新间隔{}不在允许的范围{}~{}内\n使用`wow freq --min <时长> --max <时长>`调整范围",
+                status::format_duration(f.as_secs()),
+                status::format_duration(min.as_secs()),
+                status::format_duration(max.as_secs())
+            )));
+        }
+        self.config.set_freq(f);
+        self.config.flush(&self.config_path)
+    }
+
+    fn set_freq_bounds(&mut self, min: Option<Duration>, max: Option<Duration>) -> Result<()> {
+        let (cur_min, cur_max) = self.config.get_freq_bounds();
+        let (min, max) = (min.unwrap_or(cur_min), max.unwrap_or(cur_max));
+        let (lower, upper) = config::FREQ_LIMIT;
+        if min < lower || max > upper {
+            return Err(Error::config(&format!(
+                "更新间隔的范围需要在{}~{}内",
+                status::format_duration(lower.as_secs()),
+                status::format_duration(upper.as_secs())
+            )));
+        }
+        if min > max {
+            return Err(Error::config("最短间隔不能大于最长间隔"));
+        }
+        self.config.set_freq_bounds(min, max);
+        println!(
+            "允许的更新间隔: {}~{}",
+            status::format_duration(min.as_secs()),
+            status::format_duration(max.as_secs())
+        );
+        let freq = self.config.get_freq();
+        if freq < min || freq > max {
+            self.config.set_freq(freq.clamp(min, max));
             println!(
-                "{}eg:{}    wow freq 2.5  --  设置更新频率为每2.5小时",
-                color::Fg(color::LightRed),
-                color::Fg(color::Reset)
+                "更新间隔已调整为{}",
+                status::format_duration(self.config.get_freq().as_secs())
             );
+        }
+        self.config.flush(&self.config_path)
+    }
 
-            println!();
+    fn set_img_souce(&mut self, source: Option<u8>, cmd: Vec<String>) -> Result<()> {
+        match source {
+            Some(s) => {
+                if s == config::CMD_SOURCE {
+                    if !cmd.is_empty() {
+                        self.config.set_source_cmd(&cmd.join(" "));
+                    } else if self.config.get_source_cmd().is_empty() {
+                        return Err(Error::config("需要指定命令\n使用`wow from 3 <命令>`设置"));
+                    }
+                }
+                self.config.set_url(s);
+                self.config.flush(&self.config_path)?;
+                println!("设置成功");
+                Ok(())
+            }
+            None => {
+                println!(
+                    "{}壁纸图片来源{}",
+                    color::Fg(color::LightGreen),
+                    color::Fg(color::Reset),
+                );
+                for s in 1..=config::CMD_SOURCE {
+                    println!(
+                        "{} {}  -> {}{}",
+                        if s == self.config.get_source() {
+                            "*"
+                        } else {
+                            " "
+                        },
+                        s,
+                        Config::name_of(s),
+                        color::Fg(color::Reset)
+                    );
+                }
+                if !self.config.get_source_cmd().is_empty() {
+                    println!("       {}", self.config.get_source_cmd());
+                }
+                println!("使用`wow from x`选择图片来源，`wow from 3 <命令>`使用自定义命令");
+                Ok(())
+            }
+        }
+    }
 
-            print_info()
+    /// 临时使用`source`更新壁纸，
+    /// 不修改配置中的图片来源
+    async fn update_from(&mut self, source: Option<u8>) -> Result<()> {
+        let Some(s) = source else {
+            return self.try_update(true).await;
         };
-        match self.args.get(2) {
-            Some(a) => {
-                if self.args.get(3).is_some() {
-                    print_help()
-                } else {
-                    let freq = match a.parse::<f32>() {
-                        Ok(f) => f,
-                        Err(e) => {
-                            println!("{}", e);
-                            return print_help();
-                        }
-                    };
-                    // FIXME: 限制范围
-                    if freq > 0.0 {
-                        let err = self._set_update_frequance(freq);
-                        if err.is_empty() {
-                            println!("设置成功");
+        let saved = self.config.get_source();
+        self.config.set_url(s);
+        let res = self.try_update(true).await;
+        self.config.set_url(saved);
+        self.config.flush(&self.config_path)?;
+        res
+    }
+
+    fn set_filter(&mut self, item: Option<FilterCmd>) -> Result<()> {
+        let mut f = self.config.get_filter().clone();
+        match item {
+            Some(FilterCmd::Brightness { value }) => f.brightness = value,
+            Some(FilterCmd::Blur { value }) => f.blur = value,
+            Some(FilterCmd::Gray { state }) => f.grayscale = state.is_on(),
+            Some(FilterCmd::Tint { color, strength }) => f.tint = color.0.map(|c| (c, strength)),
+            Some(FilterCmd::Vignette { value }) => f.vignette = value,
+            Some(FilterCmd::Dark { state, brightness }) => {
+                f.dark.enabled = state.is_on();
+                if let Some(b) = brightness {
+                    f.dark.brightness = b;
+                }
+            }
+            Some(FilterCmd::Reset) => f = Filter::default(),
+            None => {
+                println!(
+                    "{}当前滤镜{}",
+                    color::Fg(color::LightGreen),
+                    color::Fg(color::Reset)
+                );
+                println!(
+                    "  亮度 {} | 模糊 {} | 灰度 {} | 着色 {} | 暗角 {} | 暗色壁纸 {}",
+                    f.brightness,
+                    f.blur,
+                    if f.grayscale { "on" } else { "off" },
+                    match f.tint {
+                        Some((c, s)) => format!("#{:02x}{:02x}{:02x} {}", c[0], c[1], c[2], s),
+                        None => "off".to_string(),
+                    },
+                    f.vignette,
+                    if f.dark.enabled {
+                        format!("on {}", f.dark.brightness)
+                    } else {
+                        "off".to_string()
+                    },
+                );
+                println!("使用`wow filter --help`查看用法");
+                return Ok(());
+            }
+        }
+
+        self.config.set_filter(f);
+        self.config.flush(&self.config_path)?;
+        println!("设置成功，将在下次更新壁纸时生效");
+        Ok(())
+    }
+
+    fn set_monitor(&mut self, item: Option<MonitorCmd>, json: bool) -> Result<()> {
+        let mut m = self.config.get_monitor().clone();
+        match item {
+            Some(MonitorCmd::Mode { mode }) => m.mode = mode,
+            Some(MonitorCmd::Map { output, source }) => {
+                m.map.retain(|(n, _)| *n != output);
+                m.map.push((output, source));
+            }
+            Some(MonitorCmd::Unmap { output }) => m.map.retain(|(n, _)| *n != output),
+            None => {
+                let (outputs, err) = match Setter::detect(&self.dirs.exe).outputs() {
+                    Ok(outputs) => (outputs, Ok(())),
+                    Err(e) => (vec![], Err(e)),
+                };
+                let mode = m
+                    .mode
+                    .to_possible_value()
+                    .map(|v| v.get_name().to_string())
+                    .unwrap_or_default();
+                if json {
+                    let outputs: Vec<serde_json::Value> = outputs
+                        .iter()
+                        .map(|o| {
+                            serde_json::json!({
+                                "name": o.name,
+                                "id": o.id,
+                                "x": o.x,
+                                "y": o.y,
+                                "width": o.width,
+                                "height": o.height,
+                                "source": m.source_of(&o.name),
+                            })
+                        })
+                        .collect();
+                    println!(
+                        "{}",
+                        serde_json::json!({ "mode": mode, "outputs": outputs })
+                    );
+                    return err;
+                }
+                println!(
+                    "  当前模式: {}{}{}",
+                    color::Fg(color::LightCyan),
+                    mode,
+                    color::Fg(color::Reset)
+                );
+                println!("  显示器:");
+                for o in &outputs {
+                    println!(
+                        "    {}{}{} {}x{}+{}+{} 图片来源: {}",
+                        color::Fg(color::LightCyan),
+                        o.name,
+                        color::Fg(color::Reset),
+                        o.width,
+                        o.height,
+                        o.x,
+                        o.y,
+                        match m.source_of(&o.name) {
+                            Some(s) => s.to_string(),
+                            None => "全局".to_string(),
                         }
-                        err
+                    );
+                }
+                println!("使用`wow monitor --help`查看用法");
+                return err;
+            }
+        }
+
+        self.config.set_monitor(m);
+        self.config.flush(&self.config_path)?;
+        println!("设置成功，将在下次更新壁纸时生效");
+        Ok(())
+    }
+
+    fn set_lock(&mut self, item: Option<LockCmd>) -> Result<()> {
+        let mut l = self.config.get_lock().clone();
+        match item {
+            Some(LockCmd::On) => l.enabled = true,
+            Some(LockCmd::Off) => l.enabled = false,
+            Some(LockCmd::Blur { value }) => l.filter.blur = value,
+            Some(LockCmd::Brightness { value }) => l.filter.brightness = value,
+            Some(LockCmd::Copy { path }) => {
+                l.copy_to = if path == "off" { String::new() } else { path }
+            }
+            None => {
+                println!(
+                    "{}当前设置{}",
+                    color::Fg(color::LightGreen),
+                    color::Fg(color::Reset)
+                );
+                println!(
+                    "  {} | 模糊 {} | 亮度 {} | 复制到 {}",
+                    if l.enabled { "on" } else { "off" },
+                    l.filter.blur,
+                    l.filter.brightness,
+                    if l.copy_to.is_empty() {
+                        "无"
                     } else {
-                        print_help()
+                        &l.copy_to
                     }
+                );
+                println!("使用`wow lock --help`查看用法");
+                return Ok(());
+            }
+        }
+
+        let setter = Setter::detect(&self.dirs.exe);
+        if l.enabled && l.copy_to.is_empty() && !setter.supports_lock() {
+            return Err(setter.lock_unsupported());
+        }
+        self.config.set_lock(l);
+        self.config.flush(&self.config_path)?;
+        println!("设置成功，将在下次更新壁纸时生效");
+        Ok(())
+    }
+
+    fn set_rule(&mut self, item: Option<RuleCmd>) -> Result<()> {
+        let mut r = self.config.get_rule().clone();
+        match item {
+            Some(RuleCmd::Size { width, height }) => {
+                r.min_width = width;
+                r.min_height = height;
+            }
+            Some(RuleCmd::Ratio { min, max }) => {
+                if max > 0.0 && min > max {
+                    return Err(Error::config("最小宽高比不能大于最大宽高比"));
                 }
+                r.min_ratio = min;
+                r.max_ratio = max;
+            }
+            Some(RuleCmd::Orientation { value }) => r.orientation = value,
+            Some(RuleCmd::Maxsize { mb }) => r.max_size = (mb as f64 * rule::MB) as u64,
+            Some(RuleCmd::Retry { times }) => r.retry = times,
+            Some(RuleCmd::Reset) => r = ImageRule::default(),
+            None => {
+                println!(
+                    "{}当前规则{}",
+                    color::Fg(color::LightGreen),
+                    color::Fg(color::Reset)
+                );
+                println!(
+                    "  最小分辨率 {}x{} | 宽高比 {} | 方向 {} | 最大 {} | 重试 {}次",
+                    r.min_width,
+                    r.min_height,
+                    r.ratio_text(),
+                    r.orientation
+                        .to_possible_value()
+                        .map(|v| v.get_name().to_string())
+                        .unwrap_or_default(),
+                    if r.max_size > 0 {
+                        format!("{:.1}MB", r.max_size as f64 / rule::MB)
+                    } else {
+                        "不限".to_string()
+                    },
+                    r.retry
+                );
+                println!("使用`wow rule --help`查看用法");
+                return Ok(());
             }
-            None => print_help(),
         }
+
+        self.config.set_rule(r);
+        self.config.flush(&self.config_path)?;
+        println!("设置成功");
+        Ok(())
     }
 
-    fn _set_update_frequance(&mut self, f: f32) -> ErrInfo {
-        let f = f * 3600.0;
-        self.config.set_freq(f as usize);
-        self.config
-            .flush(&(self.working_space.clone() + "/wow.conf"))
+    fn set_palette(&mut self, item: Option<PaletteCmd>) -> Result<()> {
+        let mut p = self.config.get_palette().clone();
+        match item {
+            Some(PaletteCmd::On) => p.enabled = true,
+            Some(PaletteCmd::Off) => p.enabled = false,
+            Some(PaletteCmd::Now) => {
+                self.export_palette()?;
+                println!("配色已导出到{}", self.palette_dir());
+                return Ok(());
+            }
+            Some(PaletteCmd::Reload { cmd }) => {
+                p.reload_cmd = if cmd == ["off"] {
+                    String::new()
+                } else {
+                    cmd.join(" ")
+                };
+            }
+            None => {
+                println!(
+                    "  自动导出: {}{}{}",
+                    color::Fg(color::LightCyan),
+                    if p.enabled { "on" } else { "off" },
+                    color::Fg(color::Reset)
+                );
+                println!(
+                    "  重载命令: {}{}{}",
+                    color::Fg(color::LightCyan),
+                    if p.reload_cmd.is_empty() {
+                        "无"
+                    } else {
+                        &p.reload_cmd
+                    },
+                    color::Fg(color::Reset)
+                );
+                println!(
+                    "  导出目录: {}{}{}",
+                    color::Fg(color::LightCyan),
+                    self.palette_dir(),
+                    color::Fg(color::Reset)
+                );
+                println!("  json / colors.sh / Xresources / kitty / alacritty");
+                println!("使用`wow palette --help`查看用法");
+                return Ok(());
+            }
+        }
+
+        self.config.set_palette(p);
+        self.config.flush(&self.config_path)?;
+        println!("设置成功");
+        Ok(())
+    }
+
+    async fn set_hook(&mut self, item: Option<HookCmd>) -> Result<()> {
+        let mut h = self.config.get_hook().clone();
+        match item {
+            Some(HookCmd::Set { kind, cmd }) => {
+                h.set(
+                    kind,
+                    if cmd == ["off"] {
+                        String::new()
+                    } else {
+                        cmd.join(" ")
+                    },
+                );
+            }
+            Some(HookCmd::Timeout { secs }) => h.timeout = secs,
+            Some(HookCmd::Run { kind }) => {
+                return h
+                    .run(kind, self.config.get_cur_img(), self.config.get_cur_meta())
+                    .await;
+            }
+            None => {
+                for kind in HookKind::all() {
+                    let cmd = h.get(kind);
+                    println!(
+                        "  {}{:<14}{}{}",
+                        color::Fg(color::LightGreen),
+                        kind.name(),
+                        color::Fg(color::Reset),
+                        if cmd.is_empty() { "无" } else { cmd }
+                    );
+                }
+                println!("  超时: {}秒", h.timeout);
+                println!("使用`wow hook --help`查看用法");
+                return Ok(());
+            }
+        }
+
+        self.config.set_hook(h);
+        self.config.flush(&self.config_path)?;
+        println!("设置成功");
+        Ok(())
     }
 
-    fn set_img_souce(&mut self) -> ErrInfo {
-        let print_help = || {
+    async fn set_quiet(&mut self, item: Option<QuietCmd>) -> Result<()> {
+        let mut q = self.config.get_quiet().clone();
+        match item {
+            Some(QuietCmd::Add { window }) => {
+                if !q.hours.contains(&window) {
+                    q.hours.push(window);
+                }
+            }
+            Some(QuietCmd::Remove { window }) => {
+                if !q.hours.contains(&window) {
+                    return Err(Error::config(&format!("没有安静时段{}", window)));
+                }
+                q.hours.retain(|w| *w != window);
+            }
+            Some(QuietCmd::Clear) => q.hours.clear(),
+            Some(QuietCmd::Fullscreen { state }) => q.fullscreen = state.is_on(),
+            Some(QuietCmd::Inhibit { state }) => q.inhibit = state.is_on(),
+            Some(QuietCmd::Check { cmd }) => {
+                q.check = if cmd == ["off"] {
+                    String::new()
+                } else {
+                    cmd.join(" ")
+                }
+            }
+            Some(QuietCmd::Test) => {
+                match q.reason(SystemTime::now()).await {
+                    Some(reason) => println!("现在会暂缓更新: {}", reason),
+                    None => println!("现在不会暂缓更新"),
+                }
+                return Ok(());
+            }
+            None => {
+                println!(
+                    "{}当前设置{}",
+                    color::Fg(color::LightGreen),
+                    color::Fg(color::Reset)
+                );
+                let hours: Vec<String> = q.hours.iter().map(|w| w.to_string()).collect();
+                println!(
+                    "  安静时段: {}",
+                    if hours.is_empty() {
+                        "无".to_string()
+                    } else {
+                        hours.join(", ")
+                    }
+                );
+                let on_off = |b: bool| if b { "on" } else { "off" };
+                println!("  全屏时暂缓: {}", on_off(q.fullscreen));
+                println!("  阻止空闲时暂缓: {}", on_off(q.inhibit));
+                println!(
+                    "  检查命令: {}",
+                    if q.check.is_empty() { "无" } else { &q.check }
+                );
+                println!("使用`wow quiet --help`查看用法");
+                return Ok(());
+            }
+        }
+
+        self.config.set_quiet(q);
+        self.config.flush(&self.config_path)?;
+        println!("设置成功");
+        Ok(())
+    }
+
+    fn set_sun(&mut self, item: Option<SunCmd>) -> Result<()> {
+        let mut s = self.config.get_sun().clone();
+        match item {
+            Some(SunCmd::On) => {
+                if s.location.is_none() {
+                    return Err(Error::config(
+                        "请先使用`wow sun location <纬度> <经度>`设置位置",
+                    ));
+                }
+                s.enabled = true;
+            }
+            Some(SunCmd::Off) => s.enabled = false,
+            Some(SunCmd::Location { lat, lon }) => s.location = Some((lat, lon)),
+            Some(SunCmd::Offset { dawn, dusk }) => (s.dawn, s.dusk) = (dawn, dusk),
+            Some(SunCmd::Mode { mode }) => {
+                if mode == SunMode::Variant && !self.config.get_filter().dark.enabled {
+                    let mut f = self.config.get_filter().clone();
+                    f.dark.enabled = true;
+                    self.config.set_filter(f);
+                    println!("已开启暗色壁纸，将在下次更新壁纸时生成");
+                }
+                s.mode = mode;
+            }
+            Some(SunCmd::Source { day, night }) => {
+                if (day == config::CMD_SOURCE || night == config::CMD_SOURCE)
+                    && self.config.get_source_cmd().is_empty()
+                {
+                    return Err(Error::config(
+                        "未设置自定义命令\n使用`wow from 3 <命令>`设置",
+                    ));
+                }
+                (s.day_source, s.night_source) = (day, night);
+            }
+            None => {
+                self.print_sun(&s);
+                return Ok(());
+            }
+        }
+
+        self.config.set_sun(s);
+        self.config.flush(&self.config_path)?;
+        println!("设置成功");
+        Ok(())
+    }
+
+    fn print_sun(&self, s: &sun::SunConf) {
+        let time = |t: SystemTime| {
+            chrono::DateTime::<chrono::Local>::from(t)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        };
+        println!(
+            "{}当前设置{}",
+            color::Fg(color::LightGreen),
+            color::Fg(color::Reset)
+        );
+        println!("  {}", if s.enabled { "on" } else { "off" });
+        match s.location {
+            Some((lat, lon)) => println!("  位置: 纬度 {} | 经度 {}", lat, lon),
+            None => println!("  位置: 未设置"),
+        }
+        println!("  偏移: 日出 {}分钟 | 日落 {}分钟", s.dawn, s.dusk);
+        match s.mode {
+            SunMode::Source => println!(
+                "  模式: source | 白天 {} | 夜间 {}",
+                Config::name_of(s.day_source),
+                Config::name_of(s.night_source)
+            ),
+            SunMode::Variant => println!("  模式: variant"),
+        }
+        if let Some((lat, lon)) = s.location {
+            let now = SystemTime::now();
+            let today = (chrono::Local::now().date_naive()
+                - chrono::NaiveDate::from_ymd_opt(2000, 1, 1).unwrap_or_default())
+            .num_days();
+            match sun::sunrise_sunset(today, lat, lon) {
+                Some((rise, set)) => println!("  今天: 日出 {} | 日落 {}", time(rise), time(set)),
+                None => println!("  今天: 极昼或极夜"),
+            }
+            if let Some(night) = s.night_at(now) {
+                println!("  现在: {}", if night { "夜间" } else { "白天" });
+            }
+            if let Some(t) = s.next_transition(now) {
+                println!("  下次切换: {}", time(t));
+            }
+        }
+        println!("使用`wow sun --help`查看用法");
+    }
+
+    fn set_dynamic(&mut self, item: Option<DynamicCmd>) -> Result<()> {
+        let location = self.config.get_sun().location;
+        let mut d = self.config.get_dynamic().clone();
+        match item {
+            Some(DynamicCmd::Import { path }) => {
+                d = DynamicConf::import(&path, &self.dirs.data, location)?;
+                println!("已导入{}帧", d.frames.len());
+            }
+            Some(DynamicCmd::Export { path }) => {
+                let xml = d.to_gnome_xml(location, chrono::Local::now().date_naive())?;
+                fs::write(&path, xml).map_err(|e| Error::io(&format!("无法写入{}", path), e))?;
+                println!("已导出到{}", path);
+                return Ok(());
+            }
+            Some(DynamicCmd::On) => {
+                if d.frames.is_empty() {
+                    return Err(Error::config(
+                        "还没有动态壁纸\n使用`wow dynamic import <路径>`导入",
+                    ));
+                }
+                d.enabled = true;
+            }
+            Some(DynamicCmd::Off) => {
+                d.enabled = false;
+                d.applied.clear();
+            }
+            None => {
+                self.print_dynamic(&d);
+                return Ok(());
+            }
+        }
+
+        self.config.set_dynamic(d);
+        self.config.flush(&self.config_path)?;
+        println!("设置成功");
+        Ok(())
+    }
+
+    fn print_dynamic(&self, d: &DynamicConf) {
+        let location = self.config.get_sun().location;
+        let now = SystemTime::now();
+        let today = chrono::Local::now().date_naive();
+        println!(
+            "{}当前设置{}",
+            color::Fg(color::LightGreen),
+            color::Fg(color::Reset)
+        );
+        if d.frames.is_empty() {
+            println!("  无");
+            println!("使用`wow dynamic --help`查看用法");
+            return;
+        }
+        println!("  {} | {}", if d.enabled { "on" } else { "off" }, d.name);
+        let current = d.frame_at(location, now);
+        let width = d
+            .frames
+            .iter()
+            .map(|f| f.at.to_string().len())
+            .max()
+            .unwrap_or(0)
+            .max(14);
+        for f in &d.frames {
+            let today_at =
+                f.at.resolve(today, location)
+                    .map(|t| {
+                        chrono::DateTime::<chrono::Local>::from(t)
+                            .format("%H:%M")
+                            .to_string()
+                    })
+                    .unwrap_or("--:--".to_string());
             println!(
-                "{}设置壁纸图片来源{}",
-                color::Fg(color::LightGreen),
+                "  {}{:<width$}{} {} {}{}",
+                color::Fg(color::LightBlue),
+                f.at.to_string(),
                 color::Fg(color::Reset),
+                today_at,
+                f.image,
+                if current.is_some_and(|c| std::ptr::eq(c, f)) {
+                    " <-"
+                } else {
+                    ""
+                }
             );
+        }
+        if let Some(t) = d.next_switch(location, now) {
             println!(
-                "{}usage:{} wow from x    --  x = 1 or 2",
-                color::Fg(color::LightRed),
-                color::Fg(color::Reset)
+                "  下次切换: {}",
+                chrono::DateTime::<chrono::Local>::from(t).format("%Y-%m-%d %H:%M:%S")
             );
-            println!("1  -> 必应随机历史图片");
-            println!("2  -> 必应每日图片");
+        }
+        println!("使用`wow dynamic --help`查看用法");
+    }
+
+    /// 设置动态壁纸当前的帧，
+    /// 没有变化或暂缓时返回`Error::NotDue`
+    async fn apply_frame(&mut self) -> Result<()> {
+        let now = SystemTime::now();
+        let mut d = self.config.get_dynamic().clone();
+        let Some(frame) = d.frame_at(self.config.get_sun().location, now) else {
+            return Err(Error::NotDue);
         };
-        match self.args.get(2) {
-            Some(s) => {
-                if self.args.get(3).is_some() {
-                    print_help();
-                    ErrInfo::empty()
+        let (at, image) = (frame.at.to_string(), frame.image.clone());
+        if image == d.applied {
+            return Err(Error::NotDue);
+        }
+        if let Some(reason) = self.config.get_quiet().reason(now).await {
+            logger::debug(&format!("{}，暂缓切换动态壁纸", reason));
+            return Err(Error::NotDue);
+        }
+        let applied = update::apply_image(&self.config, &self.dirs, &image).await?;
+        // 切换帧不算作自动更新
+        let update_at = self.config.get_update_at();
+        applied.store(&mut self.config);
+        self.config.set_update_at(update_at);
+        logger::info(&format!("动态壁纸切换到{}: {}", at, image));
+        d.applied = image;
+        self.config.set_dynamic(d);
+        self.config.flush(&self.config_path)
+    }
+
+    /// 昼夜切换，暂缓更新时返回`Error::NotDue`
+    async fn switch_sun(&mut self, night: bool) -> Result<()> {
+        if let Some(reason) = self.config.get_quiet().reason(SystemTime::now()).await {
+            logger::debug(&format!("{}，暂缓昼夜切换", reason));
+            return Err(Error::NotDue);
+        }
+        logger::info(if night {
+            "日落，切换到夜间壁纸"
+        } else {
+            "日出，切换到白天壁纸"
+        });
+        let s = self.config.get_sun();
+        let done = match s.mode {
+            SunMode::Variant => update::apply_variant(&self.config, &self.dirs, night)?,
+            // 白天与夜间来源相同时不需要重新获取
+            SunMode::Source => s.day_source == s.night_source,
+        };
+        if done {
+            return Ok(());
+        }
+        self.try_update(true).await
+    }
+
+    fn set_net(&mut self, item: Option<NetCmd>) -> Result<()> {
+        let mut n = self.config.get_net().clone();
+        match item {
+            Some(NetCmd::Proxy { url }) => n.proxy = if url == "env" { String::new() } else { url },
+            Some(NetCmd::Timeout { connect, read }) => {
+                n.connect_timeout = connect;
+                n.read_timeout = read;
+            }
+            Some(NetCmd::Ua { ua }) => {
+                n.user_agent = if ua == ["default"] {
+                    String::new()
                 } else {
-                    let s = match s.parse::<u8>() {
-                        Ok(s) => s,
-                        Err(e) => {
-                            // println!("{}", e);
-                            print_help();
-                            return ErrInfo::new(&format!("{}", e));
-                        }
-                    };
-                    self.config.set_url(s);
-                    self.config
-                        .flush(&(self.working_space.clone() + "/wow.conf"))
+                    ua.join(" ")
                 }
             }
+            Some(NetCmd::Ca { path }) => {
+                if path == "off" {
+                    n.ca_certs.clear();
+                } else {
+                    let path = fs::canonicalize(&path)
+                        .map_err(|e| Error::io(&format!("找不到证书{}", path), e))?
+                        .to_string_lossy()
+                        .to_string();
+                    if !n.ca_certs.contains(&path) {
+                        n.ca_certs.push(path);
+                    }
+                }
+            }
+            Some(NetCmd::Redirects { max }) => n.max_redirects = max,
+            Some(NetCmd::Reset) => n = NetConf::default(),
             None => {
-                print_help();
-                ErrInfo::empty()
+                println!(
+                    "{}当前设置{}",
+                    color::Fg(color::LightGreen),
+                    color::Fg(color::Reset)
+                );
+                println!(
+                    "  代理: {}",
+                    match n.proxy.as_str() {
+                        "" => "读取环境变量",
+                        net::PROXY_OFF => "不使用",
+                        url => url,
+                    }
+                );
+                println!(
+                    "  超时: 连接 {} | 读取 {}",
+                    status::format_duration(n.connect_timeout.as_secs()),
+                    status::format_duration(n.read_timeout.as_secs())
+                );
+                println!("  User-Agent: {}", n.user_agent());
+                println!(
+                    "  根证书: {}",
+                    if n.ca_certs.is_empty() {
+                        "无".to_string()
+                    } else {
+                        n.ca_certs.join(", ")
+                    }
+                );
+                println!("  最多重定向: {}次", n.max_redirects);
+                println!("使用`wow net --help`查看用法");
+                return Ok(());
+            }
+        }
+
+        // 守护进程在下次更新时使用新的设置
+        n.build()?;
+        self.config.set_net(n);
+        self.config.flush(&self.config_path)?;
+        println!("设置成功");
+        Ok(())
+    }
+
+    fn set_mirror(&mut self, item: Option<MirrorCmd>) -> Result<()> {
+        let mut m = self.config.get_mirror().clone();
+        match item {
+            Some(MirrorCmd::Add { source, url }) => {
+                reqwest::Url::parse(&url)
+                    .map_err(|e| Error::config(&format!("地址{}无效: {}", url, e)))?;
+                if !m.add(source, &url) {
+                    return Err(Error::config(&format!("来源{}已有镜像{}", source, url)));
+                }
+            }
+            Some(MirrorCmd::Remove { source, url }) => {
+                if m.mirrors.iter().filter(|x| x.source == source).count() == 1 {
+                    return Err(Error::config(&format!("来源{}至少需要一个镜像", source)));
+                }
+                if !m.remove(source, &url) {
+                    return Err(Error::config(&format!("来源{}没有镜像{}", source, url)));
+                }
+            }
+            Some(MirrorCmd::Reset) => m = MirrorConf::default(),
+            None => {
+                self.print_mirrors();
+                println!("使用`wow mirror --help`查看用法，`wow doctor`检查是否可用");
+                return Ok(());
+            }
+        }
+
+        self.config.set_mirror(m);
+        self.config.flush(&self.config_path)?;
+        println!("设置成功");
+        Ok(())
+    }
+
+    fn print_mirrors(&self) {
+        let now = SystemTime::now();
+        for source in mirror::sources() {
+            println!(
+                "{}{}. {}{}",
+                color::Fg(color::LightGreen),
+                source,
+                Config::name_of(source),
+                color::Fg(color::Reset)
+            );
+            for (i, m) in self
+                .config
+                .get_mirror()
+                .ordered(source, now)
+                .into_iter()
+                .enumerate()
+            {
+                let mut stat = if m.ok + m.failed == 0 {
+                    "尚未使用".to_string()
+                } else if m.ok == 0 {
+                    format!("成功 0/{}", m.failed)
+                } else {
+                    format!(
+                        "成功 {}/{} | 平均延迟 {}ms",
+                        m.ok,
+                        m.ok + m.failed,
+                        m.latency.as_millis()
+                    )
+                };
+                if m.cooling(now) {
+                    stat += &format!(
+                        " | {}冷却至{}{}",
+                        color::Fg(color::Yellow),
+                        chrono::DateTime::<chrono::Local>::from(m.cooldown_until)
+                            .format("%H:%M:%S"),
+                        color::Fg(color::Reset)
+                    );
+                }
+                println!("  {}) {}\n     {}", i + 1, m.url, stat);
+            }
+        }
+    }
+
+    /// 同时请求所有镜像，结果计入统计
+    async fn doctor(&mut self) -> Result<()> {
+        let client = net::client(self.config.get_net())?;
+        let mirrors = self.config.get_mirror().mirrors.clone();
+        println!("正在检查{}个镜像...", mirrors.len());
+        let results =
+            futures_util::future::join_all(mirrors.iter().map(|m| mirror::probe(&client, &m.url)))
+                .await;
+        let mut failed = 0;
+        let mut records = vec![];
+        for (m, res) in mirrors.iter().zip(results) {
+            records.push((m.url.clone(), res.as_ref().ok().copied()));
+            match res {
+                Ok(latency) => println!(
+                    "{}✓{} [{}] {} {}ms",
+                    color::Fg(color::LightGreen),
+                    color::Fg(color::Reset),
+                    m.source,
+                    m.url,
+                    latency.as_millis()
+                ),
+                Err(e) => {
+                    failed += 1;
+                    println!(
+                        "{}✗{} [{}] {} {}",
+                        color::Fg(color::LightRed),
+                        color::Fg(color::Reset),
+                        m.source,
+                        m.url,
+                        e
+                    );
+                }
             }
         }
+
+        // 重新读取，避免覆盖守护进程在检查期间写入的配置
+        let mut latest = Config::default();
+        if latest.load(&self.config_path) {
+            self.config = latest;
+        }
+        let mut m = self.config.get_mirror().clone();
+        for (url, latency) in records {
+            m.record(&url, latency);
+        }
+        self.config.set_mirror(m);
+        self.config.flush(&self.config_path)?;
+        if failed > 0 {
+            return Err(Error::Network {
+                msg: format!("{}个镜像不可用", failed),
+                source: None,
+            });
+        }
+        Ok(())
+    }
+
+    fn set_log(&mut self, item: LogCmd) -> Result<()> {
+        let mut l = self.config.get_log().clone();
+        match item {
+            LogCmd::SetLevel { level } => l.level = level,
+            LogCmd::Rotate { mb, files } => {
+                l.max_size = (mb as f64 * rule::MB) as u64;
+                l.max_files = files;
+            }
+            LogCmd::System { target } => l.system = target,
+        }
+
+        self.config.set_log(l);
+        self.config.flush(&self.config_path)?;
+        println!("设置成功");
+        Ok(())
+    }
+
+    fn palette_dir(&self) -> String {
+        palette::dir(&self.dirs.cache)
+    }
+
+    /// 从当前壁纸导出配色
+    fn export_palette(&self) -> Result<()> {
+        let img = self.config.get_cur_img();
+        if img.is_empty() {
+            return Err(Error::config("还没有壁纸\n使用`wow update`更新壁纸"));
+        }
+        update::export_palette(&self.config, &self.dirs.cache, img)
     }
 
     fn show_tip_code(&self) {
@@ -425,127 +1486,299 @@ impl Wow {
         );
     }
 
-    fn self_remove(&self) {
-        println!("TODO");
-        println!("请手动删除{}", self.working_space);
-    }
-
-    async fn try_update(&mut self, anyway: bool) -> ErrInfo {
-        println!("准备更新");
-        // 读取配置
-        // 检查更新时间
-        let time_now = SystemTime::now();
-        let time_update = self.config.get_update_at();
-        match time_now.duration_since(time_update) {
-            Ok(d) => {
-                if anyway || d >= Duration::from_secs(self.config.get_freq() as u64) {
-                    println!("更新中...");
-                    let res = self.update_paper(time_now).await;
-                    if res.is_empty() {
-                        // sync_update_time(time_now);
-                        self.config.set_update_at(time_now);
-                        self.config
-                            .flush(&(self.working_space.clone() + "/wow.conf"));
-                        return ErrInfo::new("壁纸已更新");
-                    }
-                    res
-                } else {
-                    ErrInfo::new("未到更新时间\n使用`wow update`手动更新\n使用`wow help`获取帮助")
+    async fn set_notify(&mut self, item: Option<NotifyCmd>) -> Result<()> {
+        let mut n = self.config.get_notify().clone();
+        match item {
+            Some(NotifyCmd::On) => n.enabled = true,
+            Some(NotifyCmd::Off) => n.enabled = false,
+            Some(NotifyCmd::Error { state }) => n.on_error = state.is_on(),
+            Some(NotifyCmd::Test) => {
+                match self.update_notice().send().await {
+                    Ok(Some(action)) => println!("点击了{}", action),
+                    Ok(None) => println!("通知已发送"),
+                    Err(e) => return Err(e),
                 }
+                return Ok(());
             }
-            Err(e) => {
-                self.config.set_update_at(time_now);
-                self.config
-                    .flush(&(self.working_space.clone() + "/wow.conf"));
-                ErrInfo::new(&format!("{}\n已重置时间", e))
+            None => {
+                println!(
+                    "{}当前设置{}",
+                    color::Fg(color::LightGreen),
+                    color::Fg(color::Reset)
+                );
+                println!(
+                    "  更新后 {} | 失败时 {}",
+                    if n.enabled { "on" } else { "off" },
+                    if n.on_error { "on" } else { "off" }
+                );
+                println!("使用`wow notify --help`查看用法");
+                return Ok(());
             }
         }
+
+        self.config.set_notify(n);
+        self.config.flush(&self.config_path)?;
+        println!("设置成功");
+        Ok(())
     }
 
-    /// 根据配置的图片源，尝试更新图片
-    /// 返回失败的原因
-    ///
-    /// `当前支持`
-    /// - 必应每日图片
-    /// - 必应随机历史图片
-    async fn update_paper(&mut self, t: SystemTime) -> ErrInfo {
-        let client = reqwest::Client::new();
+    /// 以当前壁纸生成通知
+    fn update_notice(&self) -> Notice {
+        let meta = self.config.get_cur_meta();
+        let thumb = self.dirs.cache.clone() + "/notify-thumb.jpg";
+        let icon = if notify::thumbnail(self.config.get_cur_img(), &thumb).is_ok() {
+            thumb
+        } else {
+            String::new()
+        };
+        Notice {
+            summary: if meta.title.is_empty() {
+                "壁纸已更新".to_string()
+            } else {
+                meta.title.clone()
+            },
+            body: if meta.copyright.is_empty() {
+                Config::name_of(self.config.get_source()).to_string()
+            } else {
+                meta.copyright.clone()
+            },
+            icon,
+            actions: true,
+            critical: false,
+        }
+    }
 
-        let img_url = self.config.get_url();
-        let real_url = match client.get(img_url).send().await {
-            Err(e) => {
-                return ErrInfo {
-                    info: format!("fetch image error:\n{}", e),
-                };
+    /// 守护进程更新壁纸或失败后在后台发送通知，
+    /// 点击按钮时执行对应的`wow next`或`wow like`
+    fn notify_update(&self, updated: bool, failed: bool) {
+        let n = self.config.get_notify();
+        let notice = if updated && n.enabled {
+            self.update_notice()
+        } else if failed && n.on_error {
+            Notice {
+                summary: "壁纸更新失败".to_string(),
+                body: self.config.get_last_error().to_string(),
+                icon: String::new(),
+                actions: false,
+                critical: true,
             }
-            Ok(resp) => resp.url().clone().to_string(),
+        } else {
+            return;
         };
+        let config_path = self.config_path.clone();
+        tokio::spawn(async move {
+            let args = match notice.send().await {
+                Ok(Some(action)) => notify::action_args(&action, &config_path),
+                Ok(None) => None,
+                Err(e) => return e.log(),
+            };
+            let (Some(args), Ok(exe)) = (args, std::env::current_exe()) else {
+                return;
+            };
+            let _ = tokio::process::Command::new(exe).args(args).status().await;
+        });
+    }
 
-        let stamp = t
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap_or(Duration::from_secs(114514))
-            .as_secs()
-            .to_string();
-
-        let save_path = self.working_space.clone() + "/" + &stamp + ".jpg";
-        match client.get(real_url).send().await {
-            Err(e) => ErrInfo::new(&format!("fetch image error:\n{}", e)),
-            Ok(resp) => {
-                let mut fs = match File::create(&save_path) {
-                    Ok(f) => f,
-                    Err(e) => {
-                        return ErrInfo {
-                            info: format!("can't open the file to save image:\n{}", e),
-                        };
-                    }
+    fn set_service(&self, item: ServiceCmd) -> Result<()> {
+        match item {
+            ServiceCmd::Install { timer } => {
+                if Status::collect(&self.config, &self.dirs.data).running {
+                    return Err(Error::config(
+                        "wow已在运行中\n先使用`wow stop`停止，再由服务启动",
+                    ));
+                }
+                let exe = std::env::current_exe()
+                    .map_err(|e| Error::io("can't access current working space", e))?;
+                let mut args = vec![];
+                if self.config_path != self.dirs.config_file() {
+                    let path = std::path::absolute(&self.config_path)
+                        .map_err(|e| Error::io("无法获取配置文件路径", e))?;
+                    args = vec!["--config".to_string(), path.to_string_lossy().to_string()];
+                }
+                let envs = std::env::var("WOW_HOME")
+                    .map(|h| vec![("WOW_HOME".to_string(), h)])
+                    .unwrap_or_default();
+                let service = Service {
+                    exe: exe.to_string_lossy().to_string(),
+                    args,
+                    envs,
+                    timer,
                 };
-                match resp.bytes().await {
-                    Err(e) => ErrInfo::new(&format!("error when read image data:\n{}", e)),
-                    Ok(data) => match fs.write_all(&data) {
-                        Ok(_) => {
-                            self.delete_pre_img();
-                            self.config.set_cur_img(&save_path);
-                            match Command::new(self.working_space.clone() + "/updater")
-                                .arg(save_path)
-                                .status()
-                            {
-                                Ok(_) => ErrInfo::empty(),
-                                Err(e) => ErrInfo::new(&format!("{}\n设置壁纸失败", e)),
-                            }
-                        }
-                        Err(e) => ErrInfo::new(&format!("error when saving image:\n{}", e)),
-                    },
+                for path in service.install()? {
+                    println!("已写入{}", path);
+                }
+                println!("服务已启用");
+                Ok(())
+            }
+            ServiceCmd::Uninstall => {
+                let removed = service::uninstall()?;
+                if removed.is_empty() {
+                    println!("服务未安装");
+                }
+                for path in removed {
+                    println!("已删除{}", path);
+                }
+                Ok(())
+            }
+            ServiceCmd::Status => {
+                let status = service::status()?;
+                if status.is_empty() {
+                    println!("服务未安装\n使用`wow service install`安装");
+                }
+                for (path, state) in status {
+                    println!(
+                        "  {}{}{} {}",
+                        color::Fg(color::LightCyan),
+                        path,
+                        color::Fg(color::Reset),
+                        state
+                    );
                 }
+                Ok(())
             }
         }
     }
 
-    fn delete_pre_img(&self) {
-        if fs::remove_file(self.config.get_cur_img()).is_err() {}
-    }
-}
+    async fn upgrade(&self, check: bool, manifest: Option<String>) -> Result<()> {
+        let url = manifest
+            .or(std::env::var(upgrade::MANIFEST_ENV).ok())
+            .unwrap_or(upgrade::MANIFEST_URL.to_string());
+        let client = net::client(self.config.get_net())?;
+        let release = upgrade::fetch_manifest(&client, &url).await?;
+        let current = env!("CARGO_PKG_VERSION");
+        println!(
+            "当前版本 {}{}{}，最新版本 {}{}{}",
+            color::Fg(color::LightCyan),
+            current,
+            color::Fg(color::Reset),
+            color::Fg(color::LightCyan),
+            release.version,
+            color::Fg(color::Reset)
+        );
+        if !upgrade::newer(&release.version, current) {
+            println!("已是最新版本");
+            return Ok(());
+        }
+        if check {
+            println!("使用`wow upgrade`升级");
+            return Ok(());
+        }
 
-pub struct ErrInfo {
-    info: String,
-}
-impl ErrInfo {
-    pub fn empty() -> Self {
-        ErrInfo {
-            info: String::new(),
+        let exe = std::env::current_exe()
+            .map_err(|e| Error::io("can't access current working space", e))?;
+        let exe = exe.to_string_lossy().to_string();
+        upgrade::install(&client, &release, &exe).await?;
+        logger::info(&format!("已升级到{}", release.version));
+        let status = Status::collect(&self.config, &self.dirs.data);
+        if status.running {
+            upgrade::restart_daemon(&exe, &self.config_path, status.pid)?;
+            println!("已重启守护进程");
         }
+        Ok(())
     }
-    pub fn new(info: &str) -> Self {
-        Self {
-            info: info.to_string(),
+
+    /// 将当前壁纸复制到收藏目录
+    fn like(&self) -> Result<()> {
+        let img = self.config.get_cur_img();
+        if img.is_empty() {
+            return Err(Error::config("还没有壁纸\n使用`wow update`更新壁纸"));
+        }
+        let dir = self.dirs.data.clone() + "/liked";
+        let title = &self.config.get_cur_meta().title;
+        let name = if title.is_empty() {
+            std::path::Path::new(img)
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default()
+        } else {
+            title.replace(['/', ' '], "_")
+        };
+        let dst = format!("{}/{}.jpg", dir, name);
+        match fs::create_dir_all(&dir).and_then(|_| fs::copy(img, &dst)) {
+            Ok(_) => {
+                println!("已收藏到{}", dst);
+                Ok(())
+            }
+            Err(e) => Err(Error::io("收藏失败", e)),
         }
     }
-    fn print_err(&self) {
-        if self.info.is_empty() {
-            return;
+
+    fn self_remove(&self, keep_images: bool, dry_run: bool, yes: bool) -> Result<()> {
+        let status = Status::collect(&self.config, &self.dirs.data);
+        let pid = if status.running {
+            Some(status.pid)
+        } else {
+            None
+        };
+        let plan = bye::Plan::new(&self.dirs, &self.config_path, pid, keep_images)?;
+        println!(
+            "{}将要执行{}",
+            color::Fg(color::LightGreen),
+            color::Fg(color::Reset)
+        );
+        plan.print();
+        if dry_run {
+            return Ok(());
         }
-        println!("{}", self.info);
+        if !yes {
+            print!("确认卸载？[y/N] ");
+            let _ = std::io::stdout().flush();
+            let mut answer = String::new();
+            let _ = std::io::stdin().read_line(&mut answer);
+            if !matches!(answer.trim(), "y" | "Y" | "yes") {
+                println!("已取消");
+                return Ok(());
+            }
+        }
+        plan.execute()?;
+        println!("已卸载，再见 ωoω");
+        Ok(())
     }
-    fn is_empty(&self) -> bool {
-        self.info.is_empty()
+
+    async fn try_update(&mut self, anyway: bool) -> Result<()> {
+        // 开启动态壁纸时只切换帧
+        if !anyway && self.config.get_dynamic().enabled {
+            return self.apply_frame().await;
+        }
+        logger::debug("准备更新");
+        // 读取配置
+        // 检查更新时间
+        let time_now = SystemTime::now();
+        let time_update = self.config.get_update_at();
+        match time_now.duration_since(time_update) {
+            Ok(d) => {
+                if anyway || d >= self.config.get_freq() {
+                    // 手动更新不受影响，
+                    // 暂缓的更新在条件解除后的下一次检查执行
+                    if !anyway && let Some(reason) = self.config.get_quiet().reason(time_now).await
+                    {
+                        logger::debug(&format!("{}，暂缓更新", reason));
+                        return Err(Error::NotDue);
+                    }
+                    logger::info("更新中...");
+                    let res = match update::update_once(&mut self.config, &self.dirs).await {
+                        Ok(applied) => {
+                            applied.store(&mut self.config);
+                            logger::info(&format!("壁纸已更新: {}", applied.image));
+                            Ok(())
+                        }
+                        Err(e) => {
+                            self.config.set_last_error(&e.report(), time_now);
+                            Err(e)
+                        }
+                    };
+                    self.config.flush(&self.config_path)?;
+                    res
+                } else {
+                    Err(Error::NotDue)
+                }
+            }
+            Err(e) => {
+                self.config.set_update_at(time_now);
+                self.config.flush(&self.config_path)?;
+                logger::warn(&format!("{}\n已重置时间", e));
+                Ok(())
+            }
+        }
     }
 }
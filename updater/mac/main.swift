@@ -3,6 +3,30 @@ import Foundation
 
 // Function to set wallpaper for all screens
 func setWallpaperForAllScreens(imagePath: String) {
+    setWallpaper(imagePath: imagePath, screens: NSScreen.screens)
+}
+
+// The display ID, unique even when several screens share the same name
+func displayID(_ screen: NSScreen) -> String {
+    let number = screen.deviceDescription[NSDeviceDescriptionKey("NSScreenNumber")] as? NSNumber
+    return number.map { "\($0.uint32Value)" } ?? ""
+}
+
+// Function to set wallpaper for the screen with the given display ID,
+// older versions of wow pass the screen name instead
+func setWallpaperForScreen(id: String, imagePath: String) {
+    var screens = NSScreen.screens.filter { displayID($0) == id }
+    if screens.isEmpty {
+        screens = NSScreen.screens.filter { $0.localizedName == id }
+    }
+    if screens.isEmpty {
+        print("Error: No screen with ID \(id).")
+        exit(1)
+    }
+    setWallpaper(imagePath: imagePath, screens: screens)
+}
+
+func setWallpaper(imagePath: String, screens: [NSScreen]) {
     let imageUrl = URL(fileURLWithPath: imagePath)
 
     // Check if the file exists
@@ -12,7 +36,6 @@ func setWallpaperForAllScreens(imagePath: String) {
     }
 
     let workspace = NSWorkspace.shared
-    let screens = NSScreen.screens
 
     if screens.isEmpty {
         print("Error: No screens found.")
@@ -50,13 +73,38 @@ func setWallpaperForAllScreens(imagePath: String) {
     }
 }
 
+// Print one line per screen: name, x, y, width, height in pixels, display ID.
+// The origin is the top-left corner of the main screen.
+func listScreens() {
+    guard let main = NSScreen.screens.first else {
+        print("Error: No screens found.")
+        exit(1)
+    }
+    for screen in NSScreen.screens {
+        let scale = screen.backingScaleFactor
+        let frame = screen.frame
+        let top = main.frame.maxY - frame.maxY
+        print("\(screen.localizedName)\t\(Int(frame.minX * scale))\t\(Int(top * scale))\t\(Int(frame.width * scale))\t\(Int(frame.height * scale))\t\(displayID(screen))")
+    }
+}
+
 // --- Main execution ---
-if CommandLine.arguments.count < 2 {
+func printUsage() {
     print("Usage: wallpaper <image_path>")
+    print("       wallpaper --screen <display_id> <image_path>")
+    print("       wallpaper --list")
     print("Example: wallpaper /path/to/your/image.jpg")
-    exit(1)
 }
 
-let imagePathArgument = CommandLine.arguments[1]
+let arguments = CommandLine.arguments
 
-setWallpaperForAllScreens(imagePath: imagePathArgument)
+if arguments.count == 2 && arguments[1] == "--list" {
+    listScreens()
+} else if arguments.count == 4 && arguments[1] == "--screen" {
+    setWallpaperForScreen(id: arguments[2], imagePath: arguments[3])
+} else if arguments.count == 2 {
+    setWallpaperForAllScreens(imagePath: arguments[1])
+} else {
+    printUsage()
+    exit(1)
+}